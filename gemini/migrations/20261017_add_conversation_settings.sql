@@ -0,0 +1,10 @@
+PRAGMA foreign_keys = ON;
+-- ----------------------------
+-- add the model, system instruction and generation config used by a conversation
+-- empty for conversations created before, which use the current profile when loaded
+-- ----------------------------
+ALTER TABLE "gemini_conversation" ADD COLUMN "conversation_model" TEXT;
+ALTER TABLE "gemini_conversation" ADD COLUMN "conversation_system_instruction" TEXT;
+ALTER TABLE "gemini_conversation" ADD COLUMN "conversation_options" TEXT;
+
+PRAGMA foreign_keys = OFF;
@@ -0,0 +1,11 @@
+PRAGMA foreign_keys = ON;
+-- ----------------------------
+-- add the finish reason and safety ratings reported by the API to replies and their versions
+-- safety ratings are saved as JSON, both are empty for messages saved before and for user messages
+-- ----------------------------
+ALTER TABLE "gemini_message_record" ADD COLUMN "finish_reason" TEXT;
+ALTER TABLE "gemini_message_record" ADD COLUMN "safety_ratings" TEXT;
+ALTER TABLE "gemini_message_version" ADD COLUMN "finish_reason" TEXT;
+ALTER TABLE "gemini_message_version" ADD COLUMN "safety_ratings" TEXT;
+
+PRAGMA foreign_keys = OFF;
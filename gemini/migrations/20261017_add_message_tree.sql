@@ -0,0 +1,23 @@
+PRAGMA foreign_keys = ON;
+-- ----------------------------
+-- add parent_record_id for gemini_message_record, messages of a conversation form a tree so that it can be branched
+-- ----------------------------
+ALTER TABLE "gemini_message_record" ADD COLUMN "parent_record_id" TEXT;
+UPDATE "gemini_message_record" SET "parent_record_id" = (
+  SELECT "parent"."record_id" FROM "gemini_message_record" AS "parent"
+  WHERE "parent"."conversation_id" = "gemini_message_record"."conversation_id"
+  AND "parent"."sort_index" < "gemini_message_record"."sort_index"
+  ORDER BY "parent"."sort_index" DESC LIMIT 1
+);
+CREATE INDEX IF NOT EXISTS "idx_gemini_message_record_parent_record_id" ON "gemini_message_record" ("parent_record_id");
+-- ----------------------------
+-- add active_record_id for gemini_conversation, the last record of the active branch
+-- ----------------------------
+ALTER TABLE "gemini_conversation" ADD COLUMN "active_record_id" TEXT;
+UPDATE "gemini_conversation" SET "active_record_id" = (
+  SELECT "record_id" FROM "gemini_message_record"
+  WHERE "gemini_message_record"."conversation_id" = "gemini_conversation"."conversation_id"
+  ORDER BY "sort_index" DESC LIMIT 1
+);
+
+PRAGMA foreign_keys = OFF;
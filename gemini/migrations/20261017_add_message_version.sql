@@ -0,0 +1,18 @@
+PRAGMA foreign_keys = ON;
+-- ----------------------------
+-- Table structure for gemini_message_version, keep every regenerated version of a reply
+-- ----------------------------
+CREATE TABLE IF NOT EXISTS "gemini_message_version" (
+  "version_id" TEXT NOT NULL,
+  "record_id" TEXT,
+  "version_content" TEXT,
+  "version_time" DATE,
+  "version_state" TEXT DEFAULT 'Complete',
+  "version_index" INTEGER DEFAULT 0,
+  "selected" INTEGER DEFAULT 0,
+  PRIMARY KEY ("version_id"),
+  FOREIGN KEY ("record_id") REFERENCES "gemini_message_record" ("record_id") ON DELETE CASCADE ON UPDATE CASCADE
+);
+CREATE INDEX IF NOT EXISTS "idx_gemini_message_version_record_id" ON "gemini_message_version" ("record_id");
+
+PRAGMA foreign_keys = OFF;
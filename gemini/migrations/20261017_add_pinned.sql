@@ -0,0 +1,7 @@
+PRAGMA foreign_keys = ON;
+-- ----------------------------
+-- add the pinned flag to messages, pinned messages are never dropped from the context
+-- ----------------------------
+ALTER TABLE "gemini_message_record" ADD COLUMN "pinned" INTEGER NOT NULL DEFAULT 0;
+
+PRAGMA foreign_keys = OFF;
@@ -0,0 +1,7 @@
+PRAGMA foreign_keys = ON;
+-- ----------------------------
+-- add record_state for gemini_message_record, mark the partial response when streaming is interrupted
+-- ----------------------------
+ALTER TABLE "gemini_message_record" ADD COLUMN "record_state" TEXT DEFAULT 'Complete';
+
+PRAGMA foreign_keys = OFF;
@@ -0,0 +1,9 @@
+PRAGMA foreign_keys = ON;
+-- ----------------------------
+-- add the model replies and their versions were generated with, used to price their token usage
+-- empty for replies saved before and for other messages
+-- ----------------------------
+ALTER TABLE "gemini_message_record" ADD COLUMN "model" TEXT;
+ALTER TABLE "gemini_message_version" ADD COLUMN "model" TEXT;
+
+PRAGMA foreign_keys = OFF;
@@ -0,0 +1,9 @@
+PRAGMA foreign_keys = ON;
+-- ----------------------------
+-- add the response schema replies and their versions were generated with
+-- saved as JSON, empty for replies generated without a schema and for other messages
+-- ----------------------------
+ALTER TABLE "gemini_message_record" ADD COLUMN "response_schema" TEXT;
+ALTER TABLE "gemini_message_version" ADD COLUMN "response_schema" TEXT;
+
+PRAGMA foreign_keys = OFF;
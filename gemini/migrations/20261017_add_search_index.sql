@@ -0,0 +1,17 @@
+PRAGMA foreign_keys = ON;
+-- ----------------------------
+-- full-text search index of message records and conversation titles, rows with empty record_id are titles
+-- trigram tokenizer supports substring search of text without spaces, such as Chinese
+-- ----------------------------
+CREATE VIRTUAL TABLE IF NOT EXISTS "gemini_search_index" USING fts5(
+  "conversation_id" UNINDEXED,
+  "record_id" UNINDEXED,
+  "content",
+  tokenize = 'trigram'
+);
+INSERT INTO "gemini_search_index" ("conversation_id", "record_id", "content")
+SELECT "conversation_id", "record_id", "record_content" FROM "gemini_message_record";
+INSERT INTO "gemini_search_index" ("conversation_id", "record_id", "content")
+SELECT "conversation_id", NULL, "conversation_title" FROM "gemini_conversation";
+
+PRAGMA foreign_keys = OFF;
@@ -0,0 +1,11 @@
+PRAGMA foreign_keys = ON;
+-- ----------------------------
+-- add the token usage reported by the API to replies and their versions
+-- empty for messages saved before and for user messages
+-- ----------------------------
+ALTER TABLE "gemini_message_record" ADD COLUMN "prompt_token_count" INTEGER;
+ALTER TABLE "gemini_message_record" ADD COLUMN "response_token_count" INTEGER;
+ALTER TABLE "gemini_message_version" ADD COLUMN "prompt_token_count" INTEGER;
+ALTER TABLE "gemini_message_version" ADD COLUMN "response_token_count" INTEGER;
+
+PRAGMA foreign_keys = OFF;
@@ -0,0 +1,8 @@
+PRAGMA foreign_keys = ON;
+-- ----------------------------
+-- add the structured function calls and function responses of tool messages
+-- saved as JSON, empty for user messages and replies
+-- ----------------------------
+ALTER TABLE "gemini_message_record" ADD COLUMN "tool_payload" TEXT;
+
+PRAGMA foreign_keys = OFF;
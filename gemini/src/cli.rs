@@ -0,0 +1,549 @@
+use std::{
+    io::{stdin, stdout, IsTerminal, Read, Write},
+    path::PathBuf,
+};
+
+use anyhow::{bail, Result};
+use chrono::Local;
+use gemini_api::{model::blocking::Gemini, param::LanguageModel};
+use serde_json::Value;
+
+use crate::model::{
+    db::Conversation,
+    view::{ChatMessage, MessageState, Sender},
+};
+use crate::store::ConversationStore;
+use crate::utils::db_utils::{conversation_store, generate_unique_id, update_db_structure};
+use crate::utils::export_utils::{export_conversation, export_to_file, load_for_export, ExportFormat};
+use crate::utils::gemini_utils::{send_message_stream, summary_by_gemini, user_content, RequestOptions};
+use crate::utils::import_utils::{import_file, ImportStatus};
+use crate::utils::schema_utils::schema_mismatch;
+use crate::utils::secret_utils::{passphrase, read_passphrase, set_passphrase};
+use crate::utils::store_utils::{read_config, save_config, Profile, ENV_NAME};
+
+/// 命令行使用说明
+const USAGE: &str = r#"Usage:
+    gemini [GLOBAL OPTIONS]                          启动终端界面
+    gemini [GLOBAL OPTIONS] ask [OPTIONS] [PROMPT]   发送一条消息并将回复输出到标准输出
+    gemini [GLOBAL OPTIONS] list                     列出所有会话
+    gemini [GLOBAL OPTIONS] export [OPTIONS] <ID>    导出一个会话，ID 可以是会话 id 的唯一前缀
+    gemini [GLOBAL OPTIONS] import [OPTIONS] <FILE>...
+                                                     导入本程序导出的 JSON 文档或 ChatGPT 导出的 conversations.json
+
+Global Options:
+    --config-dir <DIR>   配置目录，默认为 $GEMINI_CONFIG_DIR 或 $XDG_CONFIG_HOME/gemini
+    --data-dir <DIR>     数据目录，存放数据库与附件缓存，默认为 $GEMINI_DATA_DIR 或 $XDG_DATA_HOME/gemini
+
+Ask Options:
+    -i, --image <PATH>   附带一个文件（图片、PDF、音频或文本文件），可以是本地路径或网络地址，可以重复使用以附带多个文件
+    -s, --save           将本次对话保存为一个新的会话
+    -t, --title <TITLE>  保存会话时使用的标题，默认由模型总结
+
+Export Options:
+    -f, --format <FORMAT>  导出格式，可选 markdown、json、html，默认为 markdown
+    -o, --output <PATH>    导出文件路径，默认输出到标准输出
+
+Import Options:
+    -n, --dry-run          只检查将会导入的会话，不写入数据库
+
+    -h, --help           显示帮助信息
+
+Environment:
+    GEMINI_KEY           未保存密钥时使用的 API 密钥
+    GEMINI_PASSPHRASE    解密已保存密钥的口令，未设置时在终端中输入
+
+未提供 PROMPT 时从标准输入读取，两者都提供时标准输入的内容将追加到 PROMPT 之后"#;
+
+/// 参数错误的退出码
+const EXIT_USAGE: i32 = 2;
+/// 请求失败的退出码
+const EXIT_FAILURE: i32 = 1;
+
+/// 命令行参数
+#[derive(Default)]
+pub struct Cli {
+    /// 配置目录
+    pub config_dir: Option<PathBuf>,
+    /// 数据目录
+    pub data_dir: Option<PathBuf>,
+    /// 指令
+    pub command: Command,
+}
+
+/// 命令行指令
+#[derive(Default)]
+pub enum Command {
+    /// 启动终端界面
+    #[default]
+    Tui,
+    /// 显示帮助信息
+    Help,
+    /// 发送一条消息
+    Ask(AskArgs),
+    /// 列出所有会话
+    List,
+    /// 导出一个会话
+    Export(ExportArgs),
+    /// 导入会话
+    Import(ImportArgs),
+}
+
+/// `ask` 指令的参数
+#[derive(Default)]
+pub struct AskArgs {
+    /// 消息内容
+    prompt: Vec<String>,
+    /// 所有图片路径
+    images: Vec<String>,
+    /// 是否保存到数据库
+    save: bool,
+    /// 保存时使用的会话标题
+    title: Option<String>,
+}
+
+/// `export` 指令的参数
+#[derive(Default)]
+pub struct ExportArgs {
+    /// 会话 id 或其唯一前缀
+    conversation_id: String,
+    /// 导出格式
+    format: ExportFormat,
+    /// 导出文件路径
+    output: Option<PathBuf>,
+}
+
+/// `import` 指令的参数
+#[derive(Default)]
+pub struct ImportArgs {
+    /// 导入文件路径
+    files: Vec<PathBuf>,
+    /// 是否只检查不写入
+    dry_run: bool,
+}
+
+/// 解析命令行参数，不包含程序名
+pub fn parse_args<I>(args: I) -> Result<Cli>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut args = args.into_iter();
+    let mut cli = Cli::default();
+    // 全局选项需要位于指令之前
+    while let Some(arg) = args.next() {
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name.to_owned(), Some(value.to_owned())),
+            _ => (arg.clone(), None),
+        };
+        cli.command = match name.as_str() {
+            "--config-dir" => {
+                cli.config_dir = Some(option_value(&name, inline_value, &mut args)?.into());
+                continue;
+            }
+            "--data-dir" => {
+                cli.data_dir = Some(option_value(&name, inline_value, &mut args)?.into());
+                continue;
+            }
+            "-h" | "--help" | "help" => Command::Help,
+            "ask" => parse_ask_args(args)?,
+            "list" => match args.next() {
+                None => Command::List,
+                Some(arg) if arg == "-h" || arg == "--help" => Command::Help,
+                Some(arg) => bail!("未知的参数: {}", arg),
+            },
+            "export" => parse_export_args(args)?,
+            "import" => parse_import_args(args)?,
+            _ if name.starts_with('-') => bail!("未知的选项: {}", name),
+            other => bail!("未知的指令: {}", other),
+        };
+        break;
+    }
+    Ok(cli)
+}
+
+/// 解析 `ask` 指令的参数
+fn parse_ask_args<I>(mut args: I) -> Result<Command>
+where
+    I: Iterator<Item = String>,
+{
+    let mut ask_args = AskArgs::default();
+    while let Some(arg) = args.next() {
+        // 支持 --option=value 的写法
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name.to_owned(), Some(value.to_owned())),
+            _ => (arg.clone(), None),
+        };
+        match name.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-s" | "--save" => ask_args.save = true,
+            "-i" | "--image" => ask_args.images.push(option_value(&name, inline_value, &mut args)?),
+            "-t" | "--title" => ask_args.title = Some(option_value(&name, inline_value, &mut args)?),
+            // 之后的参数都作为消息内容
+            "--" => {
+                ask_args.prompt.extend(args.by_ref());
+            }
+            _ if name.starts_with('-') && name.len() > 1 => bail!("未知的选项: {}", name),
+            _ => ask_args.prompt.push(arg),
+        }
+    }
+    Ok(Command::Ask(ask_args))
+}
+
+/// 解析 `export` 指令的参数
+fn parse_export_args<I>(mut args: I) -> Result<Command>
+where
+    I: Iterator<Item = String>,
+{
+    let mut export_args = ExportArgs::default();
+    while let Some(arg) = args.next() {
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name.to_owned(), Some(value.to_owned())),
+            _ => (arg.clone(), None),
+        };
+        match name.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-f" | "--format" => {
+                let format = option_value(&name, inline_value, &mut args)?;
+                export_args.format = format
+                    .to_lowercase()
+                    .parse()
+                    .map_err(|_| anyhow::Error::msg(format!("不支持的导出格式: {}", format)))?;
+            }
+            "-o" | "--output" => export_args.output = Some(option_value(&name, inline_value, &mut args)?.into()),
+            _ if name.starts_with('-') && name.len() > 1 => bail!("未知的选项: {}", name),
+            _ if export_args.conversation_id.is_empty() => export_args.conversation_id = arg,
+            _ => bail!("只能导出一个会话"),
+        }
+    }
+    if export_args.conversation_id.is_empty() {
+        bail!("缺少会话 id");
+    }
+    Ok(Command::Export(export_args))
+}
+
+/// 解析 `import` 指令的参数
+fn parse_import_args<I>(args: I) -> Result<Command>
+where
+    I: Iterator<Item = String>,
+{
+    let mut import_args = ImportArgs::default();
+    for arg in args {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-n" | "--dry-run" => import_args.dry_run = true,
+            _ if arg.starts_with('-') && arg.len() > 1 => bail!("未知的选项: {}", arg),
+            _ => import_args.files.push(arg.into()),
+        }
+    }
+    if import_args.files.is_empty() {
+        bail!("缺少导入文件");
+    }
+    Ok(Command::Import(import_args))
+}
+
+/// 获取选项的值
+fn option_value<I>(name: &str, inline_value: Option<String>, args: &mut I) -> Result<String>
+where
+    I: Iterator<Item = String>,
+{
+    match inline_value.or_else(|| args.next()) {
+        Some(value) if !value.is_empty() => Ok(value),
+        _ => bail!("选项 {} 缺少参数值", name),
+    }
+}
+
+/// 输出帮助信息
+pub fn print_usage() {
+    println!("{}", USAGE);
+}
+
+/// 输出参数错误信息，返回退出码
+pub fn usage_error(error: anyhow::Error) -> i32 {
+    eprintln!("error: {}\n\n{}", error, USAGE);
+    EXIT_USAGE
+}
+
+/// 执行 `ask` 指令，返回退出码
+pub fn ask(args: AskArgs) -> i32 {
+    let prompt = match read_prompt(&args.prompt) {
+        Ok(prompt) if !prompt.is_empty() => prompt,
+        Ok(_) => return usage_error(anyhow::Error::msg("消息内容为空")),
+        Err(e) => return usage_error(e),
+    };
+    exit_code(send(prompt, args))
+}
+
+/// 执行 `list` 指令，返回退出码
+pub fn list() -> i32 {
+    exit_code(list_conversations())
+}
+
+/// 执行 `export` 指令，返回退出码
+pub fn export(args: ExportArgs) -> i32 {
+    exit_code(export_conversation_by_id(args))
+}
+
+/// 执行 `import` 指令，返回退出码
+pub fn import(args: ImportArgs) -> i32 {
+    exit_code(import_files(args))
+}
+
+/// 根据执行结果输出错误信息，返回退出码
+fn exit_code(result: Result<()>) -> i32 {
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            EXIT_FAILURE
+        }
+    }
+}
+
+/// 拼接参数与标准输入中的消息内容
+fn read_prompt(prompt: &[String]) -> Result<String> {
+    let prompt = prompt.join(" ");
+    // 标准输入为终端时不读取，避免阻塞等待输入
+    if stdin().is_terminal() {
+        return Ok(prompt);
+    }
+    let mut input = String::new();
+    stdin().read_to_string(&mut input)?;
+    let input = input.trim_end();
+    Ok(match (prompt.is_empty(), input.is_empty()) {
+        (_, true) => prompt,
+        (true, false) => input.to_owned(),
+        (false, false) => format!("{}\n\n{}", prompt, input),
+    })
+}
+
+/// 发送消息并逐段输出回复，需要时保存对话
+fn send(prompt: String, args: AskArgs) -> Result<()> {
+    let gemini = restore_gemini()?;
+    let user_time = Local::now();
+    let content = user_content(prompt.clone(), args.images.clone())?;
+    // 命令行中无法确认工具调用，因此不声明本地工具
+    let config = read_config().unwrap_or_default();
+    let options = RequestOptions {
+        safety: config.safety,
+        response_schema: config.response_schema,
+        ..Default::default()
+    };
+    let stream = send_message_stream(&gemini, content, &options)?;
+    let mut response = String::new();
+    let mut usage = None;
+    let mut finish = None;
+    let mut out = stdout().lock();
+    for chunk in stream {
+        let chunk = chunk?;
+        out.write_all(chunk.text.as_bytes())?;
+        out.flush()?;
+        response.push_str(&chunk.text);
+        usage = chunk.usage.or(usage);
+        finish = chunk.finish.or(finish);
+    }
+    // 保证输出以换行结尾
+    if !response.ends_with('\n') {
+        writeln!(out)?;
+    }
+    let response = response.trim_end_matches('\n').to_owned();
+    // 回复被拦截或截断时在标准错误中提示原因
+    match &finish {
+        Some(finish) if response.is_empty() && !finish.is_normal() => bail!("Response Blocked: {}", finish),
+        Some(finish) if !finish.is_normal() || !finish.flagged_ratings().is_empty() => {
+            eprintln!("warning: {}", finish)
+        }
+        _ => {}
+    }
+    if response.is_empty() {
+        bail!("Empty Response");
+    }
+    // 回复不符合格式约束时在标准错误中提示
+    if let Some(mismatch) = options
+        .response_schema
+        .as_ref()
+        .and_then(|schema| schema_mismatch(&response, schema))
+    {
+        eprintln!("warning: Schema Mismatch: {}", mismatch)
+    }
+    if args.save {
+        let title = args
+            .title
+            .unwrap_or_else(|| summary_by_gemini(gemini.key.clone(), response.clone()));
+        update_db_structure()?;
+        let user_message = ChatMessage {
+            success: true,
+            message: prompt,
+            sender: Sender::User(args.images),
+            date_time: user_time,
+            state: MessageState::Complete,
+            ..Default::default()
+        };
+        let bot_message = ChatMessage {
+            success: true,
+            message: response,
+            sender: Sender::Bot,
+            date_time: Local::now(),
+            state: MessageState::Complete,
+            usage,
+            finish,
+            response_schema: options.response_schema.clone(),
+            model: Some(gemini.model.to_string()),
+            ..Default::default()
+        };
+        save(&gemini, options.response_schema, title, user_message, bot_message)?;
+    }
+    Ok(())
+}
+
+/// 输出所有会话的 id、最后修改时间与标题
+fn list_conversations() -> Result<()> {
+    update_db_structure()?;
+    let mut out = stdout().lock();
+    for conversation in conversation_store().list()? {
+        writeln!(
+            out,
+            "{}\t{}\t{}",
+            conversation.conversation_id,
+            conversation.conversation_modify_time.format("%Y-%m-%d %H:%M:%S"),
+            conversation.conversation_title
+        )?;
+    }
+    Ok(())
+}
+
+/// 导出会话到文件或标准输出
+fn export_conversation_by_id(args: ExportArgs) -> Result<()> {
+    update_db_structure()?;
+    // 先精确匹配，再按前缀匹配
+    let conversations = conversation_store().list()?;
+    let conversation = match conversations
+        .iter()
+        .find(|conversation| conversation.conversation_id == args.conversation_id)
+    {
+        Some(conversation) => conversation.clone(),
+        None => {
+            let matched: Vec<&Conversation> = conversations
+                .iter()
+                .filter(|conversation| conversation.conversation_id.starts_with(&args.conversation_id))
+                .collect();
+            match matched.as_slice() {
+                [conversation] => (*conversation).clone(),
+                [] => bail!("会话不存在: {}", args.conversation_id),
+                _ => bail!("存在多个匹配的会话: {}", args.conversation_id),
+            }
+        }
+    };
+    let conversation = load_for_export(&conversation.conversation_id, args.format)?;
+    match args.output {
+        Some(output) => {
+            let path = export_to_file(&conversation, args.format, Some(output))?;
+            eprintln!("exported to {}", path.display());
+        }
+        None => {
+            let mut content = export_conversation(&conversation, args.format)?;
+            if !content.ends_with('\n') {
+                content.push('\n');
+            }
+            stdout().lock().write_all(content.as_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+/// 导入所有文件并输出导入报告，存在导入失败的会话时返回错误
+fn import_files(args: ImportArgs) -> Result<()> {
+    update_db_structure()?;
+    let mut out = stdout().lock();
+    let (mut imported, mut duplicate, mut failed) = (0, 0, 0);
+    for file in args.files {
+        let report = match import_file(conversation_store(), &file, args.dry_run) {
+            Ok(report) => report,
+            Err(e) => {
+                writeln!(out, "{}: {}", file.display(), e)?;
+                failed += 1;
+                continue;
+            }
+        };
+        writeln!(out, "{} ({})", file.display(), report.format)?;
+        for entry in report.entries.iter() {
+            let status = match &entry.status {
+                ImportStatus::Imported(conversation_id) => format!("imported as {}", conversation_id),
+                ImportStatus::WouldImport => "would import".into(),
+                ImportStatus::Duplicate(conversation_id) if conversation_id.is_empty() => "duplicate in file".into(),
+                ImportStatus::Duplicate(conversation_id) => format!("duplicate of {}", conversation_id),
+                ImportStatus::Failed(e) => format!("failed: {}", e),
+            };
+            writeln!(
+                out,
+                "    [{}] {} ({} messages)",
+                status, entry.title, entry.record_count
+            )?;
+        }
+        imported += report.imported_count();
+        duplicate += report.duplicate_count();
+        failed += report.failed_count();
+    }
+    let action = if args.dry_run { "would import" } else { "imported" };
+    writeln!(
+        out,
+        "{} {}, {} duplicate, {} failed",
+        imported, action, duplicate, failed
+    )?;
+    if failed > 0 {
+        bail!("{} conversations failed to import", failed);
+    }
+    Ok(())
+}
+
+/// 读取配置文件构建 Gemini API，没有配置文件时尝试从环境变量中读取密钥
+fn restore_gemini() -> Result<Gemini> {
+    match read_config() {
+        Ok(mut store_data) => {
+            // 密钥加密保存且未设置口令环境变量时，在终端中输入口令
+            if store_data.is_locked() {
+                set_passphrase(Some(read_passphrase("Passphrase: ")?));
+            }
+            // 旧版本保存的明文密钥，设置了口令时加密保存
+            if store_data.is_plaintext() && passphrase().is_some() {
+                save_config(store_data.clone())?;
+            }
+            store_data.resolve_key()?;
+            let mut gemini = Gemini::rebuild(store_data.key, store_data.model, Vec::new(), store_data.options);
+            gemini.set_system_instruction(store_data.system_instruction.unwrap_or_default());
+            Ok(gemini)
+        }
+        Err(_) => match std::env::var(ENV_NAME) {
+            Ok(key) if !key.is_empty() => {
+                let mut gemini = Gemini::new(key, LanguageModel::Gemini1_5Flash);
+                gemini.start_chat(Vec::new());
+                Ok(gemini)
+            }
+            _ => bail!("未找到配置文件，请先运行 gemini 完成配置或设置环境变量 {}", ENV_NAME),
+        },
+    }
+}
+
+/// 将本次对话保存为一个新的会话
+fn save(
+    gemini: &Gemini,
+    response_schema: Option<Value>,
+    title: String,
+    user_message: ChatMessage,
+    bot_message: ChatMessage,
+) -> Result<()> {
+    let conversation_id = generate_unique_id();
+    let store = conversation_store();
+    store.save_exchange(&conversation_id, &title, None, user_message, bot_message)?;
+    // 保存会话使用的模型、系统指令、生成配置与回复格式约束
+    let profile = Profile {
+        options: gemini.options.clone(),
+        response_schema,
+        ..Default::default()
+    };
+    store.modify_settings(
+        &conversation_id,
+        &gemini.model.to_string(),
+        gemini.system_instruction.as_deref(),
+        &profile.options_json(),
+    )?;
+    Ok(())
+}
@@ -1,14 +1,41 @@
+mod cli;
 mod model;
+mod store;
 mod ui;
 mod utils;
 
+use std::{env, process};
+
 use anyhow::Result;
+use cli::Command;
 use ui::page::main_page::UI;
+use utils::path_utils;
 
 fn main() -> Result<()> {
-    // Setup terminal
-    let terminal = ratatui::init();
-    let app_result = UI::default().run(terminal);
-    ratatui::restore();
-    app_result
+    let cli = match cli::parse_args(env::args().skip(1)) {
+        Ok(cli) => cli,
+        Err(e) => process::exit(cli::usage_error(e)),
+    };
+    // 初始化配置与数据目录，并迁移旧版本保存在程序所在目录的文件
+    path_utils::init_dirs(cli.config_dir, cli.data_dir);
+    if let Err(e) = path_utils::migrate_legacy_files() {
+        eprintln!("Failed to migrate legacy files: {}", e);
+    }
+    match cli.command {
+        Command::Tui => {
+            // Setup terminal
+            let terminal = ratatui::init();
+            let app_result = UI::default().run(terminal);
+            ratatui::restore();
+            app_result
+        }
+        Command::Help => {
+            cli::print_usage();
+            Ok(())
+        }
+        Command::Ask(args) => process::exit(cli::ask(args)),
+        Command::List => process::exit(cli::list()),
+        Command::Export(args) => process::exit(cli::export(args)),
+        Command::Import(args) => process::exit(cli::import(args)),
+    }
 }
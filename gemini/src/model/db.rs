@@ -1,11 +1,14 @@
 #![allow(dead_code)]
 
 use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-use super::view::Sender;
+use super::view::{FinishInfo, MessageState, Sender, TokenUsage};
 
 /// 一个聊天记录项
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Conversation {
     /// conversation id
     pub conversation_id: String,
@@ -15,16 +18,45 @@ pub struct Conversation {
     pub conversation_start_time: DateTime<Local>,
     /// 最后一条聊天记录发送时间
     pub conversation_modify_time: DateTime<Local>,
+    /// 当前分支的最后一条记录 id
+    pub active_record_id: Option<String>,
+    /// 会话使用的模型，旧版本创建的会话为空
+    pub conversation_model: Option<String>,
+    /// 会话使用的系统指令
+    pub conversation_system_instruction: Option<String>,
+    /// 会话使用的生成配置，JSON 格式
+    pub conversation_options: Option<String>,
     /// 聊天记录
     pub conversation_records: Vec<MessageRecord>,
 }
 
-/// 单条聊天记录
+/// 全文搜索的一条结果
 #[derive(Debug, Clone)]
+pub struct SearchResult {
+    /// 命中的会话 id
+    pub conversation_id: String,
+    /// 命中的会话标题
+    pub conversation_title: String,
+    /// 命中的记录 id，命中会话标题时为空
+    pub record_id: Option<String>,
+    /// 命中内容的摘要，关键词使用 `SNIPPET_START` 与 `SNIPPET_END` 包裹
+    pub snippet: String,
+    /// 会话最后修改时间
+    pub conversation_modify_time: DateTime<Local>,
+}
+
+/// 搜索摘要中关键词的开始标记
+pub const SNIPPET_START: char = '\u{2}';
+/// 搜索摘要中关键词的结束标记
+pub const SNIPPET_END: char = '\u{3}';
+
+/// 单条聊天记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MessageRecord {
     /// 记录 id
     pub record_id: String,
     /// 对应的 conversation id
+    #[serde(default)]
     pub conversation_id: String,
     /// 记录内容
     pub record_content: String,
@@ -33,13 +65,41 @@ pub struct MessageRecord {
     /// 记录发送者
     pub record_sender: Sender,
     /// 排序
-    pub sort_index: u8,
+    #[serde(default)]
+    pub sort_index: u32,
+    /// 记录接收状态
+    #[serde(default)]
+    pub record_state: MessageState,
     /// 图片内容
-    pub image_record: Option<ImageRecord>,
+    #[serde(default, alias = "image_record", deserialize_with = "super::one_or_many")]
+    pub image_records: Vec<ImageRecord>,
+    /// 重新生成的所有回复版本
+    #[serde(default)]
+    pub record_versions: Vec<VersionRecord>,
+    /// 父记录 id，会话中第一条记录为空
+    pub parent_record_id: Option<String>,
+    /// 同一父记录下的所有记录 id，包含当前记录
+    #[serde(default)]
+    pub sibling_record_ids: Vec<String>,
+    /// 回复消耗的 token 数量
+    #[serde(default)]
+    pub token_usage: Option<TokenUsage>,
+    /// 回复结束的原因与安全评级
+    #[serde(default)]
+    pub finish_info: Option<FinishInfo>,
+    /// 回复生成时使用的回复格式约束
+    #[serde(default)]
+    pub response_schema: Option<Value>,
+    /// 回复生成时使用的模型
+    #[serde(default)]
+    pub model: Option<String>,
+    /// 是否被固定
+    #[serde(default)]
+    pub pinned: bool,
 }
 
 /// 单条聊天记录携带的图片
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ImageRecord {
     /// 图片记录 id
     pub image_record_id: String,
@@ -50,3 +110,34 @@ pub struct ImageRecord {
     /// 图片类型
     pub image_type: String,
 }
+
+/// 单条回复的一个版本
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersionRecord {
+    /// 版本记录 id
+    pub version_id: String,
+    /// 对应的 record id
+    pub record_id: String,
+    /// 版本内容
+    pub version_content: String,
+    /// 版本生成时间
+    pub version_time: DateTime<Local>,
+    /// 版本接收状态
+    pub version_state: MessageState,
+    /// 版本序号
+    pub version_index: u32,
+    /// 是否为当前选中的版本
+    pub selected: bool,
+    /// 版本消耗的 token 数量
+    #[serde(default)]
+    pub token_usage: Option<TokenUsage>,
+    /// 版本结束的原因与安全评级
+    #[serde(default)]
+    pub finish_info: Option<FinishInfo>,
+    /// 版本生成时使用的回复格式约束
+    #[serde(default)]
+    pub response_schema: Option<Value>,
+    /// 版本生成时使用的模型
+    #[serde(default)]
+    pub model: Option<String>,
+}
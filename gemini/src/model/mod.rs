@@ -1,2 +1,35 @@
+use serde::{Deserialize, Deserializer};
+
 pub(crate) mod db;
 pub(crate) mod view;
+
+/// 单个值或数组
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany<T> {
+    Many(Vec<T>),
+    One(T),
+}
+
+/// 反序列化单个值、数组或空值为数组，用于兼容旧版本导出文件中只有一张图片的记录
+pub(crate) fn one_or_many<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Ok(match Option::<OneOrMany<T>>::deserialize(deserializer)? {
+        Some(OneOrMany::Many(values)) => values,
+        Some(OneOrMany::One(value)) => vec![value],
+        None => Vec::new(),
+    })
+}
+
+/// 反序列化图片路径，旧版本导出文件中没有图片时为空字符串
+pub(crate) fn image_paths<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut paths: Vec<String> = one_or_many(deserializer)?;
+    paths.retain(|path| !path.is_empty());
+    Ok(paths)
+}
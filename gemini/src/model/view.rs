@@ -1,9 +1,17 @@
+use std::{fmt, ops::AddAssign, sync::Arc};
+
 use chrono::{DateTime, Local};
+use ratatui::text::Text;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use strum::{Display, EnumString};
+
+use crate::utils::preview_utils::ImagePreview;
 
 /// 单条聊天消息
 ///
 /// 包含消息状态、消息内容、发送者、发送时间等信息
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct ChatMessage {
     /// 消息状态，true表示已发送成功，false表示发送失败
     pub success: bool,
@@ -13,15 +21,218 @@ pub struct ChatMessage {
     pub sender: Sender,
     /// 发送时间
     pub date_time: DateTime<Local>,
+    /// 消息接收状态
+    pub state: MessageState,
+    /// 回复的所有版本，只有重新生成过的回复才会有值
+    pub versions: Vec<MessageVersion>,
+    /// 当前展示的版本索引
+    pub version_index: usize,
+    /// 对应的数据库记录 id，消息保存后才会有值
+    pub record_id: String,
+    /// 同一父消息下的所有分支消息 id，包含当前消息，只有编辑过的消息才会有多个
+    pub sibling_ids: Vec<String>,
+    /// 是否被选中
+    pub selected: bool,
+    /// 渲染后的消息内容，为空时展示原始文本
+    pub rendered: Option<Text<'static>>,
+    /// 用户消息中图片附件的记录 id，用于读取缓存的图片展示预览
+    pub image_record_ids: Vec<String>,
+    /// 图片预览，绘制时根据图片记录 id 加载
+    pub previews: Vec<Arc<ImagePreview>>,
+    /// 回复消耗的 token 数量，只有回复才会有值
+    pub usage: Option<TokenUsage>,
+    /// 回复结束的原因与安全评级，只有回复才会有值
+    pub finish: Option<FinishInfo>,
+    /// 是否被固定，固定的消息所在的一轮对话不会被移出上下文
+    pub pinned: bool,
+    /// 上一次请求时是否已被移出上下文
+    pub out_of_context: bool,
+    /// 回复生成时使用的回复格式约束，只有设置了约束时生成的回复才会有值
+    pub response_schema: Option<Value>,
+    /// 回复生成时使用的模型，用于按模型价格估算费用，只有回复才会有值
+    pub model: Option<String>,
+    /// 回复不符合生成时的回复格式约束时的说明，未设置约束或符合约束时为空
+    pub schema_mismatch: Option<String>,
 }
 
-/// 发送者类型
+/// 回复的一个版本
 #[derive(Debug, Clone)]
+pub struct MessageVersion {
+    /// 版本内容
+    pub message: String,
+    /// 版本生成时间
+    pub date_time: DateTime<Local>,
+    /// 版本接收状态
+    pub state: MessageState,
+    /// 版本消耗的 token 数量
+    pub usage: Option<TokenUsage>,
+    /// 版本结束的原因与安全评级
+    pub finish: Option<FinishInfo>,
+    /// 版本生成时使用的回复格式约束
+    pub response_schema: Option<Value>,
+    /// 版本生成时使用的模型
+    pub model: Option<String>,
+}
+
+/// 单次请求消耗的 token 数量
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    /// 请求内容的 token 数量，包含系统指令与全部历史记录
+    pub prompt_tokens: u32,
+    /// 回复内容的 token 数量
+    pub response_tokens: u32,
+}
+
+impl TokenUsage {
+    /// 根据数据库中保存的两列还原，任意一列为空时返回空
+    pub fn from_columns(prompt_tokens: Option<u32>, response_tokens: Option<u32>) -> Option<Self> {
+        Some(Self {
+            prompt_tokens: prompt_tokens?,
+            response_tokens: response_tokens?,
+        })
+    }
+
+    /// 总 token 数量
+    pub fn total(&self) -> u32 {
+        self.prompt_tokens + self.response_tokens
+    }
+}
+
+impl AddAssign for TokenUsage {
+    fn add_assign(&mut self, other: Self) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.response_tokens += other.response_tokens;
+    }
+}
+
+/// 回复结束的原因与安全评级
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FinishInfo {
+    /// 结束原因，例如 `STOP`、`MAX_TOKENS`、`SAFETY`
+    pub reason: String,
+    /// 回复内容的安全评级
+    #[serde(default)]
+    pub safety_ratings: Vec<SafetyRating>,
+}
+
+impl FinishInfo {
+    /// 根据数据库中保存的两列还原，结束原因为空时返回空
+    pub fn from_columns(reason: Option<String>, safety_ratings: Option<String>) -> Option<Self> {
+        Some(Self {
+            reason: reason?,
+            safety_ratings: safety_ratings
+                .and_then(|ratings| serde_json::from_str(&ratings).ok())
+                .unwrap_or_default(),
+        })
+    }
+
+    /// 安全评级保存到数据库中的 JSON 格式
+    pub fn safety_ratings_column(&self) -> String {
+        serde_json::to_string(&self.safety_ratings).unwrap_or_default()
+    }
+
+    /// 是否因达到最大输出 token 数量而被截断，被截断的回复可以继续生成
+    pub fn is_truncated(&self) -> bool {
+        self.reason == "MAX_TOKENS"
+    }
+
+    /// 是否为正常结束
+    pub fn is_normal(&self) -> bool {
+        matches!(self.reason.as_str(), "STOP" | "FINISH_REASON_UNSPECIFIED")
+    }
+
+    /// 需要提示的安全评级，包含被拦截以及概率为中等或以上的类别
+    pub fn flagged_ratings(&self) -> Vec<&SafetyRating> {
+        self.safety_ratings
+            .iter()
+            .filter(|rating| rating.blocked || matches!(rating.probability.as_str(), "MEDIUM" | "HIGH"))
+            .collect()
+    }
+}
+
+impl fmt::Display for FinishInfo {
+    /// 结束原因以及需要提示的安全评级，例如 `SAFETY (HARASSMENT: HIGH)`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.reason)?;
+        let flagged = self.flagged_ratings();
+        if !flagged.is_empty() {
+            let ratings = flagged.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ");
+            write!(f, " ({})", ratings)?;
+        }
+        Ok(())
+    }
+}
+
+/// 单个类别的安全评级，与 API 返回的格式一致
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SafetyRating {
+    /// 类别，例如 `HARM_CATEGORY_HARASSMENT`
+    pub category: String,
+    /// 有害内容的概率，例如 `NEGLIGIBLE`、`LOW`、`MEDIUM`、`HIGH`
+    pub probability: String,
+    /// 是否因该类别被拦截
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub blocked: bool,
+}
+
+impl fmt::Display for SafetyRating {
+    /// 省略类别的 `HARM_CATEGORY_` 前缀，例如 `HARASSMENT: HIGH`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let category = self.category.strip_prefix("HARM_CATEGORY_").unwrap_or(&self.category);
+        if self.blocked {
+            write!(f, "{}: {} blocked", category, self.probability)
+        } else {
+            write!(f, "{}: {}", category, self.probability)
+        }
+    }
+}
+
+/// 模型请求调用的本地工具，与 API 中 `functionCall` 的格式一致
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FunctionCall {
+    /// 工具名称
+    pub name: String,
+    /// 调用参数
+    #[serde(default)]
+    pub args: Value,
+}
+
+/// 本地工具的执行结果，与 API 中 `functionResponse` 的格式一致
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FunctionResponse {
+    /// 工具名称
+    pub name: String,
+    /// 执行结果，成功时为 `output`，失败或被拒绝时为 `error`
+    pub response: Value,
+}
+
+/// 消息接收状态
+#[derive(Debug, Clone, Default, PartialEq, Eq, Display, EnumString, Serialize, Deserialize)]
+pub enum MessageState {
+    /// 消息已完整接收
+    #[default]
+    Complete,
+    /// 正在流式接收消息
+    Receiving,
+    /// 接收过程中被中断，仅包含部分内容
+    Partial,
+    /// 请求已被用户取消
+    Cancelled,
+}
+
+/// 发送者类型
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub enum Sender {
-    /// 用户发送的消息, 第一个元组参数为图片路径
-    User(String),
+    /// 用户发送的消息, 第一个元组参数为所有图片路径
+    User(#[serde(deserialize_with = "super::image_paths")] Vec<String>),
     /// AI 回复的消息
     Bot,
+    /// 模型请求调用的本地工具
+    ToolCall(Vec<FunctionCall>),
+    /// 本地工具的执行结果
+    ToolResult(Vec<FunctionResponse>),
     /// 处理其他类型的消息，一般不会用到，用作标记作用
+    #[default]
     Never,
 }
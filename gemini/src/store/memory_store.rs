@@ -0,0 +1,426 @@
+use std::{cmp::Reverse, collections::HashMap, sync::Mutex};
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::model::{
+    db::{Conversation, ImageRecord, MessageRecord, SearchResult, VersionRecord},
+    view::{ChatMessage, Sender},
+};
+use crate::utils::attachment_utils::mime_type_by_extension;
+use crate::utils::db_utils::generate_unique_id;
+
+use super::{active_branch, make_snippet, ConversationStore, SEARCH_LIMIT};
+
+/// 保存在内存中的会话存储，用于测试
+#[derive(Default)]
+pub(crate) struct MemoryStore {
+    data: Mutex<MemoryData>,
+}
+
+#[derive(Default)]
+struct MemoryData {
+    /// 所有会话，不包含聊天记录
+    conversations: Vec<Conversation>,
+    /// 所有会话的聊天记录
+    records: Vec<MessageRecord>,
+}
+
+impl MemoryData {
+    fn conversation_mut(&mut self, conversation_id: &str) -> Result<&mut Conversation> {
+        self.conversations
+            .iter_mut()
+            .find(|conversation| conversation.conversation_id == conversation_id)
+            .ok_or_else(|| anyhow!("Conversation {} does not exist", conversation_id))
+    }
+
+    /// 会话的所有记录，按序号与发送时间排列
+    fn records_of(&self, conversation_id: &str) -> Vec<MessageRecord> {
+        let mut records: Vec<MessageRecord> = self
+            .records
+            .iter()
+            .filter(|record| record.conversation_id == conversation_id)
+            .cloned()
+            .collect();
+        records.sort_by_key(|record| (record.sort_index, record.record_time));
+        records
+    }
+
+    /// 保存一条消息，会话不存在时新建，返回新记录的 id
+    fn insert_message(
+        &mut self,
+        conversation_id: &str,
+        conversation_title: &str,
+        message: ChatMessage,
+        parent_record_id: Option<String>,
+    ) -> String {
+        let record_id = generate_unique_id();
+        match self.conversation_mut(conversation_id) {
+            Ok(conversation) => conversation.conversation_modify_time = message.date_time,
+            Err(_) => self.conversations.push(Conversation {
+                conversation_id: conversation_id.into(),
+                conversation_title: conversation_title.into(),
+                conversation_start_time: message.date_time,
+                conversation_modify_time: message.date_time,
+                ..Default::default()
+            }),
+        }
+        let sort_index = parent_record_id
+            .as_ref()
+            .and_then(|parent_record_id| self.records.iter().find(|record| record.record_id == *parent_record_id))
+            .map_or(0, |parent| parent.sort_index + 1);
+        let image_records = match &message.sender {
+            Sender::User(image_paths) => image_paths
+                .iter()
+                .map(|image_path| ImageRecord {
+                    image_record_id: generate_unique_id(),
+                    record_id: record_id.clone(),
+                    image_path: image_path.clone(),
+                    image_type: mime_type_by_extension(image_path).unwrap_or_default().into(),
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        self.records.push(MessageRecord {
+            record_id: record_id.clone(),
+            conversation_id: conversation_id.into(),
+            record_content: message.message,
+            record_time: message.date_time,
+            record_sender: message.sender,
+            sort_index,
+            record_state: message.state,
+            image_records,
+            record_versions: vec![],
+            parent_record_id,
+            sibling_record_ids: vec![],
+            token_usage: message.usage,
+            finish_info: message.finish,
+            response_schema: message.response_schema,
+            model: message.model,
+            pinned: message.pinned,
+        });
+        if let Ok(conversation) = self.conversation_mut(conversation_id) {
+            conversation.active_record_id = Some(record_id.clone());
+        }
+        record_id
+    }
+
+    fn record_mut(&mut self, record_id: &str) -> Result<&mut MessageRecord> {
+        self.records
+            .iter_mut()
+            .find(|record| record.record_id == record_id)
+            .ok_or_else(|| anyhow!("Record {} does not exist", record_id))
+    }
+}
+
+/// 使用回复的内容创建一个版本
+fn version_of(record: &MessageRecord, version_index: u32) -> VersionRecord {
+    VersionRecord {
+        version_id: generate_unique_id(),
+        record_id: record.record_id.clone(),
+        version_content: record.record_content.clone(),
+        version_time: record.record_time,
+        version_state: record.record_state.clone(),
+        version_index,
+        selected: false,
+        token_usage: record.token_usage,
+        finish_info: record.finish_info.clone(),
+        response_schema: record.response_schema.clone(),
+        model: record.model.clone(),
+    }
+}
+
+impl ConversationStore for MemoryStore {
+    fn list(&self) -> Result<Vec<Conversation>> {
+        let mut conversations = self.data.lock().unwrap().conversations.clone();
+        conversations.sort_by_key(|conversation| Reverse(conversation.conversation_modify_time));
+        Ok(conversations)
+    }
+
+    fn load(&self, conversation_id: &str) -> Result<Conversation> {
+        let mut data = self.data.lock().unwrap();
+        let conversation = data.conversation_mut(conversation_id)?.clone();
+        let all_records = data.records_of(conversation_id);
+        Ok(Conversation {
+            conversation_records: active_branch(&all_records, conversation.active_record_id.clone()),
+            ..conversation
+        })
+    }
+
+    fn load_all(&self, conversation_id: &str) -> Result<Conversation> {
+        let mut data = self.data.lock().unwrap();
+        let conversation = data.conversation_mut(conversation_id)?.clone();
+        Ok(Conversation {
+            conversation_records: data.records_of(conversation_id),
+            ..conversation
+        })
+    }
+
+    fn save_exchange(
+        &self,
+        conversation_id: &str,
+        conversation_title: &str,
+        parent_record_id: Option<String>,
+        user_message: ChatMessage,
+        bot_message: ChatMessage,
+    ) -> Result<(String, String)> {
+        if matches!(user_message.sender, Sender::Never) || matches!(bot_message.sender, Sender::Never) {
+            bail!("Unsupported message sender")
+        }
+        let mut data = self.data.lock().unwrap();
+        let user_record_id = data.insert_message(conversation_id, conversation_title, user_message, parent_record_id);
+        let bot_record_id = data.insert_message(
+            conversation_id,
+            conversation_title,
+            bot_message,
+            Some(user_record_id.clone()),
+        );
+        Ok((user_record_id, bot_record_id))
+    }
+
+    fn save_message(
+        &self,
+        conversation_id: &str,
+        conversation_title: &str,
+        parent_record_id: Option<String>,
+        message: ChatMessage,
+    ) -> Result<String> {
+        if matches!(message.sender, Sender::Never) {
+            bail!("Unsupported message sender")
+        }
+        let mut data = self.data.lock().unwrap();
+        Ok(data.insert_message(conversation_id, conversation_title, message, parent_record_id))
+    }
+
+    fn delete(&self, conversation_id: &str) -> Result<()> {
+        let mut data = self.data.lock().unwrap();
+        data.conversations
+            .retain(|conversation| conversation.conversation_id != conversation_id);
+        data.records.retain(|record| record.conversation_id != conversation_id);
+        Ok(())
+    }
+
+    fn rename(&self, conversation_id: &str, conversation_title: &str) -> Result<()> {
+        let mut data = self.data.lock().unwrap();
+        data.conversation_mut(conversation_id)?.conversation_title = conversation_title.into();
+        Ok(())
+    }
+
+    fn modify_settings(
+        &self,
+        conversation_id: &str,
+        model: &str,
+        system_instruction: Option<&str>,
+        options: &str,
+    ) -> Result<()> {
+        let mut data = self.data.lock().unwrap();
+        let conversation = data.conversation_mut(conversation_id)?;
+        conversation.conversation_model = Some(model.into());
+        conversation.conversation_system_instruction = system_instruction.map(str::to_owned);
+        conversation.conversation_options = Some(options.into());
+        Ok(())
+    }
+
+    fn set_pinned(&self, record_id: &str, pinned: bool) -> Result<()> {
+        let mut data = self.data.lock().unwrap();
+        let record = data
+            .records
+            .iter_mut()
+            .find(|record| record.record_id == record_id)
+            .ok_or_else(|| anyhow!("Record {} does not exist", record_id))?;
+        record.pinned = pinned;
+        Ok(())
+    }
+
+    fn switch_branch(&self, conversation_id: &str, record_id: &str) -> Result<()> {
+        let mut data = self.data.lock().unwrap();
+        let mut active_record_id = record_id.to_owned();
+        // 沿着最新的子记录找到分支的最后一条记录
+        while let Some(child) = data
+            .records
+            .iter()
+            .filter(|record| record.parent_record_id.as_deref() == Some(active_record_id.as_str()))
+            .max_by_key(|record| record.record_time)
+        {
+            active_record_id = child.record_id.clone();
+        }
+        data.conversation_mut(conversation_id)?.active_record_id = Some(active_record_id);
+        Ok(())
+    }
+
+    fn query_image_record_ids(&self, record_id: &str) -> Result<Vec<String>> {
+        let data = self.data.lock().unwrap();
+        Ok(data
+            .records
+            .iter()
+            .filter(|record| record.record_id == record_id)
+            .flat_map(|record| record.image_records.iter())
+            .filter(|image_record| image_record.image_type.starts_with("image/"))
+            .map(|image_record| image_record.image_record_id.clone())
+            .collect())
+    }
+
+    fn save_message_version(&self, conversation_id: &str, message: ChatMessage) -> Result<()> {
+        let mut data = self.data.lock().unwrap();
+        let record = data.record_mut(&message.record_id)?;
+        // 第一次重新生成时，先将原有的回复保存为第一个版本
+        if record.record_versions.is_empty() {
+            let version = version_of(record, 0);
+            record.record_versions.push(version);
+        }
+        record.record_content = message.message;
+        record.record_time = message.date_time;
+        record.record_state = message.state;
+        record.token_usage = message.usage;
+        record.finish_info = message.finish;
+        record.response_schema = message.response_schema;
+        record.model = message.model;
+        let mut version = version_of(record, record.record_versions.len() as u32);
+        version.selected = true;
+        for version in record.record_versions.iter_mut() {
+            version.selected = false;
+        }
+        record.record_versions.push(version);
+        data.conversation_mut(conversation_id)?.conversation_modify_time = message.date_time;
+        Ok(())
+    }
+
+    fn select_message_version(&self, record_id: &str, version_index: usize) -> Result<()> {
+        let mut data = self.data.lock().unwrap();
+        let record = data.record_mut(record_id)?;
+        let Some(version) = record
+            .record_versions
+            .iter()
+            .find(|version| version.version_index as usize == version_index)
+            .cloned()
+        else {
+            bail!("Version {} of record {} does not exist", version_index, record_id)
+        };
+        for version in record.record_versions.iter_mut() {
+            version.selected = version.version_index as usize == version_index;
+        }
+        record.record_content = version.version_content;
+        record.record_time = version.version_time;
+        record.record_state = version.version_state;
+        record.token_usage = version.token_usage;
+        record.finish_info = version.finish_info;
+        record.response_schema = version.response_schema;
+        record.model = version.model;
+        Ok(())
+    }
+
+    fn search(&self, keyword: &str) -> Result<Vec<SearchResult>> {
+        let keyword = keyword.trim();
+        if keyword.is_empty() {
+            return Ok(Vec::new());
+        }
+        let data = self.data.lock().unwrap();
+        let lower_keyword = keyword.to_lowercase();
+        let mut conversations = data.conversations.clone();
+        conversations.sort_by_key(|conversation| Reverse(conversation.conversation_modify_time));
+        // 按包含关系匹配会话标题与所有记录，按会话修改时间排序
+        let mut results = Vec::new();
+        for conversation in conversations.iter() {
+            let contents = std::iter::once((None, &conversation.conversation_title)).chain(
+                data.records
+                    .iter()
+                    .filter(|record| record.conversation_id == conversation.conversation_id)
+                    .map(|record| (Some(record.record_id.clone()), &record.record_content)),
+            );
+            for (record_id, content) in contents {
+                if content.to_lowercase().contains(&lower_keyword) {
+                    results.push(SearchResult {
+                        conversation_id: conversation.conversation_id.clone(),
+                        conversation_title: conversation.conversation_title.clone(),
+                        record_id,
+                        snippet: make_snippet(content, keyword).replace(['\r', '\n'], " "),
+                        conversation_modify_time: conversation.conversation_modify_time,
+                    });
+                }
+            }
+        }
+        results.truncate(SEARCH_LIMIT);
+        Ok(results)
+    }
+
+    fn find_duplicate_conversation(&self, conversation: &Conversation) -> Result<Option<String>> {
+        let Some(first_record) = conversation.conversation_records.first() else {
+            return Ok(None);
+        };
+        let data = self.data.lock().unwrap();
+        // 不同来源的时间精度不同，只比较到秒
+        let start_time = conversation.conversation_start_time.timestamp();
+        Ok(data
+            .records
+            .iter()
+            .filter(|record| record.parent_record_id.is_none() && record.record_content == first_record.record_content)
+            .filter_map(|record| {
+                data.conversations
+                    .iter()
+                    .find(|conversation| conversation.conversation_id == record.conversation_id)
+            })
+            .find(|conversation| conversation.conversation_start_time.timestamp() == start_time)
+            .map(|conversation| conversation.conversation_id.clone()))
+    }
+
+    fn import_conversation(&self, conversation: &Conversation, _images: &HashMap<String, Vec<u8>>) -> Result<String> {
+        let mut data = self.data.lock().unwrap();
+        let conversation_id = generate_unique_id();
+        // 导入前的记录 id 到新记录 id 的映射，用于重建父记录关系
+        let record_ids: HashMap<&str, String> = conversation
+            .conversation_records
+            .iter()
+            .map(|record| (record.record_id.as_str(), generate_unique_id()))
+            .collect();
+        let active_record_id = conversation
+            .active_record_id
+            .as_deref()
+            .or(conversation
+                .conversation_records
+                .last()
+                .map(|record| record.record_id.as_str()))
+            .and_then(|record_id| record_ids.get(record_id))
+            .cloned();
+        data.conversations.push(Conversation {
+            conversation_id: conversation_id.clone(),
+            active_record_id,
+            conversation_records: Vec::new(),
+            ..conversation.clone()
+        });
+        for record in conversation.conversation_records.iter() {
+            let record_id = record_ids[record.record_id.as_str()].clone();
+            // 图片数据只在数据库存储中写入缓存
+            let image_records = record
+                .image_records
+                .iter()
+                .map(|image_record| ImageRecord {
+                    image_record_id: generate_unique_id(),
+                    record_id: record_id.clone(),
+                    ..image_record.clone()
+                })
+                .collect();
+            let record_versions = record
+                .record_versions
+                .iter()
+                .map(|version| VersionRecord {
+                    version_id: generate_unique_id(),
+                    record_id: record_id.clone(),
+                    ..version.clone()
+                })
+                .collect();
+            data.records.push(MessageRecord {
+                record_id,
+                conversation_id: conversation_id.clone(),
+                parent_record_id: record
+                    .parent_record_id
+                    .as_deref()
+                    .and_then(|parent_record_id| record_ids.get(parent_record_id))
+                    .cloned(),
+                image_records,
+                record_versions,
+                sibling_record_ids: Vec::new(),
+                ..record.clone()
+            });
+        }
+        Ok(conversation_id)
+    }
+}
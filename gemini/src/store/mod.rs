@@ -0,0 +1,619 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serde_json::Value;
+
+use crate::model::{
+    db::{Conversation, MessageRecord, SearchResult, SNIPPET_END, SNIPPET_START},
+    view::{ChatMessage, Sender},
+};
+
+#[cfg(test)]
+pub(crate) mod memory_store;
+pub(crate) mod sqlite_store;
+
+/// 搜索结果的最大数量
+pub(crate) const SEARCH_LIMIT: usize = 50;
+/// 搜索摘要中关键词前后保留的字符数
+pub(crate) const SNIPPET_CONTEXT: usize = 16;
+
+/// 会话存储，负责会话与聊天记录的增删改查
+pub(crate) trait ConversationStore {
+    /// 查询所有会话，按最后修改时间倒序排列，不包含聊天记录
+    fn list(&self) -> Result<Vec<Conversation>>;
+
+    /// 查询会话详情，只包含当前分支上的聊天记录
+    fn load(&self, conversation_id: &str) -> Result<Conversation>;
+
+    /// 查询会话详情，包含所有分支上的聊天记录，用于无损导出
+    fn load_all(&self, conversation_id: &str) -> Result<Conversation>;
+
+    /// 保存一轮对话，用户消息作为父记录的子记录，回复作为用户消息的子记录，并成为当前分支的最后一条记录
+    ///
+    /// 会话不存在时使用给定的标题新建会话，两条消息同时保存成功或失败，返回用户消息与回复的记录 id
+    fn save_exchange(
+        &self,
+        conversation_id: &str,
+        conversation_title: &str,
+        parent_record_id: Option<String>,
+        user_message: ChatMessage,
+        bot_message: ChatMessage,
+    ) -> Result<(String, String)>;
+
+    /// 保存一条消息，作为父记录的子记录，并成为当前分支的最后一条记录，返回记录 id
+    ///
+    /// 用于保存没有回复的工具执行结果，会话不存在时使用给定的标题新建会话
+    fn save_message(
+        &self,
+        conversation_id: &str,
+        conversation_title: &str,
+        parent_record_id: Option<String>,
+        message: ChatMessage,
+    ) -> Result<String>;
+
+    /// 删除会话及其所有聊天记录
+    fn delete(&self, conversation_id: &str) -> Result<()>;
+
+    /// 修改会话标题
+    fn rename(&self, conversation_id: &str, conversation_title: &str) -> Result<()>;
+
+    /// 修改会话使用的模型、系统指令与生成配置，生成配置为 JSON 格式
+    fn modify_settings(
+        &self,
+        conversation_id: &str,
+        model: &str,
+        system_instruction: Option<&str>,
+        options: &str,
+    ) -> Result<()>;
+
+    /// 固定或取消固定一条消息
+    fn set_pinned(&self, record_id: &str, pinned: bool) -> Result<()>;
+
+    /// 切换到指定记录所在的分支，沿着最新的子记录找到该分支的最后一条记录，并设为当前分支
+    fn switch_branch(&self, conversation_id: &str, record_id: &str) -> Result<()>;
+
+    /// 查询单条记录的所有图片附件记录 id，按添加顺序排列
+    fn query_image_record_ids(&self, record_id: &str) -> Result<Vec<String>>;
+
+    /// 为回复新增一个版本，并将其设为当前版本，第一次新增版本时先将原有的回复保存为第一个版本
+    fn save_message_version(&self, conversation_id: &str, message: ChatMessage) -> Result<()>;
+
+    /// 切换回复的当前版本，回复记录中始终保存当前选中的版本
+    fn select_message_version(&self, record_id: &str, version_index: usize) -> Result<()>;
+
+    /// 在所有会话的聊天记录与标题中搜索关键词，摘要中的关键词使用 `SNIPPET_START` 与 `SNIPPET_END` 标记
+    fn search(&self, keyword: &str) -> Result<Vec<SearchResult>>;
+
+    /// 查找与给定会话重复的会话，开始时间与第一条记录内容都相同时视为重复，返回重复会话的 id
+    fn find_duplicate_conversation(&self, conversation: &Conversation) -> Result<Option<String>>;
+
+    /// 导入会话，会话、记录、图片与版本均使用新的 id，保留原有的时间与排序，返回新会话的 id
+    ///
+    /// 图片数据的键为导入前的图片记录 id，值为已压缩的图片内容
+    fn import_conversation(&self, conversation: &Conversation, images: &HashMap<String, Vec<u8>>) -> Result<String>;
+}
+
+/// 保存到数据库中的发送者名称
+pub(crate) fn sender_name(sender: &Sender) -> &'static str {
+    match sender {
+        Sender::User(_) => "User",
+        Sender::Bot => "Bot",
+        Sender::ToolCall(_) => "ToolCall",
+        Sender::ToolResult(_) => "ToolResult",
+        Sender::Never => "Never",
+    }
+}
+
+/// 工具消息保存到数据库中的结构化数据，JSON 格式，其他消息为空
+pub(crate) fn sender_payload(sender: &Sender) -> Option<String> {
+    match sender {
+        Sender::ToolCall(calls) => serde_json::to_string(calls).ok(),
+        Sender::ToolResult(responses) => serde_json::to_string(responses).ok(),
+        _ => None,
+    }
+}
+
+/// 回复生成时使用的回复格式约束保存到数据库中的内容，JSON 格式，未设置约束时为空
+pub(crate) fn schema_column(schema: &Option<Value>) -> Option<String> {
+    schema.as_ref().and_then(|schema| serde_json::to_string(schema).ok())
+}
+
+/// 根据保存的内容还原回复格式约束，内容为空或无法解析时视为未设置约束
+pub(crate) fn parse_schema(column: Option<String>) -> Option<Value> {
+    column.and_then(|column| serde_json::from_str(&column).ok())
+}
+
+/// 根据保存的发送者名称与结构化数据还原发送者，用户消息的图片路径需要另外补全
+pub(crate) fn parse_sender(name: &str, payload: Option<String>) -> Sender {
+    let payload = payload.unwrap_or_default();
+    match name {
+        "User" => Sender::User(Vec::new()),
+        "Bot" => Sender::Bot,
+        "ToolCall" => Sender::ToolCall(serde_json::from_str(&payload).unwrap_or_default()),
+        "ToolResult" => Sender::ToolResult(serde_json::from_str(&payload).unwrap_or_default()),
+        _ => Sender::Never,
+    }
+}
+
+/// 从当前分支的最后一条记录开始，沿父记录向上查找，得到当前分支上的所有记录，并记录同一父记录下的所有分支
+///
+/// `all_records` 为会话中的所有记录，当前分支为空时使用最后一条记录
+pub(crate) fn active_branch(all_records: &[MessageRecord], active_record_id: Option<String>) -> Vec<MessageRecord> {
+    let mut branch_records = Vec::new();
+    let mut current = active_record_id.or_else(|| all_records.last().map(|record| record.record_id.clone()));
+    while let Some(record_id) = current {
+        // 避免错误数据导致的循环引用
+        if branch_records.len() >= all_records.len() {
+            break;
+        }
+        let Some(record) = all_records.iter().find(|record| record.record_id == record_id) else {
+            break;
+        };
+        current = record.parent_record_id.clone();
+        branch_records.push(record.clone());
+    }
+    branch_records.reverse();
+    for record in branch_records.iter_mut() {
+        record.sibling_record_ids = all_records
+            .iter()
+            .filter(|sibling| sibling.parent_record_id == record.parent_record_id)
+            .map(|sibling| sibling.record_id.clone())
+            .collect();
+    }
+    branch_records
+}
+
+/// 截取关键词所在位置前后的内容作为摘要，并标记关键词，忽略大小写
+pub(crate) fn make_snippet(content: &str, keyword: &str) -> String {
+    let chars: Vec<char> = content.chars().collect();
+    let lower = |c: &char| c.to_lowercase().next().unwrap_or(*c);
+    let keyword: Vec<char> = keyword.chars().map(|c| lower(&c)).collect();
+    let position = chars
+        .windows(keyword.len())
+        .position(|window| window.iter().map(lower).eq(keyword.iter().copied()));
+    let Some(position) = position else {
+        return chars.iter().take(SNIPPET_CONTEXT * 2).collect();
+    };
+    let start = position.saturating_sub(SNIPPET_CONTEXT);
+    let end = (position + keyword.len() + SNIPPET_CONTEXT).min(chars.len());
+    let mut snippet = String::new();
+    if start > 0 {
+        snippet.push('…');
+    }
+    snippet.extend(&chars[start..position]);
+    snippet.push(SNIPPET_START);
+    snippet.extend(&chars[position..position + keyword.len()]);
+    snippet.push(SNIPPET_END);
+    snippet.extend(&chars[position + keyword.len()..end]);
+    if end < chars.len() {
+        snippet.push('…');
+    }
+    snippet
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Local, TimeZone};
+
+    use super::{memory_store::MemoryStore, sqlite_store::SqliteStore, *};
+    use crate::model::view::{FinishInfo, FunctionCall, FunctionResponse, MessageState, SafetyRating, TokenUsage};
+
+    fn message(sender: Sender, content: &str, minute: i64) -> ChatMessage {
+        ChatMessage {
+            success: true,
+            message: content.into(),
+            sender,
+            date_time: Local.with_ymd_and_hms(2026, 10, 17, 9, 0, 0).unwrap() + Duration::minutes(minute),
+            state: MessageState::Complete,
+            ..Default::default()
+        }
+    }
+
+    fn finish() -> FinishInfo {
+        FinishInfo {
+            reason: "MAX_TOKENS".into(),
+            safety_ratings: vec![SafetyRating {
+                category: "HARM_CATEGORY_HARASSMENT".into(),
+                probability: "MEDIUM".into(),
+                blocked: false,
+            }],
+        }
+    }
+
+    fn save(store: &impl ConversationStore, id: &str, minute: i64, parent: Option<String>) -> (String, String) {
+        store
+            .save_exchange(
+                id,
+                &format!("title {}", id),
+                parent,
+                message(Sender::User(Vec::new()), &format!("question {}", minute), minute),
+                ChatMessage {
+                    usage: Some(TokenUsage {
+                        prompt_tokens: 10,
+                        response_tokens: minute as u32,
+                    }),
+                    finish: Some(finish()),
+                    model: Some("gemini-1.5-flash".into()),
+                    ..message(Sender::Bot, &format!("answer {}", minute), minute + 1)
+                },
+            )
+            .unwrap()
+    }
+
+    fn create_and_load(store: impl ConversationStore) {
+        let (user_id, bot_id) = save(&store, "a", 0, None);
+        let conversation = store.load("a").unwrap();
+        assert_eq!(conversation.conversation_title, "title a");
+        assert_eq!(conversation.active_record_id, Some(bot_id.clone()));
+        let records = &conversation.conversation_records;
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].record_id, user_id);
+        assert_eq!(records[0].record_content, "question 0");
+        assert!(matches!(records[0].record_sender, Sender::User(_)));
+        assert_eq!(records[1].record_id, bot_id);
+        assert_eq!(records[1].parent_record_id, Some(user_id));
+        assert_eq!(records[1].record_content, "answer 0");
+        assert_eq!(records[0].token_usage, None);
+        assert_eq!(
+            records[1].token_usage,
+            Some(TokenUsage {
+                prompt_tokens: 10,
+                response_tokens: 0
+            })
+        );
+        assert_eq!(records[0].finish_info, None);
+        assert_eq!(records[1].finish_info, Some(finish()));
+        assert_eq!(records[0].model, None);
+        assert_eq!(records[1].model.as_deref(), Some("gemini-1.5-flash"));
+        assert_eq!(
+            conversation.conversation_start_time,
+            message(Sender::Bot, "", 0).date_time
+        );
+        assert_eq!(
+            conversation.conversation_modify_time,
+            message(Sender::Bot, "", 1).date_time
+        );
+        // 继续对话时追加到当前分支
+        let (_, last_id) = save(&store, "a", 5, Some(bot_id));
+        let conversation = store.load("a").unwrap();
+        assert_eq!(conversation.conversation_records.len(), 4);
+        assert_eq!(conversation.active_record_id, Some(last_id.clone()));
+        // 工具消息的结构化数据随记录一起保存
+        let call = FunctionCall {
+            name: "read_file".into(),
+            args: serde_json::json!({ "path": "Cargo.toml" }),
+        };
+        let response = FunctionResponse {
+            name: "read_file".into(),
+            response: serde_json::json!({ "output": "[package]" }),
+        };
+        store
+            .save_exchange(
+                "a",
+                "title a",
+                Some(last_id),
+                message(Sender::ToolResult(vec![response.clone()]), "read_file output", 7),
+                message(Sender::ToolCall(vec![call.clone()]), "read_file", 8),
+            )
+            .unwrap();
+        let records = store.load("a").unwrap().conversation_records;
+        assert!(matches!(&records[4].record_sender, Sender::ToolResult(responses) if *responses == [response.clone()]));
+        assert!(matches!(&records[5].record_sender, Sender::ToolCall(calls) if *calls == [call]));
+        // 没有回复的工具执行结果单独保存
+        let result_id = store
+            .save_message(
+                "a",
+                "title a",
+                Some(records[5].record_id.clone()),
+                ChatMessage {
+                    state: MessageState::Cancelled,
+                    ..message(Sender::ToolResult(vec![response]), "read_file output", 9)
+                },
+            )
+            .unwrap();
+        let conversation = store.load("a").unwrap();
+        assert_eq!(conversation.active_record_id, Some(result_id));
+        assert_eq!(conversation.conversation_records.len(), 7);
+        assert_eq!(
+            conversation.conversation_records[6].record_state,
+            MessageState::Cancelled
+        );
+        assert_eq!(
+            conversation.conversation_modify_time,
+            message(Sender::Bot, "", 9).date_time
+        );
+        assert!(store.load("missing").is_err());
+    }
+
+    fn list_ordering(store: impl ConversationStore) {
+        save(&store, "old", 0, None);
+        save(&store, "new", 10, None);
+        save(&store, "middle", 5, None);
+        let ids: Vec<String> = store
+            .list()
+            .unwrap()
+            .into_iter()
+            .map(|conversation| conversation.conversation_id)
+            .collect();
+        assert_eq!(ids, ["new", "middle", "old"]);
+        // 继续对话后排在最前面
+        let parent = store.load("old").unwrap().active_record_id;
+        save(&store, "old", 20, parent);
+        assert_eq!(store.list().unwrap()[0].conversation_id, "old");
+    }
+
+    fn delete(store: impl ConversationStore) {
+        save(&store, "a", 0, None);
+        save(&store, "b", 1, None);
+        store.delete("a").unwrap();
+        let conversations = store.list().unwrap();
+        assert_eq!(conversations.len(), 1);
+        assert_eq!(conversations[0].conversation_id, "b");
+        assert!(store.load("a").is_err());
+        assert_eq!(store.load("b").unwrap().conversation_records.len(), 2);
+    }
+
+    fn rename_and_settings(store: impl ConversationStore) {
+        save(&store, "a", 0, None);
+        store.rename("a", "renamed").unwrap();
+        store
+            .modify_settings("a", "gemini-1.5-pro", Some("be terse"), r#"{"temperature":0.5}"#)
+            .unwrap();
+        let conversation = store.load("a").unwrap();
+        assert_eq!(conversation.conversation_title, "renamed");
+        assert_eq!(conversation.conversation_model.as_deref(), Some("gemini-1.5-pro"));
+        assert_eq!(
+            conversation.conversation_system_instruction.as_deref(),
+            Some("be terse")
+        );
+        assert_eq!(
+            conversation.conversation_options.as_deref(),
+            Some(r#"{"temperature":0.5}"#)
+        );
+        assert_eq!(store.list().unwrap()[0].conversation_title, "renamed");
+    }
+
+    fn branches(store: impl ConversationStore) {
+        let (first_user, first_bot) = save(&store, "a", 0, None);
+        let (second_user, _) = save(&store, "a", 2, Some(first_bot.clone()));
+        // 编辑第二条用户消息，从同一父记录创建新的分支
+        let (edited_user, edited_bot) = save(&store, "a", 4, Some(first_bot.clone()));
+        let conversation = store.load("a").unwrap();
+        let records = &conversation.conversation_records;
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[2].record_id, edited_user);
+        assert_eq!(
+            records[2].sibling_record_ids,
+            [second_user.clone(), edited_user.clone()]
+        );
+        assert_eq!(records[0].sibling_record_ids, [first_user]);
+        // 切换回原来的分支
+        store.switch_branch("a", &second_user).unwrap();
+        let conversation = store.load("a").unwrap();
+        assert_eq!(conversation.conversation_records[2].record_id, second_user);
+        assert_eq!(conversation.conversation_records[3].record_content, "answer 2");
+        store.switch_branch("a", &edited_user).unwrap();
+        assert_eq!(store.load("a").unwrap().active_record_id, Some(edited_bot));
+        // 所有分支上的记录都会被查询
+        let records = store.load_all("a").unwrap().conversation_records;
+        assert_eq!(records.len(), 6);
+        assert!(records.iter().any(|record| record.record_id == second_user));
+    }
+
+    fn versions(store: impl ConversationStore) {
+        let (user_id, bot_id) = save(&store, "a", 0, None);
+        let regenerated = |content: &str, minute: i64| ChatMessage {
+            record_id: bot_id.clone(),
+            ..message(Sender::Bot, content, minute)
+        };
+        assert_eq!(
+            store.load("a").unwrap().conversation_records[1].finish_info,
+            Some(finish())
+        );
+        let schema = serde_json::json!({ "type": "object" });
+        store
+            .save_message_version(
+                "a",
+                ChatMessage {
+                    response_schema: Some(schema.clone()),
+                    ..regenerated("answer 2", 2)
+                },
+            )
+            .unwrap();
+        store.save_message_version("a", regenerated("answer 3", 3)).unwrap();
+        let record = store.load("a").unwrap().conversation_records[1].clone();
+        assert_eq!(record.record_content, "answer 3");
+        let versions: Vec<(&str, bool)> = record
+            .record_versions
+            .iter()
+            .map(|version| (version.version_content.as_str(), version.selected))
+            .collect();
+        assert_eq!(versions, [("answer 0", false), ("answer 2", false), ("answer 3", true)]);
+        assert_eq!(record.record_versions[1].response_schema, Some(schema.clone()));
+        assert_eq!(
+            store.list().unwrap()[0].conversation_modify_time,
+            message(Sender::Bot, "", 3).date_time
+        );
+        // 切换版本后回复记录保存选中的版本
+        store.select_message_version(&bot_id, 0).unwrap();
+        let record = store.load("a").unwrap().conversation_records[1].clone();
+        assert_eq!(record.record_content, "answer 0");
+        assert_eq!(record.record_time, message(Sender::Bot, "", 1).date_time);
+        // 结束原因与安全评级随版本一起切换
+        assert_eq!(record.finish_info, Some(finish()));
+        assert_eq!(record.model.as_deref(), Some("gemini-1.5-flash"));
+        store.select_message_version(&bot_id, 2).unwrap();
+        assert_eq!(store.load("a").unwrap().conversation_records[1].finish_info, None);
+        assert_eq!(store.load("a").unwrap().conversation_records[1].model, None);
+        // 生成时使用的回复格式约束随版本一起切换
+        assert_eq!(store.load("a").unwrap().conversation_records[1].response_schema, None);
+        store.select_message_version(&bot_id, 1).unwrap();
+        assert_eq!(
+            store.load("a").unwrap().conversation_records[1].response_schema,
+            Some(schema)
+        );
+        store.select_message_version(&bot_id, 0).unwrap();
+        let record = store.load("a").unwrap().conversation_records[1].clone();
+        assert!(record.record_versions[0].selected);
+        assert!(!record.record_versions[1].selected);
+        assert!(!record.record_versions[2].selected);
+        // 不存在的版本返回错误，回复记录保持不变
+        assert!(store.select_message_version(&bot_id, 3).is_err());
+        let unchanged = store.load("a").unwrap().conversation_records[1].clone();
+        assert_eq!(unchanged.record_content, "answer 0");
+        assert_eq!(unchanged.record_state, MessageState::Complete);
+        assert!(unchanged.record_versions[0].selected);
+        // 搜索索引同步更新
+        assert!(store.search("answer 3").unwrap().is_empty());
+        assert_eq!(store.search("answer 0").unwrap()[0].record_id, Some(bot_id));
+        assert!(store.query_image_record_ids(&user_id).unwrap().is_empty());
+    }
+
+    fn search(store: impl ConversationStore) {
+        let (user_id, _) = save(&store, "a", 0, None);
+        save(&store, "b", 5, None);
+        store.rename("b", "Weekly Report").unwrap();
+        assert!(store.search("  ").unwrap().is_empty());
+        // 全文搜索，摘要中标记关键词
+        let results = store.search("question 0").unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].conversation_id, "a");
+        assert_eq!(results[0].record_id, Some(user_id));
+        assert!(results[0]
+            .snippet
+            .contains(&format!("{}question 0{}", SNIPPET_START, SNIPPET_END)));
+        // 命中会话标题时没有记录 id
+        let results = store.search("weekly").unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].record_id, None);
+        // 少于 3 个字符的关键词按包含关系匹配，按会话修改时间排序
+        let ids: Vec<String> = store
+            .search("5")
+            .unwrap()
+            .into_iter()
+            .map(|result| result.conversation_id)
+            .collect();
+        assert_eq!(ids, ["b", "b"]);
+        assert!(store.search("missing").unwrap().is_empty());
+    }
+
+    fn import_and_duplicates(store: impl ConversationStore) {
+        let (_, first_bot) = save(&store, "a", 0, None);
+        save(&store, "a", 2, Some(first_bot.clone()));
+        save(&store, "a", 4, Some(first_bot));
+        let conversation = store.load_all("a").unwrap();
+        assert_eq!(
+            store.find_duplicate_conversation(&conversation).unwrap().as_deref(),
+            Some("a")
+        );
+        // 开始时间不同的会话不是重复会话
+        let other = Conversation {
+            conversation_start_time: message(Sender::Bot, "", 30).date_time,
+            ..conversation.clone()
+        };
+        assert_eq!(store.find_duplicate_conversation(&other).unwrap(), None);
+        assert_eq!(
+            store.find_duplicate_conversation(&Conversation::default()).unwrap(),
+            None
+        );
+        // 导入后保留所有分支与当前分支
+        let imported_id = store.import_conversation(&conversation, &HashMap::new()).unwrap();
+        assert_ne!(imported_id, "a");
+        let imported = store.load("a").unwrap();
+        let copied = store.load(&imported_id).unwrap();
+        assert_eq!(copied.conversation_title, "title a");
+        assert_eq!(copied.conversation_start_time, imported.conversation_start_time);
+        let contents = |conversation: &Conversation| {
+            conversation
+                .conversation_records
+                .iter()
+                .map(|record| record.record_content.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(contents(&copied), contents(&imported));
+        assert_eq!(copied.conversation_records[2].sibling_record_ids.len(), 2);
+        assert_eq!(store.load_all(&imported_id).unwrap().conversation_records.len(), 6);
+    }
+
+    /// 使用同一组用例测试所有存储实现
+    macro_rules! store_tests {
+        ($name:ident, $store:expr) => {
+            mod $name {
+                use super::*;
+
+                #[test]
+                fn create_and_load() {
+                    super::create_and_load($store);
+                }
+
+                #[test]
+                fn list_ordering() {
+                    super::list_ordering($store);
+                }
+
+                #[test]
+                fn delete() {
+                    super::delete($store);
+                }
+
+                #[test]
+                fn rename_and_settings() {
+                    super::rename_and_settings($store);
+                }
+
+                #[test]
+                fn branches() {
+                    super::branches($store);
+                }
+
+                #[test]
+                fn versions() {
+                    super::versions($store);
+                }
+
+                #[test]
+                fn search() {
+                    super::search($store);
+                }
+
+                #[test]
+                fn import_and_duplicates() {
+                    super::import_and_duplicates($store);
+                }
+            }
+        };
+    }
+
+    store_tests!(sqlite, SqliteStore::open_in_memory().unwrap());
+    store_tests!(memory, MemoryStore::default());
+
+    #[test]
+    fn sqlite_exchange_is_atomic() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        // 回复无法保存时，用户消息与新建的会话一并回滚
+        let error = store.save_exchange(
+            "a",
+            "title",
+            None,
+            message(Sender::User(Vec::new()), "question", 0),
+            message(Sender::Never, "answer", 1),
+        );
+        assert!(error.is_err());
+        assert!(store.list().unwrap().is_empty());
+        // 之后保存的会话中不包含回滚的用户消息
+        save(&store, "a", 2, None);
+        assert_eq!(store.load_all("a").unwrap().conversation_records.len(), 2);
+    }
+
+    #[test]
+    fn sqlite_parameters_are_bound() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        save(&store, "a", 0, None);
+        save(&store, "b", 1, None);
+        // 包含引号的 id 不会被当作 SQL 执行
+        store.delete("a' OR '1' = '1").unwrap();
+        store.rename("b", "it's").unwrap();
+        assert_eq!(store.list().unwrap().len(), 2);
+        assert_eq!(store.load("b").unwrap().conversation_title, "it's");
+    }
+}
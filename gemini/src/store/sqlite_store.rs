@@ -0,0 +1,954 @@
+use std::{
+    collections::HashMap,
+    fs::create_dir_all,
+    path::Path,
+    sync::{Mutex, MutexGuard},
+};
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Local};
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::model::{
+    db::{Conversation, ImageRecord, MessageRecord, SearchResult, VersionRecord, SNIPPET_END, SNIPPET_START},
+    view::{ChatMessage, FinishInfo, Sender, TokenUsage},
+};
+use crate::utils::attachment_utils::{cache_attachment, mime_type_by_extension};
+use crate::utils::db_utils::generate_unique_id;
+use crate::utils::image_utils::{delete_image_cache, save_image_cache};
+use crate::utils::migration_utils::{migrate, MIGRATIONS};
+
+use super::{
+    active_branch, make_snippet, parse_schema, parse_sender, schema_column, sender_name, sender_payload,
+    ConversationStore, SEARCH_LIMIT, SNIPPET_CONTEXT,
+};
+
+/// 基于 SQLite 的会话存储，所有语句均使用绑定参数，修改多张表时在同一事务中执行
+pub(crate) struct SqliteStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    /// 打开数据库文件，所在目录不存在时创建
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+        Ok(Self {
+            conn: Mutex::new(Connection::open(path)?),
+        })
+    }
+
+    /// 打开内存数据库并执行所有迁移脚本
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self> {
+        let store = Self {
+            conn: Mutex::new(Connection::open_in_memory()?),
+        };
+        store.migrate(None)?;
+        Ok(store)
+    }
+
+    /// 执行未执行的迁移脚本，已有数据的数据库在迁移前先备份到 `backup`
+    pub fn migrate(&self, backup: Option<&Path>) -> Result<Vec<&'static str>> {
+        migrate(&mut self.connection(), MIGRATIONS, backup)
+    }
+
+    /// 数据库连接
+    fn connection(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap()
+    }
+
+    /// 在同一事务中保存消息，保存失败时删除事务开始前已缓存的附件
+    fn transaction_with_attachments<T>(
+        &self,
+        attachments: &[(String, String)],
+        save: impl FnOnce(&Connection) -> Result<T>,
+    ) -> Result<T> {
+        let mut conn = self.connection();
+        let result = conn.transaction().map_err(anyhow::Error::from).and_then(|tx| {
+            let value = save(&tx)?;
+            tx.commit()?;
+            Ok(value)
+        });
+        if result.is_err() {
+            for (image_record_id, _) in attachments {
+                let _ = delete_image_cache(image_record_id.clone());
+            }
+        }
+        result
+    }
+
+    /// 查询会话详情，`all_branches` 为假时只包含当前分支上的聊天记录
+    fn load_records(&self, conversation_id: &str, all_branches: bool) -> Result<Conversation> {
+        let conn = self.connection();
+        let Some(conversation) = conn
+            .query_row(
+                r#"SELECT conversation_id, conversation_title, conversation_start_time, conversation_modify_time, active_record_id,
+                conversation_model, conversation_system_instruction, conversation_options
+                FROM gemini_conversation WHERE conversation_id = ?1"#,
+                [conversation_id],
+                map_conversation,
+            )
+            .optional()?
+        else {
+            bail!("Conversation {} does not exist", conversation_id)
+        };
+        let mut stmt = conn.prepare(
+            r#"SELECT
+            record_id, record_content, record_time, record_sender, sort_index, record_state, parent_record_id,
+            prompt_token_count, response_token_count, pinned, finish_reason, safety_ratings, tool_payload, response_schema, model
+            FROM gemini_message_record
+            WHERE conversation_id = ?1
+            ORDER BY sort_index ASC, record_time ASC"#,
+        )?;
+        let all_records = stmt
+            .query_map([conversation_id], |row| {
+                let record_sender: String = row.get(3)?;
+                let record_state: Option<String> = row.get(5)?;
+                Ok(MessageRecord {
+                    conversation_id: conversation_id.into(),
+                    record_id: row.get(0)?,
+                    record_content: row.get(1)?,
+                    record_time: row.get(2)?,
+                    record_sender: parse_sender(&record_sender, row.get(12)?),
+                    sort_index: row.get(4)?,
+                    record_state: record_state.and_then(|state| state.parse().ok()).unwrap_or_default(),
+                    image_records: vec![],
+                    record_versions: vec![],
+                    parent_record_id: row.get(6)?,
+                    sibling_record_ids: vec![],
+                    token_usage: TokenUsage::from_columns(row.get(7)?, row.get(8)?),
+                    finish_info: FinishInfo::from_columns(row.get(10)?, row.get(11)?),
+                    response_schema: parse_schema(row.get(13)?),
+                    model: row.get(14)?,
+                    pinned: row.get(9)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<MessageRecord>>>()?;
+        let mut conversation_records = if all_branches {
+            all_records
+        } else {
+            active_branch(&all_records, conversation.active_record_id.clone())
+        };
+
+        // 查询会话中所有的图片，按添加顺序排列
+        let mut stmt = conn.prepare(
+            r#"SELECT
+            image_record_id, gemini_image_record.record_id, image_path, image_type
+            FROM gemini_image_record INNER JOIN gemini_message_record
+            ON gemini_image_record.record_id = gemini_message_record.record_id
+            WHERE conversation_id = ?1
+            ORDER BY gemini_image_record.rowid ASC"#,
+        )?;
+        let image_records = stmt.query_map([conversation_id], |row| {
+            Ok(ImageRecord {
+                image_record_id: row.get(0)?,
+                record_id: row.get(1)?,
+                image_path: row.get(2)?,
+                image_type: row.get(3)?,
+            })
+        })?;
+        for image_record in image_records {
+            let image_record = image_record?;
+            if let Some(record) = conversation_records
+                .iter_mut()
+                .find(|record| record.record_id == image_record.record_id)
+            {
+                if let Sender::User(image_paths) = &mut record.record_sender {
+                    image_paths.push(image_record.image_path.clone());
+                }
+                record.image_records.push(image_record);
+            }
+        }
+
+        // 查询会话中所有回复的版本
+        let mut stmt = conn.prepare(
+            r#"SELECT
+            version_id, gemini_message_version.record_id, version_content, version_time, version_state, version_index, selected,
+            gemini_message_version.prompt_token_count, gemini_message_version.response_token_count,
+            gemini_message_version.finish_reason, gemini_message_version.safety_ratings, gemini_message_version.response_schema,
+            gemini_message_version.model
+            FROM gemini_message_version INNER JOIN gemini_message_record
+            ON gemini_message_version.record_id = gemini_message_record.record_id
+            WHERE conversation_id = ?1
+            ORDER BY version_index ASC"#,
+        )?;
+        let versions = stmt.query_map([conversation_id], |row| {
+            let version_state: Option<String> = row.get(4)?;
+            Ok(VersionRecord {
+                version_id: row.get(0)?,
+                record_id: row.get(1)?,
+                version_content: row.get(2)?,
+                version_time: row.get(3)?,
+                version_state: version_state.and_then(|state| state.parse().ok()).unwrap_or_default(),
+                version_index: row.get(5)?,
+                selected: row.get(6)?,
+                token_usage: TokenUsage::from_columns(row.get(7)?, row.get(8)?),
+                finish_info: FinishInfo::from_columns(row.get(9)?, row.get(10)?),
+                response_schema: parse_schema(row.get(11)?),
+                model: row.get(12)?,
+            })
+        })?;
+        for version in versions {
+            let version = version?;
+            if let Some(record) = conversation_records
+                .iter_mut()
+                .find(|record| record.record_id == version.record_id)
+            {
+                record.record_versions.push(version);
+            }
+        }
+
+        Ok(Conversation {
+            conversation_records,
+            ..conversation
+        })
+    }
+}
+
+impl ConversationStore for SqliteStore {
+    fn list(&self) -> Result<Vec<Conversation>> {
+        let conn = self.connection();
+        let mut stmt = conn.prepare(
+            r#"SELECT conversation_id, conversation_title, conversation_start_time, conversation_modify_time, active_record_id,
+            conversation_model, conversation_system_instruction, conversation_options
+            FROM gemini_conversation ORDER BY conversation_modify_time DESC"#,
+        )?;
+        let conversations = stmt
+            .query_map([], map_conversation)?
+            .collect::<rusqlite::Result<Vec<Conversation>>>()?;
+        Ok(conversations)
+    }
+
+    fn load(&self, conversation_id: &str) -> Result<Conversation> {
+        self.load_records(conversation_id, false)
+    }
+
+    fn load_all(&self, conversation_id: &str) -> Result<Conversation> {
+        self.load_records(conversation_id, true)
+    }
+    fn save_exchange(
+        &self,
+        conversation_id: &str,
+        conversation_title: &str,
+        parent_record_id: Option<String>,
+        user_message: ChatMessage,
+        bot_message: ChatMessage,
+    ) -> Result<(String, String)> {
+        let attachments = cache_attachments(&user_message);
+        self.transaction_with_attachments(&attachments, |tx| {
+            let user_record_id = insert_message(
+                tx,
+                conversation_id,
+                conversation_title,
+                user_message,
+                &attachments,
+                parent_record_id,
+            )?;
+            let bot_record_id = insert_message(
+                tx,
+                conversation_id,
+                conversation_title,
+                bot_message,
+                &[],
+                Some(user_record_id.clone()),
+            )?;
+            Ok((user_record_id, bot_record_id))
+        })
+    }
+
+    fn save_message(
+        &self,
+        conversation_id: &str,
+        conversation_title: &str,
+        parent_record_id: Option<String>,
+        message: ChatMessage,
+    ) -> Result<String> {
+        let attachments = cache_attachments(&message);
+        self.transaction_with_attachments(&attachments, |tx| {
+            insert_message(
+                tx,
+                conversation_id,
+                conversation_title,
+                message,
+                &attachments,
+                parent_record_id,
+            )
+        })
+    }
+
+    fn delete(&self, conversation_id: &str) -> Result<()> {
+        let mut conn = self.connection();
+        let tx = conn.transaction()?;
+        let image_record_ids = {
+            let mut stmt = tx.prepare(
+                r#"SELECT image_record_id FROM gemini_image_record
+                WHERE record_id IN (SELECT record_id FROM gemini_message_record WHERE conversation_id = ?1)"#,
+            )?;
+            let image_record_ids = stmt
+                .query_map([conversation_id], |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()?;
+            image_record_ids
+        };
+        tx.execute(
+            r#"DELETE FROM gemini_message_version
+            WHERE record_id IN (SELECT record_id FROM gemini_message_record WHERE conversation_id = ?1)"#,
+            [conversation_id],
+        )?;
+        tx.execute(
+            r#"DELETE FROM gemini_image_record
+            WHERE record_id IN (SELECT record_id FROM gemini_message_record WHERE conversation_id = ?1)"#,
+            [conversation_id],
+        )?;
+        tx.execute(
+            "DELETE FROM gemini_message_record WHERE conversation_id = ?1",
+            [conversation_id],
+        )?;
+        tx.execute(
+            "DELETE FROM gemini_search_index WHERE conversation_id = ?1",
+            [conversation_id],
+        )?;
+        tx.execute(
+            "DELETE FROM gemini_conversation WHERE conversation_id = ?1",
+            [conversation_id],
+        )?;
+        tx.commit()?;
+        // 记录删除后再删除图片缓存
+        for image_record_id in image_record_ids {
+            let _ = delete_image_cache(image_record_id);
+        }
+        Ok(())
+    }
+
+    fn rename(&self, conversation_id: &str, conversation_title: &str) -> Result<()> {
+        let mut conn = self.connection();
+        let tx = conn.transaction()?;
+        tx.execute(
+            r#"
+            UPDATE gemini_conversation SET conversation_title = ?1
+            WHERE conversation_id = ?2
+            "#,
+            [conversation_title, conversation_id],
+        )?;
+        // 同步更新全文搜索索引中的标题
+        tx.execute(
+            r#"
+            UPDATE gemini_search_index SET content = ?1
+            WHERE conversation_id = ?2 AND record_id IS NULL
+            "#,
+            [conversation_title, conversation_id],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn modify_settings(
+        &self,
+        conversation_id: &str,
+        model: &str,
+        system_instruction: Option<&str>,
+        options: &str,
+    ) -> Result<()> {
+        self.connection().execute(
+            r#"
+            UPDATE gemini_conversation
+            SET conversation_model = ?1, conversation_system_instruction = ?2, conversation_options = ?3
+            WHERE conversation_id = ?4
+            "#,
+            params![model, system_instruction, options, conversation_id],
+        )?;
+        Ok(())
+    }
+
+    fn set_pinned(&self, record_id: &str, pinned: bool) -> Result<()> {
+        let updated = self.connection().execute(
+            "UPDATE gemini_message_record SET pinned = ?1 WHERE record_id = ?2",
+            params![pinned, record_id],
+        )?;
+        if updated == 0 {
+            bail!("Record {} does not exist", record_id)
+        }
+        Ok(())
+    }
+
+    fn switch_branch(&self, conversation_id: &str, record_id: &str) -> Result<()> {
+        let conn = self.connection();
+        let mut active_record_id = record_id.to_owned();
+        while let Some(child_record_id) = conn
+            .query_row(
+                r#"
+                SELECT record_id FROM gemini_message_record WHERE parent_record_id = ?1
+                ORDER BY record_time DESC LIMIT 1
+                "#,
+                [&active_record_id],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?
+        {
+            active_record_id = child_record_id;
+        }
+        conn.execute(
+            r#"
+            UPDATE gemini_conversation SET active_record_id = ?1
+            WHERE conversation_id = ?2
+            "#,
+            [active_record_id.as_str(), conversation_id],
+        )?;
+        Ok(())
+    }
+
+    fn query_image_record_ids(&self, record_id: &str) -> Result<Vec<String>> {
+        let conn = self.connection();
+        let mut stmt = conn.prepare(
+            r#"SELECT image_record_id FROM gemini_image_record
+            WHERE record_id = ?1 AND image_type LIKE 'image/%'
+            ORDER BY rowid ASC"#,
+        )?;
+        let mut rows = stmt.query_map([record_id], |row| row.get(0))?;
+        let mut image_record_ids = Vec::new();
+        while let Some(Ok(image_record_id)) = rows.next() {
+            image_record_ids.push(image_record_id);
+        }
+        Ok(image_record_ids)
+    }
+
+    fn save_message_version(&self, conversation_id: &str, message: ChatMessage) -> Result<()> {
+        let mut conn = self.connection();
+        let tx = conn.transaction()?;
+        let record_id = message.record_id.clone();
+        let count: i64 = tx.query_row(
+            r#"
+            SELECT COUNT(*) FROM gemini_message_version WHERE record_id = ?1
+            "#,
+            [record_id.clone()],
+            |row| row.get(0),
+        )?;
+        // 第一次重新生成时，先将原有的回复保存为第一个版本
+        let version_index = if count == 0 {
+            tx.execute(
+                r#"
+                INSERT INTO gemini_message_version (version_id, record_id, version_content, version_time, version_state, version_index,
+                prompt_token_count, response_token_count, finish_reason, safety_ratings, response_schema, model)
+                SELECT ?1, record_id, record_content, record_time, record_state, 0, prompt_token_count, response_token_count,
+                finish_reason, safety_ratings, response_schema, model
+                FROM gemini_message_record WHERE record_id = ?2
+                "#,
+                [generate_unique_id(), record_id.clone()],
+            )?;
+            1
+        } else {
+            count
+        };
+        tx.execute(
+            r#"
+            UPDATE gemini_message_version SET selected = 0 WHERE record_id = ?1
+            "#,
+            [record_id.clone()],
+        )?;
+        tx.execute(
+            r#"
+            INSERT INTO gemini_message_version (version_id, record_id, version_content, version_time, version_state, version_index, selected,
+            prompt_token_count, response_token_count, finish_reason, safety_ratings, response_schema, model)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, 1, ?7, ?8, ?9, ?10, ?11, ?12)
+            "#,
+            params![
+                generate_unique_id(),
+                record_id,
+                message.message,
+                message.date_time.to_string(),
+                message.state.to_string(),
+                version_index,
+                message.usage.map(|usage| usage.prompt_tokens),
+                message.usage.map(|usage| usage.response_tokens),
+                message.finish.as_ref().map(|finish| finish.reason.clone()),
+                message.finish.as_ref().map(FinishInfo::safety_ratings_column),
+                schema_column(&message.response_schema),
+                message.model
+            ],
+        )?;
+        // 回复记录中始终保存当前选中的版本
+        tx.execute(
+            r#"
+            UPDATE gemini_message_record
+            SET record_content = ?1, record_time = ?2, record_state = ?3, prompt_token_count = ?4, response_token_count = ?5,
+            finish_reason = ?6, safety_ratings = ?7, response_schema = ?8, model = ?9
+            WHERE record_id = ?10
+            "#,
+            params![
+                message.message,
+                message.date_time.to_string(),
+                message.state.to_string(),
+                message.usage.map(|usage| usage.prompt_tokens),
+                message.usage.map(|usage| usage.response_tokens),
+                message.finish.as_ref().map(|finish| finish.reason.clone()),
+                message.finish.as_ref().map(FinishInfo::safety_ratings_column),
+                schema_column(&message.response_schema),
+                message.model,
+                record_id,
+            ],
+        )?;
+        tx.execute(
+            r#"
+            UPDATE gemini_conversation SET conversation_modify_time = ?1
+            WHERE conversation_id = ?2
+            "#,
+            params![message.date_time.to_string(), conversation_id],
+        )?;
+        sync_search_index(&tx, &record_id)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn select_message_version(&self, record_id: &str, version_index: usize) -> Result<()> {
+        let mut conn = self.connection();
+        let tx = conn.transaction()?;
+        // 版本不存在时不做修改，以免回复记录被写入空值
+        let exists = tx
+            .query_row(
+                "SELECT 1 FROM gemini_message_version WHERE record_id = ?1 AND version_index = ?2",
+                params![record_id, version_index],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some();
+        if !exists {
+            bail!("Version {} of record {} does not exist", version_index, record_id)
+        }
+        tx.execute(
+            r#"
+            UPDATE gemini_message_version SET selected = (version_index = ?1) WHERE record_id = ?2
+            "#,
+            params![version_index, record_id],
+        )?;
+        tx.execute(
+            r#"
+            UPDATE gemini_message_record
+            SET (record_content, record_time, record_state, prompt_token_count, response_token_count,
+            finish_reason, safety_ratings, response_schema, model) = (
+                SELECT version_content, version_time, version_state, prompt_token_count, response_token_count,
+                finish_reason, safety_ratings, response_schema, model
+                FROM gemini_message_version
+                WHERE record_id = ?1 AND version_index = ?2
+            )
+            WHERE record_id = ?1
+            "#,
+            params![record_id, version_index],
+        )?;
+        sync_search_index(&tx, record_id)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn search(&self, keyword: &str) -> Result<Vec<SearchResult>> {
+        let keyword = keyword.trim();
+        if keyword.is_empty() {
+            return Ok(Vec::new());
+        }
+        let conn = self.connection();
+        let map_row = |row: &rusqlite::Row| {
+            Ok(SearchResult {
+                conversation_id: row.get(0)?,
+                conversation_title: row.get(1)?,
+                record_id: row.get(2)?,
+                snippet: row.get(3)?,
+                conversation_modify_time: row.get(4)?,
+            })
+        };
+        // 三元组分词器无法匹配少于 3 个字符的关键词
+        let results: Vec<SearchResult> = if keyword.chars().count() >= 3 {
+            let mut stmt = conn.prepare(
+                r#"
+                SELECT gemini_search_index.conversation_id, conversation_title, record_id,
+                snippet(gemini_search_index, 2, ?2, ?3, '…', ?4), conversation_modify_time
+                FROM gemini_search_index INNER JOIN gemini_conversation
+                ON gemini_search_index.conversation_id = gemini_conversation.conversation_id
+                WHERE gemini_search_index MATCH ?1
+                ORDER BY rank LIMIT ?5
+                "#,
+            )?;
+            // 将关键词作为一个短语匹配，避免其中的符号被解析为查询语法
+            let phrase = format!("content : \"{}\"", keyword.replace('"', "\"\""));
+            let rows = stmt.query_map(
+                params![
+                    phrase,
+                    SNIPPET_START.to_string(),
+                    SNIPPET_END.to_string(),
+                    SNIPPET_CONTEXT * 2,
+                    SEARCH_LIMIT
+                ],
+                map_row,
+            )?;
+            rows.filter_map(|row| row.ok()).collect()
+        } else {
+            let mut stmt = conn.prepare(
+                r#"
+                SELECT gemini_search_index.conversation_id, conversation_title, record_id,
+                content, conversation_modify_time
+                FROM gemini_search_index INNER JOIN gemini_conversation
+                ON gemini_search_index.conversation_id = gemini_conversation.conversation_id
+                WHERE content LIKE ?1 ESCAPE '\'
+                ORDER BY conversation_modify_time DESC LIMIT ?2
+                "#,
+            )?;
+            let pattern = format!(
+                "%{}%",
+                keyword.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+            );
+            let rows = stmt.query_map(params![pattern, SEARCH_LIMIT], map_row)?;
+            rows.filter_map(|row| row.ok())
+                .map(|result| SearchResult {
+                    snippet: make_snippet(&result.snippet, keyword),
+                    ..result
+                })
+                .collect()
+        };
+        // 摘要中的换行替换为空格，以便在一行中展示
+        Ok(results
+            .into_iter()
+            .map(|result| SearchResult {
+                snippet: result.snippet.replace(['\r', '\n'], " "),
+                ..result
+            })
+            .collect())
+    }
+
+    fn find_duplicate_conversation(&self, conversation: &Conversation) -> Result<Option<String>> {
+        let Some(first_record) = conversation.conversation_records.first() else {
+            return Ok(None);
+        };
+        let conn = self.connection();
+        let mut stmt = conn.prepare(
+            r#"
+            SELECT gemini_conversation.conversation_id, conversation_start_time
+            FROM gemini_conversation INNER JOIN gemini_message_record
+            ON gemini_conversation.conversation_id = gemini_message_record.conversation_id
+            WHERE parent_record_id IS NULL AND record_content = ?1
+            "#,
+        )?;
+        let mut rows = stmt.query_map([first_record.record_content.clone()], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, DateTime<Local>>(1)?))
+        })?;
+        // 不同来源的时间精度不同，只比较到秒
+        let start_time = conversation.conversation_start_time.timestamp();
+        while let Some(Ok((conversation_id, conversation_start_time))) = rows.next() {
+            if conversation_start_time.timestamp() == start_time {
+                return Ok(Some(conversation_id));
+            }
+        }
+        Ok(None)
+    }
+
+    fn import_conversation(&self, conversation: &Conversation, images: &HashMap<String, Vec<u8>>) -> Result<String> {
+        let mut conn = self.connection();
+        let tx = conn.transaction()?;
+        let conversation_id = generate_unique_id();
+        // 导入前的记录 id 到新记录 id 的映射，用于重建父记录关系
+        let record_ids: HashMap<&str, String> = conversation
+            .conversation_records
+            .iter()
+            .map(|record| (record.record_id.as_str(), generate_unique_id()))
+            .collect();
+        let active_record_id = conversation
+            .active_record_id
+            .as_deref()
+            .or(conversation
+                .conversation_records
+                .last()
+                .map(|record| record.record_id.as_str()))
+            .and_then(|record_id| record_ids.get(record_id))
+            .cloned();
+        tx.execute(
+            r#"
+            INSERT INTO gemini_conversation (conversation_id, conversation_title, conversation_start_time, conversation_modify_time, active_record_id,
+            conversation_model, conversation_system_instruction, conversation_options)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+            "#,
+            params![
+                conversation_id,
+                conversation.conversation_title,
+                conversation.conversation_start_time.to_string(),
+                conversation.conversation_modify_time.to_string(),
+                active_record_id,
+                conversation.conversation_model,
+                conversation.conversation_system_instruction,
+                conversation.conversation_options
+            ],
+        )?;
+        tx.execute(
+            r#"
+            INSERT INTO gemini_search_index (conversation_id, record_id, content)
+            VALUES (?1, NULL, ?2)
+            "#,
+            [conversation_id.clone(), conversation.conversation_title.clone()],
+        )?;
+        // 图片缓存在事务提交后再写入
+        let mut image_files = Vec::new();
+        for record in conversation.conversation_records.iter() {
+            let record_id = record_ids[record.record_id.as_str()].clone();
+            let parent_record_id = record
+                .parent_record_id
+                .as_deref()
+                .and_then(|parent_record_id| record_ids.get(parent_record_id))
+                .cloned();
+            tx.execute(
+                r#"
+                INSERT INTO gemini_message_record (record_id, conversation_id, record_content, record_time, record_sender, sort_index, record_state, parent_record_id,
+                prompt_token_count, response_token_count, pinned, finish_reason, safety_ratings, tool_payload, response_schema, model)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)
+                "#,
+                params![
+                    record_id,
+                    conversation_id,
+                    record.record_content,
+                    record.record_time.to_string(),
+                    sender_name(&record.record_sender),
+                    record.sort_index,
+                    record.record_state.to_string(),
+                    parent_record_id,
+                    record.token_usage.map(|usage| usage.prompt_tokens),
+                    record.token_usage.map(|usage| usage.response_tokens),
+                    record.pinned,
+                    record.finish_info.as_ref().map(|finish| finish.reason.clone()),
+                    record.finish_info.as_ref().map(FinishInfo::safety_ratings_column),
+                    sender_payload(&record.record_sender),
+                    schema_column(&record.response_schema),
+                    record.model
+                ],
+            )?;
+            tx.execute(
+                r#"
+                INSERT INTO gemini_search_index (conversation_id, record_id, content)
+                VALUES (?1, ?2, ?3)
+                "#,
+                [
+                    conversation_id.clone(),
+                    record_id.clone(),
+                    record.record_content.clone(),
+                ],
+            )?;
+            for image_record in record.image_records.iter() {
+                let image_record_id = generate_unique_id();
+                tx.execute(
+                    r#"
+                    INSERT INTO gemini_image_record (image_record_id, record_id, image_path, image_type)
+                    VALUES (?1, ?2, ?3, ?4)
+                    "#,
+                    [
+                        image_record_id.clone(),
+                        record_id.clone(),
+                        image_record.image_path.clone(),
+                        image_record.image_type.clone(),
+                    ],
+                )?;
+                if let Some(data) = images.get(&image_record.image_record_id) {
+                    image_files.push((image_record_id, data));
+                }
+            }
+            for version in record.record_versions.iter() {
+                tx.execute(
+                    r#"
+                    INSERT INTO gemini_message_version (version_id, record_id, version_content, version_time, version_state, version_index, selected,
+                    prompt_token_count, response_token_count, finish_reason, safety_ratings, response_schema, model)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+                    "#,
+                    params![
+                        generate_unique_id(),
+                        record_id,
+                        version.version_content,
+                        version.version_time.to_string(),
+                        version.version_state.to_string(),
+                        version.version_index,
+                        version.selected,
+                        version.token_usage.map(|usage| usage.prompt_tokens),
+                        version.token_usage.map(|usage| usage.response_tokens),
+                        version.finish_info.as_ref().map(|finish| finish.reason.clone()),
+                        version.finish_info.as_ref().map(FinishInfo::safety_ratings_column),
+                        schema_column(&version.response_schema),
+                        version.model
+                    ],
+                )?;
+            }
+        }
+        tx.commit()?;
+        for (image_record_id, data) in image_files {
+            save_image_cache(image_record_id, data)?;
+        }
+        Ok(conversation_id)
+    }
+}
+
+/// 读取会话表中的一行，不包含聊天记录
+fn map_conversation(row: &rusqlite::Row) -> rusqlite::Result<Conversation> {
+    Ok(Conversation {
+        conversation_id: row.get(0)?,
+        conversation_title: row.get(1)?,
+        conversation_start_time: row.get(2)?,
+        conversation_modify_time: row.get(3)?,
+        active_record_id: row.get(4)?,
+        conversation_model: row.get(5)?,
+        conversation_system_instruction: row.get(6)?,
+        conversation_options: row.get(7)?,
+        conversation_records: vec![],
+    })
+}
+
+/// 缓存用户消息的附件，返回每个附件的附件记录 id 与文件类型
+///
+/// 原始文件已移动或下载失败时按扩展名推断文件类型，使用时再重新读取原始文件，以免已接收的回复无法保存
+fn cache_attachments(message: &ChatMessage) -> Vec<(String, String)> {
+    let Sender::User(image_paths) = &message.sender else {
+        return Vec::new();
+    };
+    image_paths
+        .iter()
+        .map(|image_path| {
+            let image_record_id = generate_unique_id();
+            let image_type = cache_attachment(image_path.clone(), image_record_id.clone())
+                .unwrap_or_else(|_| mime_type_by_extension(image_path).unwrap_or_default().into());
+            (image_record_id, image_type)
+        })
+        .collect()
+}
+
+/// 保存一条消息，会话不存在时新建，新消息作为父记录的子记录，并成为当前分支的最后一条记录，返回新记录的 id
+///
+/// `attachments` 为 `cache_attachments` 返回的用户消息附件记录 id 与文件类型，与图片路径一一对应
+fn insert_message(
+    conn: &Connection,
+    conversation_id: &str,
+    conversation_title: &str,
+    message: ChatMessage,
+    attachments: &[(String, String)],
+    parent_record_id: Option<String>,
+) -> Result<String> {
+    if matches!(message.sender, Sender::Never) {
+        bail!("Unsupported message sender")
+    }
+    let date_time = message.date_time.to_string();
+    let exists = conn
+        .query_row(
+            "SELECT 1 FROM gemini_conversation WHERE conversation_id = ?1",
+            [conversation_id],
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some();
+    if !exists {
+        // 如果不存在，则新增一个会话，并将会话标题加入全文搜索索引
+        conn.execute(
+            r#"
+            INSERT INTO gemini_conversation (conversation_id, conversation_title, conversation_start_time, conversation_modify_time)
+            VALUES (?1, ?2, ?3, ?3)
+            "#,
+            [conversation_id, conversation_title, date_time.as_str()],
+        )?;
+        conn.execute(
+            r#"
+            INSERT INTO gemini_search_index (conversation_id, record_id, content)
+            VALUES (?1, NULL, ?2)
+            "#,
+            [conversation_id, conversation_title],
+        )?;
+    } else {
+        // 如果存在，则更新会话修改时间
+        conn.execute(
+            r#"
+            UPDATE gemini_conversation SET conversation_modify_time = ?1
+            WHERE conversation_id = ?2
+            "#,
+            [date_time.as_str(), conversation_id],
+        )?;
+    }
+
+    // 消息序号为父记录序号 + 1
+    let sort_index = match &parent_record_id {
+        Some(parent_record_id) => conn
+            .query_row(
+                "SELECT sort_index FROM gemini_message_record WHERE record_id = ?1",
+                [parent_record_id],
+                |row| row.get::<_, u32>(0),
+            )
+            .optional()?
+            .map_or(0, |index| index + 1),
+        None => 0,
+    };
+
+    let record_id = generate_unique_id();
+    conn.execute(
+        r#"
+        INSERT INTO gemini_message_record (record_id, conversation_id, record_content, record_time, record_sender, sort_index, record_state, parent_record_id,
+        prompt_token_count, response_token_count, pinned, finish_reason, safety_ratings, tool_payload, response_schema, model)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)
+        "#,
+        params![
+            record_id,
+            conversation_id,
+            message.message,
+            date_time,
+            sender_name(&message.sender),
+            sort_index,
+            message.state.to_string(),
+            parent_record_id,
+            message.usage.map(|usage| usage.prompt_tokens),
+            message.usage.map(|usage| usage.response_tokens),
+            message.pinned,
+            message.finish.as_ref().map(|finish| finish.reason.clone()),
+            message.finish.as_ref().map(FinishInfo::safety_ratings_column),
+            sender_payload(&message.sender),
+            schema_column(&message.response_schema),
+            message.model
+        ],
+    )?;
+    // 为每一个附件插入附件记录
+    if let Sender::User(image_paths) = message.sender {
+        for (image_path, (image_record_id, image_type)) in image_paths.into_iter().zip(attachments) {
+            conn.execute(
+                r#"
+                INSERT INTO gemini_image_record (image_record_id, record_id, image_path, image_type)
+                VALUES (?1, ?2, ?3, ?4)
+                "#,
+                [
+                    image_record_id.as_str(),
+                    record_id.as_str(),
+                    image_path.as_str(),
+                    image_type.as_str(),
+                ],
+            )?;
+        }
+    }
+
+    // 将消息内容加入全文搜索索引
+    conn.execute(
+        r#"
+        INSERT INTO gemini_search_index (conversation_id, record_id, content)
+        VALUES (?1, ?2, ?3)
+        "#,
+        params![conversation_id, record_id, message.message],
+    )?;
+
+    // 新消息成为当前分支的最后一条记录
+    conn.execute(
+        r#"
+        UPDATE gemini_conversation SET active_record_id = ?1
+        WHERE conversation_id = ?2
+        "#,
+        [record_id.as_str(), conversation_id],
+    )?;
+
+    Ok(record_id)
+}
+
+/// 使用记录的当前内容更新全文搜索索引
+fn sync_search_index(conn: &Connection, record_id: &str) -> Result<()> {
+    conn.execute(
+        r#"
+        UPDATE gemini_search_index SET content = (
+            SELECT record_content FROM gemini_message_record WHERE record_id = ?1
+        )
+        WHERE record_id = ?1
+        "#,
+        [record_id],
+    )?;
+    Ok(())
+}
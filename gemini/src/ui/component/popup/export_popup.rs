@@ -0,0 +1,90 @@
+use ratatui::{
+    layout::{
+        Constraint::{Fill, Length},
+        Layout, Rect,
+    },
+    style::{Color, Style},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+use strum::EnumCount;
+
+use crate::utils::export_utils::ExportFormat;
+
+/// 导出格式选择弹窗
+#[derive(Clone)]
+pub struct ExportPopup {
+    // 提示文本
+    pub title: String,
+    // 当前选中的导出格式
+    pub selected_format: ExportFormat,
+    // 宽度
+    pub width: usize,
+    // 高度
+    pub height: usize,
+    // 边框颜色
+    pub border_color: Color,
+    // 按钮选中的背景色
+    pub button_selected_bg_color: Color,
+}
+
+impl Default for ExportPopup {
+    fn default() -> Self {
+        Self {
+            title: "Export As".into(),
+            selected_format: Default::default(),
+            width: 40,
+            height: 5,
+            border_color: Color::Blue,
+            button_selected_bg_color: Color::Green,
+        }
+    }
+}
+
+impl ExportPopup {
+    // 下一个导出格式
+    pub fn next_button(&mut self) {
+        let current = self.selected_format as usize;
+        let next = (current + 1) % ExportFormat::COUNT;
+        self.selected_format = ExportFormat::from_repr(next).unwrap();
+    }
+
+    // 上一个导出格式
+    pub fn prev_button(&mut self) {
+        let current = self.selected_format as usize;
+        let prev = (current + ExportFormat::COUNT - 1) % ExportFormat::COUNT;
+        self.selected_format = ExportFormat::from_repr(prev).unwrap();
+    }
+}
+
+impl ExportPopup {
+    pub fn draw(self, frame: &mut Frame, area: Rect) {
+        // 先清空弹窗区域内容
+        frame.render_widget(Clear, area);
+
+        let [_, title_area, split_area, button_area, _] =
+            Layout::vertical([Length(1), Fill(1), Length(1), Length(1), Length(1)]).areas(area);
+        // 渲染标题区域
+        let title_paragraph = Paragraph::new(format!(" {} ", self.title)).centered();
+        frame.render_widget(title_paragraph, title_area);
+        // 渲染分割线
+        let split_block = Block::default().borders(Borders::ALL).border_style(Color::Gray);
+        frame.render_widget(split_block, split_area);
+        // 渲染按钮区域，每个导出格式一个按钮
+        let [_, button_area, _] = Layout::horizontal([Length(1), Fill(1), Length(1)]).areas(button_area);
+        let button_areas = Layout::horizontal([Fill(1); ExportFormat::COUNT]).split(button_area);
+        for (i, area) in button_areas.iter().enumerate() {
+            let format = ExportFormat::from_repr(i).unwrap();
+            let style = if format == self.selected_format {
+                Style::default().fg(Color::White).bg(self.button_selected_bg_color)
+            } else {
+                Style::default().fg(Color::White)
+            };
+            let button = Paragraph::new(format.to_string()).style(style).centered();
+            frame.render_widget(button, *area);
+        }
+        // 边框
+        let border_block = Block::default().style(self.border_color).borders(Borders::ALL);
+        frame.render_widget(border_block, area);
+    }
+}
@@ -0,0 +1,235 @@
+use ratatui::{
+    crossterm::event,
+    layout::{
+        Alignment,
+        Constraint::{Fill, Length},
+        Layout, Rect,
+    },
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{block::Title, Block, Borders, Clear, Paragraph},
+    Frame,
+};
+
+use crate::ui::component::input::{input_trait::InputTextComponent, text_field::TextField};
+use ratatui::widgets::block::title::Position as TitlePosition;
+
+use ratatui::layout::Position as CursorPosition;
+
+/// 附件列表弹窗，可以添加或移除消息附带的图片、文档、音频或文本文件
+pub struct ImagePopup {
+    // 图片路径输入框
+    pub input_text: TextField,
+    // 已添加的图片路径
+    pub image_paths: Vec<String>,
+    // 选中的图片，为空时表示正在输入
+    pub selected: Option<usize>,
+    pub width: usize,
+    pub height: usize,
+    // 边框颜色
+    pub border_color: Color,
+}
+
+impl ImagePopup {
+    pub fn new(image_paths: Vec<String>) -> Self {
+        Self {
+            input_text: TextField::default(),
+            image_paths,
+            selected: None,
+            width: 50,
+            height: 3,
+            border_color: Color::Blue,
+        }
+    }
+
+    pub fn set_size(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+    }
+
+    /// 弹窗需要的高度，图片列表与输入框各占若干行，另加上下边框与分割线
+    pub fn preferred_height(&self) -> usize {
+        if self.image_paths.is_empty() {
+            3
+        } else {
+            self.image_paths.len() + 4
+        }
+    }
+
+    // 添加输入框中的图片路径
+    fn add(&mut self) -> bool {
+        let image_path = self.input_text.get_content().trim().to_owned();
+        if image_path.is_empty() {
+            return false;
+        }
+        self.image_paths.push(image_path);
+        self.input_text.clear();
+        true
+    }
+
+    // 移除选中的图片
+    fn remove(&mut self) {
+        let Some(index) = self.selected else {
+            return;
+        };
+        if index < self.image_paths.len() {
+            self.image_paths.remove(index);
+        }
+        // 移除最后一张图片后，选中新的最后一张图片，没有图片时回到输入框
+        self.selected = if self.image_paths.is_empty() {
+            None
+        } else {
+            Some(index.min(self.image_paths.len() - 1))
+        };
+    }
+
+    // 保存，输入框中尚未添加的图片路径也一并保存
+    pub fn save(&mut self) -> Vec<String> {
+        self.add();
+        self.image_paths.clone()
+    }
+}
+
+impl ImagePopup {
+    pub fn draw(&mut self, frame: &mut Frame, area: Rect) {
+        // 先清空弹窗区域内容
+        frame.render_widget(Clear, area);
+        let block = Block::bordered()
+            .title(
+                Title::from(format!("Attachments ({})", self.image_paths.len()))
+                    .position(TitlePosition::Top)
+                    .alignment(Alignment::Left),
+            )
+            .title(
+                Title::from("Add (Enter) Remove (Ctrl+D)")
+                    .position(TitlePosition::Top)
+                    .alignment(Alignment::Right),
+            )
+            .title(
+                Title::from("Save (Ctrl+S)")
+                    .position(TitlePosition::Bottom)
+                    .alignment(Alignment::Left),
+            )
+            .title(
+                Title::from("Cancel (ESC)")
+                    .position(TitlePosition::Bottom)
+                    .alignment(Alignment::Right),
+            )
+            .borders(Borders::ALL)
+            .border_style(self.border_color);
+        let inner_area = block.inner(area);
+        frame.render_widget(block, area);
+        let input_area = if self.image_paths.is_empty() {
+            inner_area
+        } else {
+            let [list_area, split_area, input_area] =
+                Layout::vertical([Fill(1), Length(1), Length(1)]).areas(inner_area);
+            // 图片列表
+            let lines: Vec<Line> = self
+                .image_paths
+                .iter()
+                .enumerate()
+                .map(|(i, image_path)| {
+                    let style = if self.selected == Some(i) {
+                        Style::default().fg(Color::Black).bg(Color::LightBlue)
+                    } else {
+                        Style::default().fg(Color::LightBlue)
+                    };
+                    Line::from(vec![
+                        Span::styled(format!("{}. ", i + 1), Color::Gray),
+                        Span::styled(image_path.clone(), style),
+                    ])
+                })
+                .collect();
+            frame.render_widget(Paragraph::new(lines), list_area);
+            // 分割线
+            let split_paragraph = Paragraph::new("─".repeat(split_area.width as usize)).style(Color::Gray);
+            frame.render_widget(split_paragraph, split_area);
+            input_area
+        };
+        // 输入框内容
+        self.input_text.set_width_height(input_area.width as usize, 1);
+        let input_paragraph =
+            Paragraph::new(self.input_text.should_show_text()).style(Style::default().fg(Color::Yellow));
+        frame.render_widget(input_paragraph, input_area);
+        // 选中图片时不展示输入框光标
+        if self.selected.is_none() {
+            let (x, y) = self.input_text.get_cursor_position();
+            frame.set_cursor_position(CursorPosition::new(input_area.x + x as u16, input_area.y + y as u16));
+        }
+    }
+}
+
+pub enum ImagePopupHandleEvent {
+    Save(Vec<String>),
+    Cancel,
+    Nothing,
+}
+
+impl ImagePopup {
+    pub fn handle_key(&mut self, key: event::KeyEvent) -> ImagePopupHandleEvent {
+        match key.code {
+            event::KeyCode::Char('s') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                ImagePopupHandleEvent::Save(self.save())
+            }
+            event::KeyCode::Char('d') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                self.remove();
+                ImagePopupHandleEvent::Nothing
+            }
+            event::KeyCode::Esc => ImagePopupHandleEvent::Cancel,
+            event::KeyCode::Enter => {
+                // 输入框为空时保存并关闭弹窗
+                if self.add() {
+                    ImagePopupHandleEvent::Nothing
+                } else {
+                    ImagePopupHandleEvent::Save(self.image_paths.clone())
+                }
+            }
+            event::KeyCode::Up => {
+                self.selected = match self.selected {
+                    Some(index) => Some(index.saturating_sub(1)),
+                    None => self.image_paths.len().checked_sub(1),
+                };
+                ImagePopupHandleEvent::Nothing
+            }
+            event::KeyCode::Down => {
+                self.selected = self
+                    .selected
+                    .map(|index| index + 1)
+                    .filter(|index| *index < self.image_paths.len());
+                ImagePopupHandleEvent::Nothing
+            }
+            event::KeyCode::Backspace => {
+                self.input_text.delete_pre_char();
+                ImagePopupHandleEvent::Nothing
+            }
+            event::KeyCode::Left => {
+                self.input_text.move_cursor_left(self.input_text.get_current_char());
+                ImagePopupHandleEvent::Nothing
+            }
+            event::KeyCode::Right => {
+                self.input_text.move_cursor_right(self.input_text.get_next_char());
+                ImagePopupHandleEvent::Nothing
+            }
+            event::KeyCode::Home => {
+                self.input_text.home_of_cursor();
+                ImagePopupHandleEvent::Nothing
+            }
+            event::KeyCode::End => {
+                self.input_text.end_of_cursor();
+                ImagePopupHandleEvent::Nothing
+            }
+            event::KeyCode::Delete => {
+                self.input_text.delete_suf_char();
+                ImagePopupHandleEvent::Nothing
+            }
+            event::KeyCode::Char(x) => {
+                // 输入时回到输入框
+                self.selected = None;
+                self.input_text.enter_char(x);
+                ImagePopupHandleEvent::Nothing
+            }
+            _ => ImagePopupHandleEvent::Nothing,
+        }
+    }
+}
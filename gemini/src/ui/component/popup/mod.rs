@@ -1,2 +1,6 @@
 pub mod delete_popup;
-pub mod input_popup;
+pub mod export_popup;
+pub mod image_popup;
+pub mod profile_popup;
+pub mod search_popup;
+pub mod tool_popup;
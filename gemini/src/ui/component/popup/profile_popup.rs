@@ -0,0 +1,221 @@
+use ratatui::{
+    crossterm::event,
+    layout::{
+        Alignment,
+        Constraint::{Fill, Length},
+        Layout, Rect,
+    },
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{block::Title, Block, Borders, Clear, Paragraph},
+    Frame,
+};
+
+use crate::ui::component::input::{input_trait::InputTextComponent, text_field::TextField};
+use ratatui::widgets::block::title::Position as TitlePosition;
+
+use ratatui::layout::Position as CursorPosition;
+
+/// 配置方案选择弹窗，输入内容用于筛选配置方案，也用作新配置方案的名称
+pub struct ProfilePopup {
+    // 筛选关键词或新配置方案名称
+    pub input_text: TextField,
+    // 所有配置方案名称
+    pub profiles: Vec<String>,
+    // 当前使用的配置方案
+    pub current: String,
+    // 选中的配置方案，为筛选后列表中的索引
+    pub selected: usize,
+    pub width: usize,
+    pub height: usize,
+    // 边框颜色
+    pub border_color: Color,
+}
+
+impl ProfilePopup {
+    pub fn new(profiles: Vec<String>, current: String) -> Self {
+        // 默认选中当前使用的配置方案
+        let selected = profiles.iter().position(|name| *name == current).unwrap_or(0);
+        Self {
+            input_text: TextField::default(),
+            profiles,
+            current,
+            selected,
+            width: 40,
+            height: 3,
+            border_color: Color::Blue,
+        }
+    }
+
+    pub fn set_size(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+    }
+
+    /// 删除配置方案后更新列表
+    pub fn set_profiles(&mut self, profiles: Vec<String>) {
+        self.profiles = profiles;
+        self.selected = self.selected.min(self.filtered().len().saturating_sub(1));
+    }
+
+    /// 弹窗需要的高度，列表与输入框各占若干行，另加上下边框与分割线
+    pub fn preferred_height(&self) -> usize {
+        self.profiles.len() + 4
+    }
+
+    /// 根据输入内容筛选后的配置方案
+    fn filtered(&self) -> Vec<&String> {
+        let keyword = self.input_text.get_content().trim().to_lowercase();
+        self.profiles
+            .iter()
+            .filter(|name| name.to_lowercase().contains(&keyword))
+            .collect()
+    }
+
+    /// 选中的配置方案
+    fn selected_profile(&self) -> Option<String> {
+        self.filtered().get(self.selected).map(|name| name.to_string())
+    }
+}
+
+impl ProfilePopup {
+    pub fn draw(&mut self, frame: &mut Frame, area: Rect) {
+        // 先清空弹窗区域内容
+        frame.render_widget(Clear, area);
+        let block = Block::bordered()
+            .title(
+                Title::from("Profiles")
+                    .position(TitlePosition::Top)
+                    .alignment(Alignment::Left),
+            )
+            .title(
+                Title::from("New (Ctrl+N) Delete (Ctrl+D)")
+                    .position(TitlePosition::Top)
+                    .alignment(Alignment::Right),
+            )
+            .title(
+                Title::from("Switch (Enter)")
+                    .position(TitlePosition::Bottom)
+                    .alignment(Alignment::Left),
+            )
+            .title(
+                Title::from("Cancel (ESC)")
+                    .position(TitlePosition::Bottom)
+                    .alignment(Alignment::Right),
+            )
+            .borders(Borders::ALL)
+            .border_style(self.border_color);
+        let inner_area = block.inner(area);
+        frame.render_widget(block, area);
+        let [list_area, split_area, input_area] = Layout::vertical([Fill(1), Length(1), Length(1)]).areas(inner_area);
+        // 配置方案列表，当前使用的配置方案以 * 标记
+        let lines: Vec<Line> = self
+            .filtered()
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let style = if i == self.selected {
+                    Style::default().fg(Color::Black).bg(Color::LightBlue)
+                } else {
+                    Style::default().fg(Color::LightBlue)
+                };
+                let mark = if *name == self.current { "* " } else { "  " };
+                Line::from(vec![
+                    Span::styled(mark, Color::Yellow),
+                    Span::styled(name.clone(), style),
+                ])
+            })
+            .collect();
+        frame.render_widget(Paragraph::new(lines), list_area);
+        // 分割线
+        let split_paragraph = Paragraph::new("─".repeat(split_area.width as usize)).style(Color::Gray);
+        frame.render_widget(split_paragraph, split_area);
+        // 输入框内容
+        self.input_text.set_width_height(input_area.width as usize, 1);
+        let input_paragraph =
+            Paragraph::new(self.input_text.should_show_text()).style(Style::default().fg(Color::Yellow));
+        frame.render_widget(input_paragraph, input_area);
+        let (x, y) = self.input_text.get_cursor_position();
+        frame.set_cursor_position(CursorPosition::new(input_area.x + x as u16, input_area.y + y as u16));
+    }
+}
+
+pub enum ProfilePopupHandleEvent {
+    /// 切换到已有的配置方案
+    Switch(String),
+    /// 复制当前配置方案创建新的配置方案
+    Create(String),
+    /// 删除配置方案
+    Delete(String),
+    Cancel,
+    Nothing,
+}
+
+impl ProfilePopup {
+    pub fn handle_key(&mut self, key: event::KeyEvent) -> ProfilePopupHandleEvent {
+        match key.code {
+            event::KeyCode::Char('d') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                match self.selected_profile() {
+                    Some(name) => ProfilePopupHandleEvent::Delete(name),
+                    None => ProfilePopupHandleEvent::Nothing,
+                }
+            }
+            event::KeyCode::Char('n') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                // 使用输入的内容作为新配置方案的名称
+                let name = self.input_text.get_content().trim().to_owned();
+                if name.is_empty() {
+                    ProfilePopupHandleEvent::Nothing
+                } else {
+                    ProfilePopupHandleEvent::Create(name)
+                }
+            }
+            event::KeyCode::Esc => ProfilePopupHandleEvent::Cancel,
+            event::KeyCode::Enter => match self.selected_profile() {
+                Some(name) => ProfilePopupHandleEvent::Switch(name),
+                None => ProfilePopupHandleEvent::Nothing,
+            },
+            event::KeyCode::Up => {
+                self.selected = self.selected.saturating_sub(1);
+                ProfilePopupHandleEvent::Nothing
+            }
+            event::KeyCode::Down => {
+                if self.selected + 1 < self.filtered().len() {
+                    self.selected += 1;
+                }
+                ProfilePopupHandleEvent::Nothing
+            }
+            event::KeyCode::Backspace => {
+                self.input_text.delete_pre_char();
+                self.selected = 0;
+                ProfilePopupHandleEvent::Nothing
+            }
+            event::KeyCode::Left => {
+                self.input_text.move_cursor_left(self.input_text.get_current_char());
+                ProfilePopupHandleEvent::Nothing
+            }
+            event::KeyCode::Right => {
+                self.input_text.move_cursor_right(self.input_text.get_next_char());
+                ProfilePopupHandleEvent::Nothing
+            }
+            event::KeyCode::Home => {
+                self.input_text.home_of_cursor();
+                ProfilePopupHandleEvent::Nothing
+            }
+            event::KeyCode::End => {
+                self.input_text.end_of_cursor();
+                ProfilePopupHandleEvent::Nothing
+            }
+            event::KeyCode::Delete => {
+                self.input_text.delete_suf_char();
+                self.selected = 0;
+                ProfilePopupHandleEvent::Nothing
+            }
+            event::KeyCode::Char(x) => {
+                self.input_text.enter_char(x);
+                self.selected = 0;
+                ProfilePopupHandleEvent::Nothing
+            }
+            _ => ProfilePopupHandleEvent::Nothing,
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use ratatui::{
+    crossterm::event,
+    layout::{
+        Alignment,
+        Constraint::{Fill, Length},
+        Layout, Rect,
+    },
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{block::Title, Block, Borders, Clear, Paragraph},
+    Frame,
+};
+
+use crate::model::db::{SearchResult, SNIPPET_END, SNIPPET_START};
+use crate::store::ConversationStore;
+use crate::ui::component::input::{input_trait::InputTextComponent, text_field::TextField};
+use crate::utils::db_utils::conversation_store;
+use ratatui::widgets::block::title::Position as TitlePosition;
+
+use ratatui::layout::Position as CursorPosition;
+
+/// 每一条搜索结果占用的高度
+const RESULT_HEIGHT: usize = 2;
+
+/// 全文搜索弹窗
+pub struct SearchPopup {
+    // 搜索关键词
+    pub input_text: TextField,
+    // 搜索结果
+    pub results: Vec<SearchResult>,
+    // 选中的搜索结果
+    pub selected: usize,
+    pub width: usize,
+    pub height: usize,
+    // 边框颜色
+    pub border_color: Color,
+}
+
+impl Default for SearchPopup {
+    fn default() -> Self {
+        Self {
+            input_text: TextField::default(),
+            results: Vec::new(),
+            selected: 0,
+            width: 60,
+            height: 20,
+            border_color: Color::Blue,
+        }
+    }
+}
+
+impl SearchPopup {
+    pub fn set_size(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+    }
+
+    // 根据当前关键词重新搜索
+    fn search(&mut self) {
+        self.results = conversation_store()
+            .search(&self.input_text.get_content())
+            .unwrap_or_default();
+        self.selected = 0;
+    }
+}
+
+impl SearchPopup {
+    pub fn draw(&mut self, frame: &mut Frame, area: Rect) {
+        // 先清空弹窗区域内容
+        frame.render_widget(Clear, area);
+        let block = Block::bordered()
+            .title(
+                Title::from("Search")
+                    .position(TitlePosition::Top)
+                    .alignment(Alignment::Left),
+            )
+            .title(
+                Title::from(format!("{} results", self.results.len()))
+                    .position(TitlePosition::Top)
+                    .alignment(Alignment::Right),
+            )
+            .title(
+                Title::from("Open (Enter)")
+                    .position(TitlePosition::Bottom)
+                    .alignment(Alignment::Left),
+            )
+            .title(
+                Title::from("Cancel (ESC)")
+                    .position(TitlePosition::Bottom)
+                    .alignment(Alignment::Right),
+            )
+            .borders(Borders::ALL)
+            .border_style(self.border_color);
+        let inner_area = block.inner(area);
+        frame.render_widget(block, area);
+        let [input_area, split_area, result_area] = Layout::vertical([Length(1), Length(1), Fill(1)]).areas(inner_area);
+        // 输入框
+        self.input_text.set_width_height(input_area.width as usize, 1);
+        let input_paragraph =
+            Paragraph::new(self.input_text.should_show_text()).style(Style::default().fg(Color::Yellow));
+        frame.render_widget(input_paragraph, input_area);
+        // 分割线
+        let split_paragraph = Paragraph::new("─".repeat(split_area.width as usize)).style(Color::Gray);
+        frame.render_widget(split_paragraph, split_area);
+        // 搜索结果，保持选中项处于可见区域内
+        let visible = (result_area.height as usize / RESULT_HEIGHT).max(1);
+        let offset = self.selected.saturating_sub(visible - 1);
+        let mut lines = Vec::new();
+        for (i, result) in self.results.iter().enumerate().skip(offset).take(visible) {
+            let selected = i == self.selected;
+            let title = if result.conversation_title.is_empty() {
+                "Untitled".to_owned()
+            } else {
+                result.conversation_title.clone()
+            };
+            let title_style = if selected {
+                Style::default().fg(Color::Black).bg(Color::LightBlue)
+            } else {
+                Style::default().fg(Color::LightBlue)
+            };
+            let date_time = result.conversation_modify_time.format(" %m/%d %H:%M").to_string();
+            lines.push(Line::from(vec![
+                Span::styled(if selected { "> " } else { "  " }, Color::LightBlue),
+                Span::styled(title, title_style),
+                Span::styled(date_time, Color::DarkGray),
+            ]));
+            // 命中标题时不展示摘要
+            let mut snippet = vec![Span::raw("  ")];
+            if result.record_id.is_some() {
+                snippet.extend(snippet_spans(&result.snippet));
+            }
+            lines.push(Line::from(snippet));
+        }
+        frame.render_widget(Paragraph::new(lines), result_area);
+        let (x, y) = self.input_text.get_cursor_position();
+        frame.set_cursor_position(CursorPosition::new(input_area.x + x as u16, input_area.y + y as u16));
+    }
+}
+
+/// 将搜索摘要转换为文本片段，高亮其中的关键词
+fn snippet_spans(snippet: &str) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    let mut highlight = false;
+    for part in snippet.split([SNIPPET_START, SNIPPET_END]) {
+        if !part.is_empty() {
+            let style = if highlight {
+                Style::default()
+                    .fg(Color::Black)
+                    .bg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(Color::Gray)
+            };
+            spans.push(Span::styled(part.to_owned(), style));
+        }
+        highlight = !highlight;
+    }
+    spans
+}
+
+pub enum SearchPopupHandleEvent {
+    Open(SearchResult),
+    Cancel,
+    Nothing,
+}
+
+impl SearchPopup {
+    pub fn handle_key(&mut self, key: event::KeyEvent) -> SearchPopupHandleEvent {
+        match key.code {
+            event::KeyCode::Enter => match self.results.get(self.selected) {
+                Some(result) => SearchPopupHandleEvent::Open(result.clone()),
+                None => SearchPopupHandleEvent::Nothing,
+            },
+            event::KeyCode::Esc => SearchPopupHandleEvent::Cancel,
+            event::KeyCode::Up => {
+                self.selected = self.selected.saturating_sub(1);
+                SearchPopupHandleEvent::Nothing
+            }
+            event::KeyCode::Down => {
+                if self.selected + 1 < self.results.len() {
+                    self.selected += 1;
+                }
+                SearchPopupHandleEvent::Nothing
+            }
+            event::KeyCode::Backspace => {
+                self.input_text.delete_pre_char();
+                self.search();
+                SearchPopupHandleEvent::Nothing
+            }
+            event::KeyCode::Left => {
+                self.input_text.move_cursor_left(self.input_text.get_current_char());
+                SearchPopupHandleEvent::Nothing
+            }
+            event::KeyCode::Right => {
+                self.input_text.move_cursor_right(self.input_text.get_next_char());
+                SearchPopupHandleEvent::Nothing
+            }
+            event::KeyCode::Home => {
+                self.input_text.home_of_cursor();
+                SearchPopupHandleEvent::Nothing
+            }
+            event::KeyCode::End => {
+                self.input_text.end_of_cursor();
+                SearchPopupHandleEvent::Nothing
+            }
+            event::KeyCode::Delete => {
+                self.input_text.delete_suf_char();
+                self.search();
+                SearchPopupHandleEvent::Nothing
+            }
+            event::KeyCode::Char(x) => {
+                self.input_text.enter_char(x);
+                self.search();
+                SearchPopupHandleEvent::Nothing
+            }
+            _ => SearchPopupHandleEvent::Nothing,
+        }
+    }
+}
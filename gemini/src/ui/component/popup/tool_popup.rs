@@ -0,0 +1,120 @@
+use std::time::{Duration, Instant};
+
+use ratatui::{
+    crossterm::event,
+    layout::{Alignment, Rect},
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{block::Title, Block, Borders, Clear, Paragraph, Wrap},
+    Frame,
+};
+
+use crate::model::view::FunctionCall;
+use crate::utils::{char_utils::s_length, tool_utils::describe_call};
+use ratatui::widgets::block::title::Position as TitlePosition;
+
+/// 弹窗显示后需要等待的时间，避免正在输入时按下的回车键直接执行工具
+const CONFIRM_DELAY: Duration = Duration::from_secs(1);
+
+/// 工具调用确认弹窗，展示模型请求调用的所有工具及参数
+pub struct ToolPopup {
+    // 模型请求调用的工具
+    pub calls: Vec<FunctionCall>,
+    pub width: usize,
+    pub height: usize,
+    // 边框颜色
+    pub border_color: Color,
+    // 弹窗显示的时间
+    pub shown_at: Instant,
+}
+
+impl ToolPopup {
+    pub fn new(calls: Vec<FunctionCall>) -> Self {
+        Self {
+            calls,
+            width: 60,
+            height: 3,
+            border_color: Color::Magenta,
+            shown_at: Instant::now(),
+        }
+    }
+
+    /// 是否已经可以确认执行
+    pub fn confirmable(&self) -> bool {
+        self.shown_at.elapsed() >= CONFIRM_DELAY
+    }
+
+    pub fn set_size(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+    }
+
+    /// 弹窗在指定宽度下需要的高度，每个工具调用按宽度换行，另加上下边框
+    pub fn preferred_height(&self, width: usize) -> usize {
+        let width = width.saturating_sub(2).max(1);
+        self.calls
+            .iter()
+            .map(|call| s_length(format!("- {}", describe_call(call))).div_ceil(width).max(1))
+            .sum::<usize>()
+            + 2
+    }
+}
+
+impl ToolPopup {
+    pub fn draw(&self, frame: &mut Frame, area: Rect) {
+        // 先清空弹窗区域内容
+        frame.render_widget(Clear, area);
+        let block = Block::bordered()
+            .title(
+                Title::from("Run Tools?")
+                    .position(TitlePosition::Top)
+                    .alignment(Alignment::Left),
+            )
+            .title(
+                Title::from(if self.confirmable() {
+                    "Run (Enter)"
+                } else {
+                    "Run (wait...)"
+                })
+                .position(TitlePosition::Bottom)
+                .alignment(Alignment::Left),
+            )
+            .title(
+                Title::from("Decline (ESC)")
+                    .position(TitlePosition::Bottom)
+                    .alignment(Alignment::Right),
+            )
+            .borders(Borders::ALL)
+            .border_style(self.border_color);
+        let lines: Vec<Line> = self
+            .calls
+            .iter()
+            .map(|call| {
+                Line::from(vec![
+                    Span::styled("- ", Color::Yellow),
+                    Span::styled(describe_call(call), Style::default().fg(Color::LightMagenta)),
+                ])
+            })
+            .collect();
+        let paragraph = Paragraph::new(lines).block(block).wrap(Wrap { trim: false });
+        frame.render_widget(paragraph, area);
+    }
+}
+
+pub enum ToolPopupHandleEvent {
+    /// 执行所有工具调用，弹窗显示一段时间后才能确认
+    Run,
+    /// 拒绝执行，拒绝的结果同样会发送给模型
+    Decline,
+    Nothing,
+}
+
+impl ToolPopup {
+    pub fn handle_key(&mut self, key: event::KeyEvent) -> ToolPopupHandleEvent {
+        match key.code {
+            event::KeyCode::Enter if self.confirmable() => ToolPopupHandleEvent::Run,
+            event::KeyCode::Esc => ToolPopupHandleEvent::Decline,
+            _ => ToolPopupHandleEvent::Nothing,
+        }
+    }
+}
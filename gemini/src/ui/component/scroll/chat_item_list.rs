@@ -11,8 +11,9 @@ use ratatui::{
 
 use crate::{
     model::db::Conversation,
-    ui::component::popup::delete_popup::DeletePopup,
-    utils::db_utils::{delete_one, query_all, query_detail_by_id},
+    store::ConversationStore,
+    ui::component::popup::{delete_popup::DeletePopup, export_popup::ExportPopup},
+    utils::db_utils::conversation_store,
 };
 
 /// 滚动条相关属性
@@ -28,6 +29,8 @@ pub struct ChatItemListScrollProps {
     pub selected_conversation: usize,
     /// 是否展示确认删除弹窗
     pub popup_delete_confirm_dialog: Option<DeletePopup>,
+    /// 是否展示导出格式选择弹窗
+    pub popup_export_dialog: Option<ExportPopup>,
     /// 是否展示
     pub show: bool,
 }
@@ -114,7 +117,7 @@ impl ChatItemListScrollProps {
     /// 重建聊天记录
     pub fn rebuild(&self) -> Option<Conversation> {
         let selected_conversation = self.chat_history.get(self.selected_conversation)?;
-        if let Ok(conversation) = query_detail_by_id(selected_conversation.conversation.clone()) {
+        if let Ok(conversation) = conversation_store().load(&selected_conversation.conversation.conversation_id) {
             Some(conversation)
         } else {
             None
@@ -149,7 +152,7 @@ impl ChatItemListScrollProps {
     /// 删除选中的会话
     pub fn delete_item(&mut self) -> String {
         let id = if let Some(selected_conversation) = self.chat_history.get(self.selected_conversation) {
-            let _ = delete_one(selected_conversation.conversation.clone());
+            let _ = conversation_store().delete(&selected_conversation.conversation.conversation_id);
             selected_conversation.conversation.conversation_id.clone()
         } else {
             "".into()
@@ -163,7 +166,7 @@ impl ChatItemListScrollProps {
     /// 查询所有会话
     fn query_all(&self, focused: bool) -> Vec<SelectableConversation> {
         let mut conversations = Vec::new();
-        for (index, conversation) in query_all().unwrap_or_default().iter().enumerate() {
+        for (index, conversation) in conversation_store().list().unwrap_or_default().iter().enumerate() {
             let conversation = conversation.clone();
             if index == self.selected_conversation {
                 conversations.push(SelectableConversation {
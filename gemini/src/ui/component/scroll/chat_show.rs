@@ -1,8 +1,18 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
 use ratatui::{
     buffer::Buffer,
-    layout::{Constraint, Rect},
+    layout::{Alignment, Constraint, Rect},
     style::{Color, Style},
-    widgets::{Block, Borders, Scrollbar, ScrollbarOrientation, ScrollbarState, StatefulWidget, Widget},
+    text::{Line, Text},
+    widgets::{
+        block::{Position, Title},
+        Block, Borders, Scrollbar, ScrollbarOrientation, ScrollbarState, StatefulWidget, Widget,
+    },
     Frame,
 };
 
@@ -10,7 +20,10 @@ use crate::{model::view::ChatMessage, utils::char_utils::c_len};
 
 use ratatui::layout::{Constraint::Length, Layout};
 
-use crate::model::view::Sender::Never;
+use crate::model::view::Sender::{Bot, Never};
+use crate::utils::markdown_utils::render_markdown;
+use crate::utils::preview_utils::{load_preview, GraphicsProtocol, ImagePlacement, ImagePreview, PROTOCOL};
+use crate::utils::schema_utils::pretty_json;
 
 /// 滚动条相关属性
 #[derive(Default)]
@@ -21,11 +34,36 @@ pub struct ChatShowScrollProps {
     pub scroll_offset: u16,
     /// 聊天历史记录区域高度
     pub chat_history_area_height: u16,
+    /// 是否自动滚动到最新消息底部
+    pub auto_scroll: bool,
+    /// 当前选中的消息索引
+    pub selected_message: Option<usize>,
+    /// 每一条消息顶部在聊天记录区域中的位置
+    pub message_offsets: Vec<u16>,
+    /// 是否展示回复的 Markdown 原文
+    pub raw_markdown: bool,
+    /// 回复的渲染结果缓存，以回复内容与宽度为键，避免每次绘制都重新解析与高亮
+    pub markdown_cache: HashMap<(String, usize), Text<'static>>,
+    /// 操作提示及其出现的时间
+    pub notice: Option<(String, Instant)>,
+    /// 下次绘制时是否滚动到选中的消息
+    pub scroll_to_selected: bool,
+    /// 图片预览缓存，以图片记录 id 与最大宽度为键，图片无法读取时为空
+    pub preview_cache: HashMap<(String, u16), Option<Arc<ImagePreview>>>,
+    /// 需要通过图形协议输出的图片预览位置，只包含完整处于可见区域内的图片
+    pub image_placements: Vec<ImagePlacement>,
 }
 // 顶部底部边框以及时间区域高度
 static TOP_BOTTOM_BORDER_TIME_HEIGHT: u16 = 3;
+/// 操作提示的展示时长
+const NOTICE_DURATION: Duration = Duration::from_secs(2);
 
 impl ChatShowScrollProps {
+    /// 展示一条操作提示，一段时间后自动消失
+    pub fn show_notice(&mut self, notice: String) {
+        self.notice = Some((notice, Instant::now()));
+    }
+
     pub fn draw<F>(&mut self, frame: &mut Frame, area: Rect, chat_area_width: F, is_focused: bool)
     where
         F: Fn() -> usize,
@@ -33,11 +71,57 @@ impl ChatShowScrollProps {
         let chat_block = Block::default()
             .borders(Borders::ALL)
             .border_style(Style::default().fg(if is_focused { Color::Green } else { Color::White }));
+        // 展示原文时在边框上标注
+        let chat_block = if self.raw_markdown {
+            chat_block.title(Title::from("Raw Markdown").alignment(Alignment::Right))
+        } else {
+            chat_block
+        };
+        // 展示未过期的操作提示
+        if self
+            .notice
+            .as_ref()
+            .is_some_and(|(_, time)| time.elapsed() > NOTICE_DURATION)
+        {
+            self.notice = None;
+        }
+        let chat_block = if let Some((notice, _)) = self.notice.as_ref() {
+            chat_block.title(
+                Title::from(Line::styled(format!(" {} ", notice), Color::LightGreen))
+                    .position(Position::Bottom)
+                    .alignment(Alignment::Right),
+            )
+        } else {
+            chat_block
+        };
+        // 只保留本次绘制用到的缓存
+        let mut markdown_cache = std::mem::take(&mut self.markdown_cache);
+        let mut used_cache = HashMap::new();
+        let mut preview_cache = std::mem::take(&mut self.preview_cache);
+        let mut used_preview_cache = HashMap::new();
         let items: Vec<ChatMessage> = self
             .chat_history
             .iter()
-            .map(|m| {
+            .enumerate()
+            .map(|(i, m)| {
                 let area_width = chat_area_width();
+                // 回复内容按 Markdown 渲染，渲染结果已按宽度换行，JSON 回复格式化后作为代码块渲染
+                if matches!(m.sender, Bot) && !self.raw_markdown {
+                    let key = (m.message.clone(), area_width);
+                    let rendered = markdown_cache.remove(&key).unwrap_or_else(|| {
+                        let source = match pretty_json(&m.message) {
+                            Some(json) => format!("```json\n{}\n```", json),
+                            None => m.message.clone(),
+                        };
+                        render_markdown(&source, area_width)
+                    });
+                    used_cache.insert(key, rendered.clone());
+                    return ChatMessage {
+                        rendered: Some(rendered),
+                        selected: self.selected_message == Some(i),
+                        ..m.clone()
+                    };
+                }
                 let mut message = String::new();
                 // 对长文本进行插入换行符号
                 let mut line_width = 0;
@@ -60,33 +144,76 @@ impl ChatShowScrollProps {
                         line_width = 0;
                     }
                 }
-                ChatMessage { message, ..m.clone() }
+                // 加载图片预览，预览宽度不超过消息区域宽度
+                let previews = m
+                    .image_record_ids
+                    .iter()
+                    .filter_map(|image_record_id| {
+                        let key = (image_record_id.clone(), area_width as u16);
+                        let preview = preview_cache
+                            .remove(&key)
+                            .unwrap_or_else(|| load_preview(image_record_id, area_width as u16));
+                        used_preview_cache.insert(key, preview.clone());
+                        preview
+                    })
+                    .collect();
+                ChatMessage {
+                    message,
+                    selected: self.selected_message == Some(i),
+                    previews,
+                    ..m.clone()
+                }
             })
             .collect();
-        // 计算当前聊天记录区域高度
-        self.chat_history_area_height = items
-            .clone()
+        self.markdown_cache = used_cache;
+        self.preview_cache = used_preview_cache;
+        // 计算每一条消息的高度
+        let heights: Vec<u16> = items
             .iter()
             .map(|item| {
                 if matches!(item.sender, Never) {
                     0
                 } else {
-                    item.message.lines().count() as u16 + TOP_BOTTOM_BORDER_TIME_HEIGHT
+                    let lines = match &item.rendered {
+                        Some(text) => text.lines.len(),
+                        None => item.message.lines().count(),
+                    };
+                    // 图片预览依次排列在文本下方
+                    let preview_height: u16 = item.previews.iter().map(|preview| preview.height).sum();
+                    lines as u16 + preview_height + TOP_BOTTOM_BORDER_TIME_HEIGHT
                 }
             })
-            .sum();
-
-        let layouts: Vec<Constraint> = items
-            .clone()
+            .collect();
+        // 记录每一条消息顶部的位置，用于滚动到选中的消息
+        self.message_offsets = heights
             .iter()
-            .map(|item| {
-                if let Never = item.sender {
-                    Length(0)
-                } else {
-                    Length(item.message.lines().count() as u16 + TOP_BOTTOM_BORDER_TIME_HEIGHT)
-                }
+            .scan(0, |offset, height| {
+                let top = *offset;
+                *offset += height;
+                Some(top)
             })
             .collect();
+        // 计算当前聊天记录区域高度
+        self.chat_history_area_height = heights.iter().sum();
+        // 滚动到选中的消息顶部
+        if self.scroll_to_selected {
+            self.scroll_to_selected = false;
+            self.auto_scroll = false;
+            if let Some(offset) = self.selected_message.and_then(|index| self.message_offsets.get(index)) {
+                let max_offset = self
+                    .chat_history_area_height
+                    .saturating_sub(area.height.saturating_sub(2));
+                self.scroll_offset = (*offset).min(max_offset);
+            }
+        }
+        // 自动滚动时，保持最新消息的底部处于可见区域内
+        if self.auto_scroll {
+            self.scroll_offset = self
+                .chat_history_area_height
+                .saturating_sub(area.height.saturating_sub(2));
+        }
+
+        let layouts: Vec<Constraint> = heights.into_iter().map(Length).collect();
 
         let chat_area_x = area.x;
         let chat_area_y = area.y;
@@ -108,6 +235,25 @@ impl ChatShowScrollProps {
         for (area, chat_message) in areas.iter().zip(items.iter()) {
             chat_message.clone().render(*area, &mut chat_list_full_area_buf);
         }
+        // 计算需要通过图形协议输出的图片位置，只输出完整处于可见区域内的图片，其余图片使用字符模拟
+        self.image_placements.clear();
+        if *PROTOCOL != GraphicsProtocol::HalfBlock {
+            let visible_top = chat_list_full_area.y + self.scroll_offset;
+            let visible_bottom = visible_top + chat_area_height.saturating_sub(2);
+            for (area, chat_message) in areas.iter().zip(items.iter()) {
+                for (preview, preview_area) in chat_message.previews.iter().zip(chat_message.preview_areas(*area)) {
+                    if preview_area.top() >= visible_top && preview_area.bottom() <= visible_bottom {
+                        self.image_placements.push(ImagePlacement {
+                            area: Rect {
+                                y: preview_area.y - self.scroll_offset,
+                                ..preview_area
+                            },
+                            preview: preview.clone(),
+                        });
+                    }
+                }
+            }
+        }
 
         // 将要被展示的聊天记录区域
         let visible_content = chat_list_full_area_buf
@@ -1,20 +1,25 @@
+use std::collections::BTreeMap;
 use std::sync::mpsc;
 use std::thread;
+use std::time::Duration;
 
-use self::component::popup::input_popup::InputPopup;
+use self::component::popup::image_popup::{ImagePopup, ImagePopupHandleEvent};
 
 use super::setting_page::SettingUI;
 use anyhow::Result;
 use chrono::Local;
 use component::input::{input_trait::InputTextComponent, text_field::TextField};
 use component::popup::delete_popup::DeletePopup;
+use component::popup::export_popup::ExportPopup;
+use component::popup::profile_popup::{ProfilePopup, ProfilePopupHandleEvent};
+use component::popup::search_popup::{SearchPopup, SearchPopupHandleEvent};
+use component::popup::tool_popup::{ToolPopup, ToolPopupHandleEvent};
 use component::scroll::chat_item_list::ChatItemListScrollProps;
 use component::scroll::chat_show::ChatShowScrollProps;
 use gemini_api::body::request::GenerationConfig;
 use gemini_api::body::{Content, Part, Role};
 use gemini_api::model::blocking::Gemini;
 use gemini_api::param::LanguageModel;
-use gemini_api::utils::image::blocking::get_image_type_and_base64_string;
 use ratatui::layout::Position as CursorPosition;
 use ratatui::layout::{Alignment, Rect};
 use ratatui::style::{Color, Style};
@@ -32,50 +37,99 @@ use ratatui::{
 };
 use strum::{EnumCount, FromRepr};
 
-use crate::model::view::ChatMessage;
-use crate::model::view::Sender::{Bot, Never, User};
+use crate::model::db::{Conversation, SearchResult};
+use crate::model::view::Sender::{Bot, Never, ToolCall, ToolResult, User};
+use crate::model::view::{
+    ChatMessage, FinishInfo, FunctionCall, FunctionResponse, MessageState, MessageVersion, TokenUsage,
+};
+use crate::store::ConversationStore;
 use crate::ui::component;
-use crate::utils::db_utils::{
-    current_db_version, generate_unique_id, modify_title, save_conversation, update_db_structure,
+use crate::utils::attachment_utils::rebuild_attachment;
+use crate::utils::char_utils::{mask, s_length};
+use crate::utils::clipboard_utils::copy_to_clipboard;
+use crate::utils::context_utils::{ContextOptions, ContextWindow};
+use crate::utils::db_utils::{conversation_store, generate_unique_id, update_db_structure};
+use crate::utils::export_utils::{export_to_file, load_for_export, ExportFormat};
+use crate::utils::gemini_utils::{model_content, summary_by_gemini, RequestOptions};
+use crate::utils::markdown_utils::code_blocks;
+use crate::utils::preview_utils::GraphicsLayer;
+use crate::utils::schema_utils::schema_mismatch;
+use crate::utils::secret_utils::{passphrase, set_passphrase};
+use crate::utils::store_utils::{
+    read_config, save_config, update_config, ContextConfig, KeySource, ModelPrice, Profile, StoreData,
+    DEFAULT_PROFILE_NAME, ENV_NAME,
+};
+use crate::utils::tool_utils::{
+    describe_calls, describe_responses, function_call_content, function_response_content, interrupted,
 };
-use crate::utils::image_utils::{cache_image, read_image_cache};
-use crate::utils::store_utils::{read_config, save_config, update_db_version_into_profile, StoreData};
+use crate::utils::worker_utils::{ChatWorker, WorkerEvent};
+
+/// 等待按键事件的最长时间，超时后重新绘制界面以展示后台任务的进度
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
 
-const ENV_NAME: &str = "GEMINI_KEY";
+/// 继续生成被截断的回复时发送的消息，只用于本次请求，不会展示与保存
+const CONTINUE_PROMPT: &str =
+    "你的上一条回复因长度限制被截断，请从中断处直接继续输出，不要重复已经输出的内容，也不要添加任何说明。";
 
 /// 窗口UI
 #[derive(Default)]
 pub struct UI {
     /// 是否正在接收消息
     receiving_message: bool,
+    /// 后台请求任务
+    chat_worker: ChatWorker,
+    /// 正在接收的回复
+    receiving_reply: ReceivingReply,
     /// 消息响应失败
     response_status: ResponseStatus,
     /// 是否应该退出程序
     should_exit: bool,
     /// Gemini API
     gemini: Option<Gemini>,
+    /// 正在输入的密钥或口令
+    key_prompt: Option<KeyPrompt>,
+    /// 当前使用的配置方案名称
+    profile: String,
+    /// 各模型的价格，用于估算会话费用
+    prices: BTreeMap<String, ModelPrice>,
+    /// 上下文窗口设置
+    context: ContextConfig,
+    /// 上一次请求使用的上下文
+    context_window: ContextWindow,
+    /// 请求附带的安全设置与本地工具
+    request_options: RequestOptions,
     /// 当前聚焦的组件
     focus_component: MainFocusComponent,
     /// 输入区域组件
     input_field_component: TextField,
     /// 当前窗口
     current_windows: CurrentWindows,
-    /// 图片路径
-    image_path: Option<String>,
+    /// 待发送消息附带的所有图片路径
+    image_paths: Vec<String>,
     /// 对话标题内容
     title: String,
     /// 对话 id
     conversation_id: String,
     /// 是否正在生成标题
     gen_title_ing: bool,
-    /// 数据库版本
-    db_version: Option<String>,
+    /// 生成的标题与其所属的会话 id 通过此通道返回
+    title_tx: Option<mpsc::Sender<(String, String)>>,
     /// 是否正在编辑标题
     title_editor_input_field: Option<TextField>,
-    /// 是否显示图片输入弹窗
-    image_url_input_popup: Option<InputPopup>,
+    /// 是否显示图片列表弹窗
+    image_popup: Option<ImagePopup>,
+    /// 正在编辑的用户消息索引
+    editing_message: Option<usize>,
+    /// 是否显示搜索弹窗
+    search_popup: Option<SearchPopup>,
+    /// 是否显示配置方案弹窗
+    profile_popup: Option<ProfilePopup>,
+    /// 是否显示工具调用确认弹窗
+    tool_popup: Option<ToolPopup>,
     chat_item_list: ChatItemListScrollProps,
     chat_show: ChatShowScrollProps,
+    /// 通过图形协议输出的图片预览
+    graphics: GraphicsLayer,
 }
 /// 窗口枚举
 #[derive(Default)]
@@ -85,6 +139,16 @@ pub enum CurrentWindows {
     SettingWindow(SettingUI),
 }
 
+/// 输入框中正在输入的密钥或口令
+pub enum KeyPrompt {
+    /// 输入密钥
+    Key,
+    /// 输入口令以解密保存的密钥
+    Unlock,
+    /// 设置口令以加密保存密钥，包含待保存的配置
+    SetPassphrase(Box<StoreData>),
+}
+
 /// 当前聚焦组件
 #[derive(Default, Clone, EnumCount, FromRepr)]
 pub enum MainFocusComponent {
@@ -110,50 +174,110 @@ pub enum ResponseStatus {
     Failed(String),
 }
 
-enum ChatType {
-    Simple { message: String },
-    Image { message: String, image_path: String },
+/// 正在接收的回复
+#[derive(Default)]
+struct ReceivingReply {
+    /// 本次发送的用户消息，请求发出后才会有值
+    content: Option<Content>,
+    /// 已接收到的原始回复内容
+    response: String,
+    /// 是否为重新生成最后一条回复
+    regenerate: bool,
+    /// 是否为编辑后重新发送的消息，接收完成后需要重新加载会话以更新分支信息
+    edited: bool,
+    /// 本次请求的 token 用量
+    usage: Option<TokenUsage>,
+    /// 本次回复结束的原因与安全评级
+    finish: Option<FinishInfo>,
+    /// 继续生成时原有的回复内容，新接收到的内容拼接在其后
+    prefix: String,
+    /// 继续生成时原有回复的 token 用量，与本次请求的用量累加
+    prefix_usage: Option<TokenUsage>,
+    /// 模型请求调用的本地工具
+    function_calls: Vec<FunctionCall>,
+    /// 是否已被用户取消，已接收到的内容仍然保留
+    cancelled: bool,
+    /// 本次请求是否执行了本地工具，执行后的请求失败时不会再次确认执行
+    tools_run: bool,
+}
+
+impl ReceivingReply {
+    /// 展示与保存的回复内容，继续生成时包含原有的回复
+    fn text(&self) -> String {
+        format_response(&format!("{}{}", self.prefix, self.response))
+    }
+
+    /// 回复消耗的 token 数量，继续生成时包含原有回复的用量
+    fn total_usage(&self) -> Option<TokenUsage> {
+        match (self.prefix_usage, self.usage) {
+            (Some(mut total), Some(usage)) => {
+                total += usage;
+                Some(total)
+            }
+            (total, usage) => total.or(usage),
+        }
+    }
 }
 
 impl UI {
     /// 启动UI
     pub fn run(mut self, mut terminal: DefaultTerminal) -> Result<()> {
-        let (chat_tx, chat_rx) = mpsc::channel();
         let (title_tx, title_rx) = mpsc::channel();
-        self.restore_or_new_gemini(None);
-        // 如果数据库版本不一致，则更新数据库结构，补全更新数据库版本
-        if self.db_version.clone().unwrap_or_default() != current_db_version() {
-            // 更新数据库结构
-            update_db_structure()?;
-            self.db_version = Some(current_db_version());
-        }
+        self.title_tx = Some(title_tx);
+        // 执行未执行的数据库迁移脚本
+        update_db_structure()?;
+        self.restore_or_new_gemini();
         while !self.should_exit {
             // 异步生成标题
-            if let Ok(title) = title_rx.try_recv() {
+            if let Ok((conversation_id, title)) = title_rx.try_recv() {
                 self.gen_title_ing = false;
-                self.title = title;
+                // 将生成的标题保存到生成时所在的会话，使其可以被搜索，期间可能已切换到其它会话
+                let _ = conversation_store().rename(&conversation_id, &title);
+                if conversation_id == self.conversation_id {
+                    self.title = title;
+                }
             }
+            // 处理后台请求任务返回的事件
+            self.handle_worker_events();
             match self.current_windows {
                 CurrentWindows::MainWindow => {
                     terminal.draw(|frame| self.draw(frame))?;
-                    self.handle_key(chat_tx.clone(), title_tx.clone(), &chat_rx);
+                    // 图片位置发生变化时，清除旧的图片并重新绘制界面，再输出新的图片
+                    if self.graphics.changed(&self.chat_show.image_placements) {
+                        self.graphics.clear(&mut terminal)?;
+                        terminal.draw(|frame| self.draw(frame))?;
+                        self.graphics.show(&mut terminal, &self.chat_show.image_placements)?;
+                    }
+                    self.handle_key()?;
                 }
                 CurrentWindows::SettingWindow(ref mut setting_ui) => {
                     if setting_ui.should_exit {
                         // 如果配置更新了，则重构 Gemini API
                         if setting_ui.update {
-                            self.restore_or_new_gemini(None);
+                            if let Some(profile) = setting_ui.conversation_profile.take() {
+                                // 会话设置只应用到当前会话
+                                let contents = self.gemini.clone().map(|gemini| gemini.contents).unwrap_or_default();
+                                self.rebuild_gemini(profile, contents);
+                                self.save_conversation_settings();
+                                self.validate_replies();
+                            } else if let Some(data) = setting_ui.unsaved.take() {
+                                // 新的密钥需要设置口令后才能保存
+                                self.key_prompt = Some(KeyPrompt::SetPassphrase(Box::new(data)));
+                                self.focus_component = MainFocusComponent::InputField;
+                            } else {
+                                self.restore_or_new_gemini();
+                            }
                         }
                         self.current_windows = CurrentWindows::MainWindow;
                     } else {
+                        // 设置页面不展示图片预览
+                        self.graphics.clear(&mut terminal)?;
                         terminal.draw(|frame| setting_ui.draw(frame))?;
                         setting_ui.handle_key();
                     }
                 }
             }
         }
-        // 程序退出时，保存数据版本变更
-        let _ = update_db_version_into_profile();
         Ok(())
     }
 
@@ -161,76 +285,182 @@ impl UI {
         self.chat_show.chat_history_area_height
     }
 
-    /// 尝试通过读取环境变量信息初始化 Gemini API
-    fn restore_or_new_gemini(&mut self, key: Option<String>) {
+    /// 读取配置文件初始化 Gemini API，无法获取密钥时提示输入密钥或口令
+    fn restore_or_new_gemini(&mut self) {
         // 尝试读取配置文件
-        match read_config() {
-            Ok(store_data) => {
-                if let Some(gemini_origin) = self.gemini.clone() {
-                    // gemini 已经存在，则此方法是在settings页面切换到main页面，更新配置信息
-                    let mut gemini_new = Gemini::rebuild(
-                        store_data.key,
-                        store_data.model,
-                        gemini_origin.contents,
-                        store_data.options,
-                    );
-                    gemini_new.set_system_instruction(store_data.system_instruction.unwrap_or("".into()));
-                    self.gemini = Some(gemini_new)
-                } else {
-                    // gemini 不存在，读取到配置文件则直接使用配置文件中的 Gemini API
-                    let mut gemini_new =
-                        Gemini::rebuild(store_data.key, store_data.model, Vec::new(), store_data.options);
-                    gemini_new.set_system_instruction(store_data.system_instruction.unwrap_or("".into()));
-                    // 读取数据库版本
-                    self.db_version = store_data.db_version;
-                    self.gemini = Some(gemini_new)
+        let mut store_data = match read_config() {
+            Ok(store_data) => store_data,
+            Err(_) => {
+                // 没有配置文件时，尝试从环境变量中读取密钥，否则提示输入密钥
+                match std::env::var(ENV_NAME) {
+                    Ok(key) if !key.is_empty() => self.init_gemini(key),
+                    _ => self.key_prompt = Some(KeyPrompt::Key),
                 }
+                return;
             }
-            Err(_) => {
-                if let Some(key) = key {
-                    // 尝试从 key 构造 Gemini API
-                    self.init_gemini(key);
-                } else if let Ok(key) = std::env::var(ENV_NAME) {
-                    // 尝试从环境变量中读取密钥
-                    self.init_gemini(key);
+        };
+        self.profile = store_data.profile.clone();
+        self.prices = store_data.prices.clone();
+        self.context = store_data.context.clone();
+        self.request_options.safety = store_data.safety.clone();
+        self.request_options.tools = store_data.tools.clone();
+        // 密钥加密保存且没有口令时，提示输入口令
+        if store_data.is_locked() {
+            self.key_prompt = Some(KeyPrompt::Unlock);
+            return;
+        }
+        // 旧版本保存的明文密钥，需要在读取环境变量中的密钥之前判断
+        let plaintext = store_data.is_plaintext();
+        if let Err(e) = store_data.resolve_key() {
+            self.response_status = ResponseStatus::Failed(e.to_string());
+            self.key_prompt = if matches!(store_data.key_source, KeySource::Encrypted(_)) {
+                // 口令错误，清除后重新输入
+                set_passphrase(None);
+                Some(KeyPrompt::Unlock)
+            } else {
+                Some(KeyPrompt::Key)
+            };
+            return;
+        }
+        // 旧版本保存的明文密钥，设置口令后加密保存
+        self.key_prompt = None;
+        if plaintext {
+            if passphrase().is_some() {
+                let _ = save_config(store_data.clone());
+            } else {
+                self.key_prompt = Some(KeyPrompt::SetPassphrase(Box::new(store_data.clone())));
+            }
+        }
+        match self.gemini.clone() {
+            // gemini 已经存在，则此方法是在settings页面切换到main页面、切换配置方案或解锁密钥，已保存的会话保留其配置，只更新密钥
+            Some(gemini_origin) if !self.conversation_id.is_empty() => {
+                let mut gemini_new = Gemini::rebuild(
+                    store_data.key,
+                    gemini_origin.model,
+                    gemini_origin.contents,
+                    gemini_origin.options,
+                );
+                gemini_new.set_system_instruction(gemini_origin.system_instruction.unwrap_or_default());
+                self.gemini = Some(gemini_new);
+            }
+            // 尚未保存的新会话，或 gemini 不存在时，直接使用配置文件中的配置
+            gemini_origin => {
+                let contents = gemini_origin.map(|gemini| gemini.contents).unwrap_or_default();
+                let mut gemini_new = Gemini::rebuild(store_data.key, store_data.model, contents, store_data.options);
+                gemini_new.set_system_instruction(store_data.system_instruction.unwrap_or("".into()));
+                self.gemini = Some(gemini_new);
+                self.request_options.response_schema = store_data.response_schema;
+                self.validate_replies();
+            }
+        }
+    }
+
+    /// 提交输入的密钥或口令
+    fn submit_key_prompt(&mut self, key_prompt: KeyPrompt, input: String) {
+        match key_prompt {
+            KeyPrompt::Key => {
+                // 保留已有的配置，使用输入的密钥替换原有的密钥来源
+                let mut data = read_config().unwrap_or_else(|_| default_store_data());
+                data.key = input;
+                data.key_source = KeySource::None;
+                if passphrase().is_some() {
+                    self.save_key(data);
+                } else {
+                    self.key_prompt = Some(KeyPrompt::SetPassphrase(Box::new(data)));
                 }
             }
+            KeyPrompt::Unlock => {
+                set_passphrase(Some(input));
+                self.restore_or_new_gemini();
+            }
+            KeyPrompt::SetPassphrase(data) => {
+                set_passphrase(Some(input));
+                self.save_key(*data);
+            }
         }
     }
 
-    /// 设置图片或清除图片路径
-    fn show_image_input(&mut self) {
-        if self.image_url_input_popup.is_none() {
-            self.image_url_input_popup = Some(InputPopup::new(self.image_path.clone().unwrap_or_default(), 50, 3));
+    /// 加密保存密钥，并使用新的配置重建 Gemini API
+    fn save_key(&mut self, data: StoreData) {
+        if let Err(e) = save_config(data.clone()) {
+            self.response_status = ResponseStatus::Failed(e.to_string());
+            self.key_prompt = Some(KeyPrompt::SetPassphrase(Box::new(data)));
+            return;
         }
+        self.restore_or_new_gemini();
     }
 
-    /// 初始化 Gemini API
-    fn init_gemini(&mut self, key: String) {
-        let system_instruction = String::new();
-        let mut gemini = Gemini::new(key, LanguageModel::Gemini1_5Flash);
-        gemini.set_options(GenerationConfig::default());
-        gemini.set_system_instruction(system_instruction.clone());
-        let data = StoreData {
-            key: gemini.key.clone(),
+    /// 修改配置方案，并使用修改后的配置方案重建 Gemini API
+    fn change_profile(&mut self, f: impl FnOnce(&mut StoreData) -> Result<()>) {
+        match update_config(f) {
+            Ok(()) => self.restore_or_new_gemini(),
+            Err(e) => self.response_status = ResponseStatus::Failed(e.to_string()),
+        }
+    }
+
+    /// 使用配置方案中的模型、系统指令与生成配置重建 Gemini API，保留原有的密钥
+    fn rebuild_gemini(&mut self, profile: Profile, contents: Vec<Content>) {
+        self.request_options.response_schema = profile.response_schema;
+        if let Some(gemini) = self.gemini.clone() {
+            let mut gemini_new = Gemini::rebuild(gemini.key, profile.model, contents, profile.options);
+            gemini_new.set_system_instruction(profile.system_instruction.unwrap_or_default());
+            self.gemini = Some(gemini_new);
+        }
+    }
+
+    /// 当前会话使用的模型、系统指令与生成配置
+    fn conversation_settings(&self) -> Option<Profile> {
+        self.gemini.as_ref().map(|gemini| Profile {
             model: gemini.model.clone(),
-            system_instruction: Some(system_instruction),
+            system_instruction: gemini.system_instruction.clone(),
             options: gemini.options.clone(),
-            db_version: None,
-        };
-        gemini.start_chat(Vec::new());
-        let _ = save_config(data);
-        self.gemini = Some(gemini)
+            response_schema: self.request_options.response_schema.clone(),
+            ..Default::default()
+        })
     }
 
-    /// 判断图片路径是否为空
-    fn blank_image(&self) -> bool {
-        if let Some(image_path) = self.image_path.clone() {
-            image_path.is_empty()
-        } else {
-            true
+    /// 将当前使用的模型、系统指令与生成配置保存到会话，尚未保存的新会话除外
+    ///
+    /// 只在新建会话以及通过会话设置窗口修改配置时调用，全局配置与配置方案只应用到新的会话
+    fn save_conversation_settings(&self) {
+        if self.conversation_id.is_empty() {
+            return;
+        }
+        if let Some(profile) = self.conversation_settings() {
+            let _ = conversation_store().modify_settings(
+                &self.conversation_id,
+                &profile.model.to_string(),
+                profile.system_instruction.as_deref(),
+                &profile.options_json(),
+            );
+        }
+    }
+
+    /// 打开当前会话的设置窗口
+    fn open_conversation_setting(&mut self) {
+        if let Some(profile) = self.conversation_settings() {
+            self.current_windows = CurrentWindows::SettingWindow(SettingUI::new_for_conversation(profile));
+        }
+    }
+
+    /// 添加或移除图片
+    fn show_image_input(&mut self) {
+        if self.image_popup.is_none() {
+            self.image_popup = Some(ImagePopup::new(self.image_paths.clone()));
         }
     }
+
+    /// 使用环境变量中的密钥初始化 Gemini API，配置文件中不保存密钥
+    fn init_gemini(&mut self, key: String) {
+        let data = default_store_data();
+        self.profile = data.profile.clone();
+        let mut gemini = Gemini::new(key, data.model.clone());
+        gemini.set_options(data.options.clone());
+        gemini.set_system_instruction(data.system_instruction.clone().unwrap_or_default());
+        gemini.start_chat(Vec::new());
+        let _ = save_config(data);
+        self.gemini = Some(gemini)
+    }
 }
 
 /// 渲染 UI
@@ -256,14 +486,62 @@ impl UI {
             let rect = Rect::new(x, y, popup.width as u16, popup.height as u16);
             popup.draw(frame, rect);
         }
-        // 是否显示图片输入弹窗
-        if let Some(ref mut popup) = self.image_url_input_popup {
-            popup.set_size(area.width.saturating_sub(50).max(50) as usize, 3);
+        // 是否显示导出弹窗
+        if let Some(popup) = self.chat_item_list.popup_export_dialog.clone() {
+            let x = (area.width - popup.width as u16) / 2;
+            let y = (area.height - popup.height as u16) / 2;
+            let rect = Rect::new(x, y, popup.width as u16, popup.height as u16);
+            popup.draw(frame, rect);
+        }
+        // 是否显示搜索弹窗
+        if let Some(ref mut popup) = self.search_popup {
+            popup.set_size(
+                area.width.saturating_sub(20).clamp(40.min(area.width), 100) as usize,
+                area.height.saturating_sub(6).max(8.min(area.height)) as usize,
+            );
+            let x = (area.width - popup.width as u16) / 2;
+            let y = (area.height - popup.height as u16) / 2;
+            let rect = Rect::new(x, y, popup.width as u16, popup.height as u16);
+            popup.draw(frame, rect);
+        }
+        // 是否显示图片列表弹窗
+        if let Some(ref mut popup) = self.image_popup {
+            let height = popup.preferred_height().min(area.height as usize);
+            popup.set_size(area.width.saturating_sub(50).max(50).min(area.width) as usize, height);
+            let x = (area.width - popup.width as u16) / 2;
+            let y = (area.height - popup.height as u16) / 2;
+            let rect = Rect::new(x, y, popup.width as u16, popup.height as u16);
+            popup.draw(frame, rect);
+        }
+        // 是否显示配置方案弹窗
+        if let Some(ref mut popup) = self.profile_popup {
+            let height = popup.preferred_height().min(area.height as usize);
+            popup.set_size(60.min(area.width) as usize, height);
+            let x = (area.width - popup.width as u16) / 2;
+            let y = (area.height - popup.height as u16) / 2;
+            let rect = Rect::new(x, y, popup.width as u16, popup.height as u16);
+            popup.draw(frame, rect);
+        }
+        // 是否显示工具调用确认弹窗
+        if let Some(ref mut popup) = self.tool_popup {
+            let width = area.width.saturating_sub(20).clamp(40.min(area.width), 100) as usize;
+            let height = popup.preferred_height(width).min(area.height as usize);
+            popup.set_size(width, height);
             let x = (area.width - popup.width as u16) / 2;
             let y = (area.height - popup.height as u16) / 2;
             let rect = Rect::new(x, y, popup.width as u16, popup.height as u16);
             popup.draw(frame, rect);
         }
+        // 图形协议输出的图片会遮挡弹窗，显示弹窗时不输出图片
+        if self.chat_item_list.popup_delete_confirm_dialog.is_some()
+            || self.chat_item_list.popup_export_dialog.is_some()
+            || self.search_popup.is_some()
+            || self.image_popup.is_some()
+            || self.profile_popup.is_some()
+            || self.tool_popup.is_some()
+        {
+            self.chat_show.image_placements.clear();
+        }
     }
 
     /// 渲染左侧区域
@@ -326,8 +604,18 @@ impl UI {
 
     /// 渲染头部区域
     fn render_header_area(&mut self, frame: &mut Frame, header_area: Rect) {
-        let [tip_area, title_area, edit_tip_area] =
-            Layout::horizontal([Length(10), Fill(1), Length(10)]).areas(header_area);
+        // 当前配置方案名称显示在右侧
+        let profile_text = format!("{}(F2)", self.profile);
+        // 会话累计的 token 用量显示在配置方案名称左侧
+        let usage_text = self.usage_text();
+        let [tip_area, title_area, usage_area, profile_area, edit_tip_area] = Layout::horizontal([
+            Length(10),
+            Fill(1),
+            Length(s_length(usage_text.clone()) as u16 + 1),
+            Length(s_length(profile_text.clone()) as u16 + 1),
+            Length(10),
+        ])
+        .areas(header_area);
         let tip_text = if self.chat_item_list.show { "< F3" } else { "> F3" };
         let tip_paragraph = Paragraph::new(tip_text)
             .style(Style::default().fg(Color::Red))
@@ -365,6 +653,44 @@ impl UI {
             .style(Style::default().fg(Color::Red))
             .right_aligned();
         frame.render_widget(edit_tip_paragraph, edit_tip_area);
+
+        let profile_paragraph = Paragraph::new(profile_text)
+            .style(Style::default().fg(Color::Yellow))
+            .right_aligned();
+        frame.render_widget(profile_paragraph, profile_area);
+
+        let usage_paragraph = Paragraph::new(usage_text)
+            .style(Style::default().fg(Color::Green))
+            .right_aligned();
+        frame.render_widget(usage_paragraph, usage_area);
+    }
+
+    /// 会话累计的 token 用量，没有用量时为空
+    ///
+    /// 每条回复按其生成时使用的模型的价格估算费用，未记录模型的回复使用当前模型，所用模型均配置了价格时才附带费用
+    fn usage_text(&self) -> String {
+        let current_model = self.gemini.as_ref().map(|gemini| gemini.model.to_string());
+        let mut total = TokenUsage::default();
+        let mut cost = Some(0.0);
+        let mut used = false;
+        for chat_message in self.chat_show.chat_history.iter() {
+            let Some(usage) = chat_message.usage else {
+                continue;
+            };
+            used = true;
+            total += usage;
+            let price = chat_message
+                .model
+                .as_ref()
+                .or(current_model.as_ref())
+                .and_then(|model| self.prices.get(model));
+            cost = cost.zip(price).map(|(cost, price)| cost + price.cost(usage));
+        }
+        match (used, cost) {
+            (false, _) => String::new(),
+            (true, Some(cost)) => format!("{} tokens ≈ ${:.4}", total.total(), cost),
+            (true, None) => format!("{} tokens", total.total()),
+        }
     }
 
     /// 渲染输入区域
@@ -373,23 +699,29 @@ impl UI {
         self.input_field_component
             .set_width_height(input_area.width as usize - 2, 1);
         // 输入区域（底部）
-        let input_block_title = if self.gemini.is_none() {
-            "Input Key"
+        let input_block_title = if let Some(key_prompt) = &self.key_prompt {
+            match key_prompt {
+                KeyPrompt::Key => "Input Key",
+                KeyPrompt::Unlock => "Input Passphrase",
+                KeyPrompt::SetPassphrase(_) => "Set a Passphrase to Encrypt the Key",
+            }
+        } else if self.receiving_message {
+            // 如果处于等待消息接收状态，则显示等待提示，此时仍可编辑下一条消息
+            "Receiving message... Press Esc to cancel"
+        } else if self.editing_message.is_some() {
+            "Editing message... Press Esc to cancel"
         } else {
             "Input Text"
         };
         // 根据图片是否为空设置文本
-        let title = if self.blank_image() {
-            Title::from("Press F4 Set Image Path")
+        let title = if self.image_paths.is_empty() {
+            Title::from("Press F4 Add Attachments")
                 .position(TitlePosition::Top)
                 .alignment(Alignment::Right)
         } else {
-            Title::from(format!(
-                "[{}] Press F4 Modify Image Path",
-                self.image_path.clone().unwrap_or_default()
-            ))
-            .position(TitlePosition::Top)
-            .alignment(Alignment::Right)
+            Title::from(format!("[{}] Press F4 Modify Attachments", self.image_paths.join(", ")))
+                .position(TitlePosition::Top)
+                .alignment(Alignment::Right)
         };
 
         // 根据是否选中组件变色
@@ -408,15 +740,14 @@ impl UI {
                     Color::White
                 }),
             );
-        // 输入框内容
-        let text = self.input_field_component.should_show_text();
+        // 输入框内容，密钥与口令不显示明文
+        let text = if self.key_prompt.is_some() {
+            mask(&self.input_field_component.should_show_text())
+        } else {
+            self.input_field_component.should_show_text()
+        };
 
-        let input_paragraph = if self.receiving_message {
-            // 如果处于等待消息接收状态，则显示等待提示
-            Paragraph::new("Receiving message...")
-                .block(input_block)
-                .style(Style::default().fg(Color::Cyan))
-        } else if let ResponseStatus::Failed(msg) = &self.response_status {
+        let input_paragraph = if let ResponseStatus::Failed(msg) = &self.response_status {
             // 接收响应消息失败
             let text = msg.clone();
             Paragraph::new(text)
@@ -450,164 +781,105 @@ impl UI {
 
 /// 处理输入事件
 impl UI {
-    /// 处理按键事件
-    fn handle_key(
-        &mut self,
-        chat_tx: mpsc::Sender<ChatType>,
-        title_rx: mpsc::Sender<String>,
-        chat_rx: &mpsc::Receiver<ChatType>,
-    ) {
-        // 如果接收消息位为真
-        if self.receiving_message {
-            // 阻塞接收消息
-            if let Ok(request) = chat_rx.recv() {
-                match request {
-                    ChatType::Simple { message } => {
-                        match self.gemini.as_mut().unwrap().send_simple_message(message) {
-                            // 成功接收响应消息后，将响应消息封装后加入到消息列表以供展示
-                            Ok((response, _)) => {
-                                // 如果 id 为空，则生成唯一 id
-                                if self.conversation_id.is_empty() {
-                                    self.conversation_id = generate_unique_id();
-                                    // 由于是新建会话，若想保持聊天列表选中状态，则需要将选中项加一
-                                    self.chat_item_list.selected_conversation += 1;
-                                }
-                                // 如果标题为空，则总结标题
-                                if self.title.is_empty() && !self.gen_title_ing {
-                                    self.gen_title_ing = true;
-                                    let key = self.gemini.clone().unwrap().key.clone();
-                                    let response = response.clone();
-                                    // 总结标题
-                                    thread::spawn(move || {
-                                        let title = summary_by_gemini(key, response);
-                                        let _ = title_rx.send(title);
-                                    });
-                                }
-                                // 推送用户发送的消息保存到数据库
-                                let chat_message = self.chat_show.chat_history.pop().unwrap();
-                                let _ = save_conversation(
-                                    self.conversation_id.clone(),
-                                    self.title.clone(),
-                                    chat_message.clone(),
-                                );
-                                self.chat_show.chat_history.push(chat_message);
-                                let response = response.replace("\n\n", "\n");
-                                let response = if response.ends_with("\n") {
-                                    response[..response.len() - 1].to_owned()
-                                } else {
-                                    response
-                                };
-                                let chat_message = ChatMessage {
-                                    success: true,
-                                    sender: Bot,
-                                    message: response,
-                                    date_time: Local::now(),
-                                };
-                                // 推送接收到的消息保存到数据库
-                                let _ = save_conversation(
-                                    self.conversation_id.clone(),
-                                    self.title.clone(),
-                                    chat_message.clone(),
-                                );
-                                self.chat_show.chat_history.push(chat_message);
-                            }
-                            // 接收响应消息失败，将响应状态位改为失败，并提供错误信息
-                            Err(e) => {
-                                if let Some(msg) = e.downcast_ref::<String>() {
-                                    self.response_status = ResponseStatus::Failed(msg.clone());
-                                } else {
-                                    self.response_status = ResponseStatus::Failed("Unknown Error".into());
-                                }
-                                // 将最后一条消息状态修改为失败
-                                let mut chat_message = self.chat_show.chat_history.pop().unwrap();
-                                chat_message.success = false;
-                                self.chat_show.chat_history.push(chat_message);
-                            }
-                        }
-                    }
-                    ChatType::Image { message, image_path } => {
-                        match self.gemini.as_mut().unwrap().send_image_message(image_path, message) {
-                            // 成功接收响应消息后，将响应消息封装后加入到消息列表以供展示
-                            Ok((response, _)) => {
-                                // 如果 id 为空，则生成唯一 id
-                                if self.conversation_id.is_empty() {
-                                    self.conversation_id = generate_unique_id();
-                                    // 由于是新建会话，若想保持聊天列表选中状态，则需要将选中项加一
-                                    self.chat_item_list.selected_conversation += 1;
-                                }
-                                // 如果标题为空，则总结标题
-                                if self.title.is_empty() && !self.gen_title_ing {
-                                    self.gen_title_ing = true;
-                                    let key = self.gemini.clone().unwrap().key.clone();
-                                    let response = response.clone();
-                                    // 总结标题
-                                    thread::spawn(move || {
-                                        let title = summary_by_gemini(key, response);
-                                        let _ = title_rx.send(title);
-                                    });
-                                }
-                                // 推送用户发送的消息保存到数据库
-                                let chat_message = self.chat_show.chat_history.pop().unwrap();
-                                let _ = save_conversation(
-                                    self.conversation_id.clone(),
-                                    self.title.clone(),
-                                    chat_message.clone(),
-                                );
-                                self.chat_show.chat_history.push(chat_message);
-                                let response = response.replace("\n\n", "\n");
-                                let response = if response.ends_with("\n") {
-                                    response[..response.len() - 1].to_owned()
-                                } else {
-                                    response
-                                };
-                                let chat_message = ChatMessage {
-                                    success: true,
-                                    sender: Bot,
-                                    message: response,
-                                    date_time: Local::now(),
-                                };
-                                // 推送接收到的消息保存到数据库
-                                let _ = save_conversation(
-                                    self.conversation_id.clone(),
-                                    self.title.clone(),
-                                    chat_message.clone(),
-                                );
-                                self.chat_show.chat_history.push(chat_message);
-                            }
-                            // 接收响应消息失败，将响应状态位改为失败，并提供错误信息
-                            Err(e) => {
-                                if let Some(msg) = e.downcast_ref::<String>() {
-                                    self.response_status = ResponseStatus::Failed(msg.clone());
-                                } else {
-                                    self.response_status = ResponseStatus::Failed("Unknown Error".into());
-                                }
-                                // 将最后一条消息状态修改为失败
-                                let mut chat_message = self.chat_show.chat_history.pop().unwrap();
-                                chat_message.success = false;
-                                self.chat_show.chat_history.push(chat_message);
-                            }
-                        }
-                    }
-                }
-                self.receiving_message = false;
-            }
-            return;
+    /// 处理按键事件，在等待一段时间后没有按键事件则直接返回
+    fn handle_key(&mut self) -> Result<()> {
+        if !event::poll(POLL_INTERVAL)? {
+            return Ok(());
         }
-
         // 接收键盘事件
-        if let Ok(Event::Key(key)) = event::read() {
+        if let Event::Key(key) = event::read()? {
             if key.kind != KeyEventKind::Press {
-                return;
+                return Ok(());
+            }
+            // 如果正在接收消息，则 Esc 用于取消当前请求
+            if self.receiving_message && key.code == event::KeyCode::Esc {
+                self.cancel_request();
+                return Ok(());
+            }
+            // 如果工具调用确认弹窗处于显示状态，则必须先确认或拒绝执行
+            if let Some(ref mut popup) = self.tool_popup {
+                match popup.handle_key(key) {
+                    ToolPopupHandleEvent::Run => self.run_tools(true),
+                    ToolPopupHandleEvent::Decline => self.run_tools(false),
+                    ToolPopupHandleEvent::Nothing => {}
+                }
+                return Ok(());
             }
             // 如果正在编辑标题
             if self.title_editor_input_field.is_some() {
                 self.handle_title_edit_key_event(key);
-                return;
+                return Ok(());
+            }
+            // 如果搜索弹窗处于显示状态，则将按键事件视为弹窗的按键事件
+            if let Some(ref mut popup) = self.search_popup {
+                match popup.handle_key(key) {
+                    SearchPopupHandleEvent::Open(result) => {
+                        self.search_popup = None;
+                        self.open_search_result(result);
+                    }
+                    SearchPopupHandleEvent::Cancel => self.search_popup = None,
+                    SearchPopupHandleEvent::Nothing => {}
+                }
+                return Ok(());
+            }
+            // 打开搜索弹窗，图片路径输入弹窗显示时除外
+            let open_search = match key.code {
+                event::KeyCode::Char('f') => key.modifiers.contains(event::KeyModifiers::CONTROL),
+                event::KeyCode::F(9) => true,
+                _ => false,
+            };
+            if open_search && self.image_popup.is_none() {
+                self.search_popup = Some(SearchPopup::default());
+                return Ok(());
+            }
+            // 如果配置方案弹窗处于显示状态，则将按键事件视为弹窗的按键事件
+            if let Some(ref mut popup) = self.profile_popup {
+                match popup.handle_key(key) {
+                    ProfilePopupHandleEvent::Switch(name) => {
+                        self.profile_popup = None;
+                        self.change_profile(|config| config.switch_profile(&name));
+                    }
+                    ProfilePopupHandleEvent::Create(name) => {
+                        self.profile_popup = None;
+                        self.change_profile(|config| config.create_profile(&name));
+                    }
+                    ProfilePopupHandleEvent::Delete(name) => {
+                        match update_config(|config| config.delete_profile(&name)).and_then(|_| read_config()) {
+                            Ok(config) => popup.set_profiles(config.profile_names()),
+                            Err(e) => self.response_status = ResponseStatus::Failed(e.to_string()),
+                        }
+                    }
+                    ProfilePopupHandleEvent::Cancel => self.profile_popup = None,
+                    ProfilePopupHandleEvent::Nothing => {}
+                }
+                return Ok(());
+            }
+            // 打开配置方案弹窗，图片路径输入弹窗显示时除外
+            let open_profile = match key.code {
+                event::KeyCode::Char('p') => key.modifiers.contains(event::KeyModifiers::CONTROL),
+                event::KeyCode::F(2) => true,
+                _ => false,
+            };
+            if open_profile && self.image_popup.is_none() {
+                if let Ok(config) = read_config() {
+                    self.profile_popup = Some(ProfilePopup::new(config.profile_names(), config.profile));
+                }
+                return Ok(());
+            }
+            // 打开当前会话的设置窗口，图片路径输入弹窗显示时除外
+            let open_conversation_setting = match key.code {
+                event::KeyCode::Char('g') => key.modifiers.contains(event::KeyModifiers::CONTROL),
+                event::KeyCode::F(11) => true,
+                _ => false,
+            };
+            if open_conversation_setting && self.image_popup.is_none() && self.key_prompt.is_none() {
+                self.open_conversation_setting();
+                return Ok(());
             }
 
             match self.focus_component {
                 // 当聚焦于输入框时，处理输入
-                MainFocusComponent::InputField => self.handle_input_key_event(key, chat_tx),
+                MainFocusComponent::InputField => self.handle_input_key_event(key),
                 // 当聚焦于新建聊天按钮时，处理输入
                 MainFocusComponent::NewChatButton => self.handle_new_chat_key_event(key),
                 // 当聚焦于聊天列表时，处理输入
@@ -618,6 +890,402 @@ impl UI {
                 MainFocusComponent::ChatShow => self.handle_chat_show_key_event(key),
             }
         }
+        Ok(())
+    }
+
+    /// 处理后台请求任务返回的事件
+    fn handle_worker_events(&mut self) {
+        while let Some(event) = self.chat_worker.try_recv() {
+            match event {
+                WorkerEvent::ToolResults(responses) => self.show_tool_results(responses),
+                WorkerEvent::Context(window) => self.update_context_window(window),
+                WorkerEvent::Started(content) => self.receiving_reply.content = Some(content),
+                WorkerEvent::Chunk(text) => {
+                    self.receiving_reply.response.push_str(&text);
+                    let response = self.receiving_reply.text();
+                    if let Some(chat_message) = self.chat_show.chat_history.last_mut() {
+                        chat_message.message = response;
+                    }
+                }
+                WorkerEvent::Usage(usage) => self.receiving_reply.usage = Some(usage),
+                WorkerEvent::Finish(finish) => self.receiving_reply.finish = Some(finish),
+                WorkerEvent::FunctionCalls(calls) => self.receiving_reply.function_calls.extend(calls),
+                WorkerEvent::Done => self.finish_reply(None),
+                // 接收过程中出现错误，保留已接收到的部分内容
+                WorkerEvent::Failed(msg) => self.finish_reply(Some(msg)),
+            }
+        }
+    }
+
+    /// 取消正在进行的请求，已接收到的回复与被中断的回复一样保存，标记为已取消
+    fn cancel_request(&mut self) {
+        self.chat_worker.cancel();
+        self.receiving_reply.cancelled = true;
+        self.finish_reply(Some("Request Cancelled".into()));
+    }
+
+    /// 结束回复接收，保存完整、被中断或被取消的回复
+    fn finish_reply(&mut self, error: Option<String>) {
+        self.receiving_message = false;
+        let receiving_reply = std::mem::take(&mut self.receiving_reply);
+        // 重新生成、继续生成或被取消时不执行工具调用
+        let function_calls = if receiving_reply.regenerate || receiving_reply.cancelled {
+            Vec::new()
+        } else {
+            receiving_reply.function_calls.clone()
+        };
+        let received = !format_response(&receiving_reply.response).is_empty() || !function_calls.is_empty();
+        let response = receiving_reply.text();
+        let usage = receiving_reply.total_usage();
+        let mut bot_message = self.chat_show.chat_history.pop().unwrap();
+        // 没有接收到任何内容，视为发送失败，回复被拦截时提示拦截原因
+        let Some(content) = receiving_reply.content.filter(|_| received) else {
+            let msg = match (error, receiving_reply.finish) {
+                (Some(error), _) => error,
+                (None, _) if !receiving_reply.function_calls.is_empty() => {
+                    "Tool Calls Unsupported When Regenerating".into()
+                }
+                (None, Some(finish)) if !finish.is_normal() => format!("Response Blocked: {}", finish),
+                (None, _) => "Empty Response".into(),
+            };
+            self.response_status = ResponseStatus::Failed(msg);
+            if receiving_reply.regenerate {
+                // 重新生成失败时，恢复原有的回复
+                restore_version(&mut bot_message);
+                self.chat_show.chat_history.push(bot_message);
+            } else if !self.settle_tool_results(receiving_reply.tools_run, receiving_reply.cancelled) {
+                if let Some(chat_message) = self.chat_show.chat_history.last_mut() {
+                    chat_message.success = false;
+                    if receiving_reply.cancelled {
+                        chat_message.state = MessageState::Cancelled;
+                    }
+                }
+            }
+            return;
+        };
+        // 被中断的回复标记为部分内容，被取消的回复标记为已取消
+        let state = if let Some(msg) = error {
+            self.response_status = ResponseStatus::Failed(msg);
+            if receiving_reply.cancelled {
+                MessageState::Cancelled
+            } else {
+                MessageState::Partial
+            }
+        } else {
+            MessageState::Complete
+        };
+        bot_message.message = response.clone();
+        bot_message.state = state.clone();
+        bot_message.usage = usage;
+        bot_message.finish = receiving_reply.finish.clone();
+        // 记录回复生成时使用的回复格式约束，之后只按该约束校验
+        bot_message.response_schema = self.request_options.response_schema.clone();
+        // 记录回复生成时使用的模型，费用按该模型的价格估算
+        bot_message.model = self.gemini.as_ref().map(|gemini| gemini.model.to_string());
+        if receiving_reply.regenerate {
+            // 将重新生成或继续生成的回复保存为新的版本，并设为当前版本
+            bot_message.versions.push(MessageVersion {
+                message: response.clone(),
+                date_time: bot_message.date_time,
+                state,
+                usage,
+                finish: receiving_reply.finish,
+                response_schema: bot_message.response_schema.clone(),
+                model: bot_message.model.clone(),
+            });
+            bot_message.version_index = bot_message.versions.len() - 1;
+            let _ = conversation_store().save_message_version(&self.conversation_id, bot_message.clone());
+            self.chat_show.chat_history.push(bot_message);
+            self.validate_replies();
+            // 使用新的回复替换历史记录中的旧回复
+            if let Some(gemini) = self.gemini.as_mut() {
+                gemini.contents.pop();
+                gemini.contents.push(model_content(response));
+            }
+            return;
+        }
+        // 如果 id 为空，则生成唯一 id
+        let new_conversation = self.conversation_id.is_empty();
+        if new_conversation {
+            self.conversation_id = generate_unique_id();
+            // 由于是新建会话，若想保持聊天列表选中状态，则需要将选中项加一
+            self.chat_item_list.selected_conversation += 1;
+        }
+        // 模型请求调用工具时，回复展示为工具调用消息
+        if !function_calls.is_empty() {
+            bot_message.message = describe_calls(&response, &function_calls);
+            bot_message.sender = ToolCall(function_calls.clone());
+        }
+        // 如果标题为空，则总结标题，工具调用消息没有可总结的回复
+        if self.title.is_empty() && !self.gen_title_ing && function_calls.is_empty() {
+            self.gen_title_ing = true;
+            let key = self.gemini.clone().unwrap().key.clone();
+            let response = response.clone();
+            let title_tx = self.title_tx.clone();
+            let conversation_id = self.conversation_id.clone();
+            // 总结标题
+            thread::spawn(move || {
+                let title = summary_by_gemini(key, response);
+                if let Some(title_tx) = title_tx {
+                    let _ = title_tx.send((conversation_id, title));
+                }
+            });
+        }
+        // 用户消息的父记录为当前分支上前一条已保存的消息
+        let history_len = self.chat_show.chat_history.len();
+        let parent_record_id = self.chat_show.chat_history[..history_len.saturating_sub(1)]
+            .iter()
+            .rev()
+            .find(|chat_message| !chat_message.record_id.is_empty())
+            .map(|chat_message| chat_message.record_id.clone());
+        // 用户发送的消息与接收到的回复一并保存到数据库
+        if let Some(chat_message) = self.chat_show.chat_history.last_mut() {
+            if let Ok((user_record_id, bot_record_id)) = conversation_store().save_exchange(
+                &self.conversation_id,
+                &self.title,
+                parent_record_id,
+                chat_message.clone(),
+                bot_message.clone(),
+            ) {
+                chat_message.record_id = user_record_id.clone();
+                chat_message.sibling_ids = vec![user_record_id.clone()];
+                // 图片保存到缓存后才能展示预览
+                chat_message.image_record_ids = conversation_store()
+                    .query_image_record_ids(&user_record_id)
+                    .unwrap_or_default();
+                bot_message.record_id = bot_record_id.clone();
+                bot_message.sibling_ids = vec![bot_record_id];
+            }
+        }
+        // 新建的会话保存其使用的模型、系统指令与生成配置
+        if new_conversation {
+            self.save_conversation_settings();
+        }
+        self.chat_show.chat_history.push(bot_message);
+        self.validate_replies();
+        // 将本轮对话加入到历史记录中
+        if let Some(gemini) = self.gemini.as_mut() {
+            gemini.contents.push(content);
+            if function_calls.is_empty() {
+                gemini.contents.push(model_content(response));
+            } else {
+                gemini.contents.push(function_call_content(&function_calls));
+            }
+        }
+        // 编辑后的消息成为新的分支，重新加载会话以更新分支信息
+        if receiving_reply.edited {
+            self.reload_conversation();
+            self.mark_out_of_context();
+        }
+        self.show_tool_popup();
+    }
+
+    /// 标记不符合生成时回复格式约束的回复，没有约束或未完整接收的回复不做校验
+    fn validate_replies(&mut self) {
+        for chat_message in self.chat_show.chat_history.iter_mut() {
+            chat_message.schema_mismatch =
+                match (&chat_message.response_schema, &chat_message.sender, &chat_message.state) {
+                    (Some(schema), Bot, MessageState::Complete) => schema_mismatch(&chat_message.message, schema),
+                    _ => None,
+                };
+        }
+    }
+
+    /// 最后一条消息为尚未执行的工具调用时，显示确认弹窗
+    fn show_tool_popup(&mut self) {
+        self.tool_popup = match self.chat_show.chat_history.last() {
+            Some(ChatMessage {
+                sender: ToolCall(calls),
+                success: true,
+                ..
+            }) => Some(ToolPopup::new(calls.clone())),
+            _ => None,
+        };
+    }
+
+    /// 执行或拒绝最后一条消息中的工具调用，执行结果作为新的消息自动发送给模型
+    fn run_tools(&mut self, approved: bool) {
+        self.tool_popup = None;
+        let Some(ChatMessage {
+            sender: ToolCall(calls),
+            ..
+        }) = self.chat_show.chat_history.last()
+        else {
+            return;
+        };
+        let calls = calls.clone();
+        let Some(gemini) = self.gemini.clone() else {
+            return;
+        };
+        let context = self.context_options(gemini.contents.len());
+        // 工具执行完成前展示占位内容，执行结果由后台任务返回
+        self.chat_show.chat_history.push(ChatMessage {
+            success: true,
+            sender: ToolResult(Vec::new()),
+            message: if approved { "Running tools..." } else { "Declined" }.into(),
+            date_time: Local::now(),
+            ..Default::default()
+        });
+        self.chat_show.chat_history.push(ChatMessage {
+            success: true,
+            sender: Bot,
+            date_time: Local::now(),
+            state: MessageState::Receiving,
+            ..Default::default()
+        });
+        self.receiving_message = true;
+        self.receiving_reply = ReceivingReply {
+            tools_run: approved,
+            ..Default::default()
+        };
+        self.chat_worker
+            .run_tools(gemini, calls, approved, context, self.request_options.clone());
+        self.chat_show.auto_scroll = true;
+    }
+
+    /// 展示工具执行结果，执行结果消息为正在接收的回复的前一条消息
+    fn show_tool_results(&mut self, responses: Vec<FunctionResponse>) {
+        let index = self.chat_show.chat_history.len().saturating_sub(2);
+        if let Some(chat_message) = self.chat_show.chat_history.get_mut(index) {
+            if matches!(chat_message.sender, ToolResult(_)) {
+                chat_message.message = describe_responses(&responses);
+                chat_message.sender = ToolResult(responses);
+            }
+        }
+    }
+
+    /// 工具执行结果发送失败或被取消且没有收到回复时，返回最后一条消息是否为工具执行结果
+    ///
+    /// 拒绝执行时移除执行结果并重新显示确认弹窗；工具已经执行时保留并保存执行结果，避免重复执行
+    fn settle_tool_results(&mut self, tools_run: bool, cancelled: bool) -> bool {
+        let Some(ChatMessage {
+            sender: ToolResult(responses),
+            ..
+        }) = self.chat_show.chat_history.last()
+        else {
+            return false;
+        };
+        if !tools_run {
+            self.chat_show.chat_history.pop();
+            self.show_tool_popup();
+            return true;
+        }
+        let history_len = self.chat_show.chat_history.len();
+        let Some(ChatMessage {
+            sender: ToolCall(calls),
+            record_id: parent_record_id,
+            ..
+        }) = self.chat_show.chat_history.get(history_len.saturating_sub(2))
+        else {
+            return true;
+        };
+        // 执行过程中被取消时，执行结果未知
+        let responses = if responses.is_empty() {
+            calls.iter().map(interrupted).collect()
+        } else {
+            responses.clone()
+        };
+        let parent_record_id = Some(parent_record_id.clone()).filter(|record_id| !record_id.is_empty());
+        let mut result_message = self.chat_show.chat_history.pop().unwrap();
+        result_message.message = describe_responses(&responses);
+        result_message.sender = ToolResult(responses.clone());
+        if cancelled {
+            result_message.state = MessageState::Cancelled;
+        }
+        if let Ok(record_id) = conversation_store().save_message(
+            &self.conversation_id,
+            &self.title,
+            parent_record_id,
+            result_message.clone(),
+        ) {
+            result_message.sibling_ids = vec![record_id.clone()];
+            result_message.record_id = record_id;
+        }
+        self.chat_show.chat_history.push(result_message);
+        // 执行结果加入到历史记录中，之后发送的消息会一并发送给模型
+        if let Some(gemini) = self.gemini.as_mut() {
+            gemini.contents.push(function_response_content(&responses));
+        }
+        true
+    }
+
+    /// 发送请求时的上下文设置，`history_len` 为随请求发送的历史记录数量
+    fn context_options(&self, history_len: usize) -> ContextOptions {
+        ContextOptions {
+            config: self.context.clone(),
+            // 只有发送成功的消息才会被加入到历史记录中
+            pinned: self
+                .chat_show
+                .chat_history
+                .iter()
+                .filter(|chat_message| chat_message.success)
+                .take(history_len)
+                .map(|chat_message| chat_message.pinned)
+                .collect(),
+            summary: self.context_window.summary.clone(),
+        }
+    }
+
+    /// 记录本次请求使用的上下文，并标记已被移出上下文的消息
+    fn update_context_window(&mut self, window: ContextWindow) {
+        // 移出的消息数量变化时提示
+        if !window.dropped.is_empty() && window.dropped.len() != self.context_window.dropped.len() {
+            let notice = if window.summary.is_some() {
+                format!(
+                    "{} earlier messages summarized to fit the context budget",
+                    window.dropped.len()
+                )
+            } else {
+                format!(
+                    "{} earlier messages dropped to fit the context budget",
+                    window.dropped.len()
+                )
+            };
+            self.chat_show.show_notice(notice);
+        }
+        self.context_window = window;
+        self.mark_out_of_context();
+    }
+
+    /// 根据上一次请求使用的上下文标记已被移出上下文的消息
+    fn mark_out_of_context(&mut self) {
+        for (index, chat_message) in self
+            .chat_show
+            .chat_history
+            .iter_mut()
+            .filter(|chat_message| chat_message.success)
+            .enumerate()
+        {
+            chat_message.out_of_context = self.context_window.dropped.contains(&index);
+        }
+    }
+
+    /// 固定或取消固定选中的消息，没有选中的消息时为最后一条，固定的消息所在的一轮对话不会被移出上下文
+    fn toggle_pinned(&mut self) {
+        let index = self
+            .chat_show
+            .selected_message
+            .or(self.chat_show.chat_history.len().checked_sub(1));
+        let Some(chat_message) = index.and_then(|index| self.chat_show.chat_history.get_mut(index)) else {
+            return;
+        };
+        // 消息保存后才能固定
+        if chat_message.record_id.is_empty() {
+            return;
+        }
+        let pinned = !chat_message.pinned;
+        let notice = match conversation_store().set_pinned(&chat_message.record_id, pinned) {
+            Ok(_) => {
+                chat_message.pinned = pinned;
+                if pinned {
+                    "Pinned message".into()
+                } else {
+                    "Unpinned message".into()
+                }
+            }
+            Err(e) => format!("Pin failed: {}", e),
+        };
+        self.chat_show.show_notice(notice);
     }
 
     /// 处理标题编辑事件
@@ -639,25 +1307,25 @@ impl UI {
     }
 
     /// 当聚焦于输入框时，处理输入
-    fn handle_input_key_event(&mut self, key: event::KeyEvent, tx: mpsc::Sender<ChatType>) {
-        // 如果输入图片路径的弹窗处于显示状态，则将按键事件视为弹窗的按键事件
-        if let Some(ref mut popup) = self.image_url_input_popup {
+    fn handle_input_key_event(&mut self, key: event::KeyEvent) {
+        // 如果图片列表弹窗处于显示状态，则将按键事件视为弹窗的按键事件
+        if let Some(ref mut popup) = self.image_popup {
             // 处理弹窗事件，如果存在返回值，
             match popup.handle_key(key) {
-                component::popup::input_popup::InputPopupHandleEvent::Save(res) => {
-                    self.image_url_input_popup = None;
-                    self.image_path = Some(res);
+                ImagePopupHandleEvent::Save(image_paths) => {
+                    self.image_popup = None;
+                    self.image_paths = image_paths;
                 }
-                component::popup::input_popup::InputPopupHandleEvent::Cancel => self.image_url_input_popup = None,
-                component::popup::input_popup::InputPopupHandleEvent::Nothing => {}
+                ImagePopupHandleEvent::Cancel => self.image_popup = None,
+                ImagePopupHandleEvent::Nothing => {}
             }
         } else {
-            self.handle_input_key_event_common(key, tx);
+            self.handle_input_key_event_common(key);
         }
     }
 
-    // 当不处于图片路径输入弹窗状态时，处理输入
-    fn handle_input_key_event_common(&mut self, key: event::KeyEvent, tx: mpsc::Sender<ChatType>) {
+    // 当不处于图片列表弹窗状态时，处理输入
+    fn handle_input_key_event_common(&mut self, key: event::KeyEvent) {
         // 如果是除 Tab 键外其他任意按键事件，则清空错误提示消息
         if key.code != event::KeyCode::Tab && !matches!(self.response_status, ResponseStatus::None) {
             self.response_status = ResponseStatus::None;
@@ -675,10 +1343,12 @@ impl UI {
                 self.make_title_editable()
             }
             event::KeyCode::F(1) => self.make_title_editable(),
+            // 正在编辑消息时，Esc 用于取消编辑
+            event::KeyCode::Esc if self.editing_message.is_some() => self.cancel_edit(),
             event::KeyCode::Esc => self.should_exit = true,
             event::KeyCode::Tab => self.next_component(),
             event::KeyCode::Backspace => self.input_field_component.delete_pre_char(),
-            event::KeyCode::Enter => self.submit_message(tx),
+            event::KeyCode::Enter => self.submit_message(),
             event::KeyCode::Left => self
                 .input_field_component
                 .move_cursor_left(self.input_field_component.get_current_char()),
@@ -709,7 +1379,7 @@ impl UI {
             self.title = content.clone();
             // 如果是已有的会话，则修改标题
             if !self.conversation_id.is_empty() {
-                let _ = modify_title(self.conversation_id.clone(), content);
+                let _ = conversation_store().rename(&self.conversation_id, &content);
             }
             self.title_editor_input_field = None;
         }
@@ -732,22 +1402,48 @@ impl UI {
     /// 创建一个新的对话
     fn new_conversation(&mut self) {
         self.receiving_message = false;
+        self.chat_worker.cancel();
+        self.receiving_reply = ReceivingReply::default();
         self.response_status = ResponseStatus::None;
-        if let Some(gemini) = self.gemini.clone() {
-            let mut gemini_new = Gemini::rebuild(gemini.key, gemini.model, Vec::new(), gemini.options);
-            gemini_new.set_system_instruction(gemini.system_instruction.unwrap_or("".into()));
-            self.gemini = Some(gemini_new);
-        };
+        // 新的会话使用当前配置方案
+        let profile = read_config()
+            .ok()
+            .map(|config| config.current_profile())
+            .or(self.conversation_settings());
+        if let Some(profile) = profile {
+            self.rebuild_gemini(profile, Vec::new());
+        }
         self.focus_component = MainFocusComponent::InputField;
         self.input_field_component.clear();
-        self.image_path = None;
+        self.image_paths.clear();
+        self.editing_message = None;
         self.title = "".into();
         self.conversation_id = "".into();
-        self.chat_show = ChatShowScrollProps::default();
+        self.context_window = ContextWindow::default();
+        // 保留回复的展示方式
+        self.chat_show = ChatShowScrollProps {
+            raw_markdown: self.chat_show.raw_markdown,
+            ..Default::default()
+        };
     }
 
     /// 当聚焦于聊天列表时，处理输入
     fn handle_chat_list_key_event(&mut self, key: event::KeyEvent) {
+        // 如果此时有导出弹窗，则处理弹窗
+        if let Some(ref mut popup) = self.chat_item_list.popup_export_dialog {
+            match key.code {
+                event::KeyCode::Esc => self.chat_item_list.popup_export_dialog = None,
+                event::KeyCode::Tab | event::KeyCode::Right => popup.next_button(),
+                event::KeyCode::Left => popup.prev_button(),
+                event::KeyCode::Enter => {
+                    let format = popup.selected_format;
+                    self.chat_item_list.popup_export_dialog = None;
+                    self.export_conversation(format);
+                }
+                _ => {}
+            }
+            return;
+        }
         match key.code {
             event::KeyCode::Esc => self.should_exit = true,
             event::KeyCode::Char('s') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
@@ -771,51 +1467,7 @@ impl UI {
                 }
                 // 否则加载对应选中项的聊天内容列表
                 if let Some(conversation) = self.chat_item_list.rebuild() {
-                    self.conversation_id = conversation.conversation_id;
-                    self.title = conversation.conversation_title;
-                    let contents: Vec<Content> = conversation
-                        .conversation_records
-                        .clone()
-                        .iter()
-                        .map(|record| {
-                            let role = match record.record_sender {
-                                User(_) => Some(Role::User),
-                                Bot => Some(Role::Model),
-                                Never => None,
-                            };
-                            let mut parts = Vec::new();
-                            parts.push(Part::Text(record.record_content.clone()));
-                            // 如果包含了图片数据，则添加到 parts 中
-                            if let Some(image_record) = record.image_record.clone() {
-                                let image_record_id = image_record.image_record_id;
-                                // 读取图片缓存数据
-                                Self::read_image_data(image_record_id, image_record.image_path, &mut parts);
-                            }
-                            Content { parts, role }
-                        })
-                        .collect();
-                    // 重新加载 gemini 客户端
-                    if let Some(gemini) = self.gemini.clone() {
-                        let mut gemini_new = Gemini::rebuild(gemini.key, gemini.model, contents, gemini.options);
-                        gemini_new.set_system_instruction(gemini.system_instruction.unwrap_or("".into()));
-                        self.gemini = Some(gemini_new);
-                    }
-                    // 加载聊天记录
-                    let chat_history: Vec<ChatMessage> = conversation
-                        .conversation_records
-                        .clone()
-                        .iter()
-                        .map(|record| ChatMessage {
-                            success: true,
-                            message: record.record_content.clone(),
-                            sender: record.record_sender.clone(),
-                            date_time: record.record_time,
-                        })
-                        .collect();
-                    self.chat_show.chat_history = chat_history;
-                    self.focus_component = MainFocusComponent::ChatShow;
-                    self.input_field_component.clear();
-                    self.image_path = None;
+                    self.open_conversation(conversation, None);
                 }
             }
             event::KeyCode::Up => self.chat_item_list.prev_item(),
@@ -824,6 +1476,10 @@ impl UI {
                 // 弹窗提示
                 self.chat_item_list.popup_delete_confirm_dialog = Some(DeletePopup::default());
             }
+            event::KeyCode::Char('x') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                self.show_export_popup()
+            }
+            event::KeyCode::F(10) => self.show_export_popup(),
             event::KeyCode::Tab => {
                 // 如果此时有确认删除的弹窗，则处理弹窗
                 if let Some(ref mut popup) = self.chat_item_list.popup_delete_confirm_dialog {
@@ -836,22 +1492,184 @@ impl UI {
         };
     }
 
-    /// 读取图片数据
-    fn read_image_data(image_record_id: String, image_path: String, parts: &mut Vec<Part>) {
-        // 读取图片缓存数据
-        if let Ok((image_type, image_data)) = read_image_cache(image_record_id.clone()) {
-            parts.push(Part::InlineData {
-                mime_type: image_type,
-                data: image_data,
-            });
-            // 如果读不到缓存的数据，则重新加载图片数据
-        } else if let Ok((image_type, image_data)) = get_image_type_and_base64_string(image_path.clone()) {
-            // 将图片数据缓存到本地
-            let _ = cache_image(image_path, image_record_id);
-            parts.push(Part::InlineData {
-                mime_type: image_type,
-                data: image_data,
-            });
+    /// 弹出导出格式选择弹窗，删除弹窗显示时除外
+    fn show_export_popup(&mut self) {
+        if self.chat_item_list.popup_delete_confirm_dialog.is_none() && !self.chat_item_list.chat_history.is_empty() {
+            self.chat_item_list.popup_export_dialog = Some(ExportPopup::default());
+        }
+    }
+
+    /// 导出聊天列表中选中的会话，并提示导出文件的路径
+    fn export_conversation(&mut self, format: ExportFormat) {
+        let Some(conversation) = self.chat_item_list.rebuild() else {
+            return;
+        };
+        let notice = match load_for_export(&conversation.conversation_id, format)
+            .and_then(|conversation| export_to_file(&conversation, format, None))
+        {
+            Ok(path) => format!("Exported to {}", path.display()),
+            Err(e) => format!("Export failed: {}", e),
+        };
+        self.chat_show.show_notice(notice);
+    }
+
+    /// 加载会话当前分支上的聊天记录，并重新加载 gemini 客户端的历史记录
+    fn load_conversation(&mut self, conversation: Conversation) {
+        // 旧版本创建的会话没有保存配置，使用当前配置方案
+        let profile = conversation_profile(&conversation)
+            .or_else(|| read_config().ok().map(|config| config.current_profile()))
+            .or(self.conversation_settings());
+        // 切换到其它会话时，上一次请求使用的上下文不再有效
+        if self.conversation_id != conversation.conversation_id {
+            self.context_window = ContextWindow::default();
+        }
+        self.conversation_id = conversation.conversation_id;
+        self.title = conversation.conversation_title;
+        let contents: Vec<Content> = conversation
+            .conversation_records
+            .clone()
+            .iter()
+            .map(|record| {
+                let role = match &record.record_sender {
+                    User(_) => Some(Role::User),
+                    Bot => Some(Role::Model),
+                    // 工具调用与执行结果根据保存的结构化数据还原
+                    ToolCall(calls) => return function_call_content(calls),
+                    ToolResult(responses) => return function_response_content(responses),
+                    Never => None,
+                };
+                let mut parts = Vec::new();
+                parts.push(Part::Text(record.record_content.clone()));
+                // 如果包含了附件，则逐一读取缓存数据并添加到 parts 中
+                for image_record in record.image_records.iter() {
+                    if let Ok(part) = rebuild_attachment(image_record) {
+                        parts.push(part);
+                    }
+                }
+                Content { parts, role }
+            })
+            .collect();
+        // 使用会话保存的配置重新加载 gemini 客户端
+        if let Some(profile) = profile {
+            self.rebuild_gemini(profile, contents);
+        }
+        // 加载聊天记录
+        let chat_history: Vec<ChatMessage> = conversation
+            .conversation_records
+            .clone()
+            .iter()
+            .map(|record| ChatMessage {
+                success: true,
+                message: record.record_content.clone(),
+                sender: record.record_sender.clone(),
+                date_time: record.record_time,
+                state: record.record_state.clone(),
+                versions: record
+                    .record_versions
+                    .iter()
+                    .map(|version| MessageVersion {
+                        message: version.version_content.clone(),
+                        date_time: version.version_time,
+                        state: version.version_state.clone(),
+                        usage: version.token_usage,
+                        finish: version.finish_info.clone(),
+                        response_schema: version.response_schema.clone(),
+                        model: version.model.clone(),
+                    })
+                    .collect(),
+                version_index: record
+                    .record_versions
+                    .iter()
+                    .position(|version| version.selected)
+                    .unwrap_or_default(),
+                record_id: record.record_id.clone(),
+                sibling_ids: record.sibling_record_ids.clone(),
+                image_record_ids: record
+                    .image_records
+                    .iter()
+                    .filter(|image_record| image_record.image_type.starts_with("image/"))
+                    .map(|image_record| image_record.image_record_id.clone())
+                    .collect(),
+                usage: record.token_usage,
+                finish: record.finish_info.clone(),
+                response_schema: record.response_schema.clone(),
+                model: record.model.clone(),
+                pinned: record.pinned,
+                ..Default::default()
+            })
+            .collect();
+        // 选中的消息可能已不在当前分支上
+        if self
+            .chat_show
+            .selected_message
+            .is_some_and(|index| index >= chat_history.len())
+        {
+            self.chat_show.selected_message = None;
+        }
+        self.chat_show.chat_history = chat_history;
+        self.validate_replies();
+        // 上一次退出时尚未确认的工具调用
+        self.show_tool_popup();
+    }
+
+    /// 打开一个会话，并选中指定的消息
+    fn open_conversation(&mut self, conversation: Conversation, record_id: Option<String>) {
+        // 切换会话前取消正在进行的请求
+        if self.receiving_message {
+            self.cancel_request();
+        }
+        self.load_conversation(conversation);
+        self.chat_show.auto_scroll = false;
+        self.chat_show.selected_message = record_id.and_then(|record_id| {
+            self.chat_show
+                .chat_history
+                .iter()
+                .position(|chat_message| chat_message.record_id == record_id)
+        });
+        self.chat_show.scroll_to_selected = self.chat_show.selected_message.is_some();
+        self.focus_component = MainFocusComponent::ChatShow;
+        self.input_field_component.clear();
+        self.image_paths.clear();
+        self.editing_message = None;
+    }
+
+    /// 打开搜索结果对应的会话，并滚动到命中的消息
+    fn open_search_result(&mut self, result: SearchResult) {
+        let Ok(mut conversation) = conversation_store().load(&result.conversation_id) else {
+            return;
+        };
+        // 命中的消息不在当前分支上时，切换到该消息所在的分支
+        if let Some(record_id) = result.record_id.clone() {
+            let in_branch = conversation
+                .conversation_records
+                .iter()
+                .any(|record| record.record_id == record_id);
+            if !in_branch
+                && conversation_store()
+                    .switch_branch(&result.conversation_id, &record_id)
+                    .is_ok()
+            {
+                if let Ok(switched) = conversation_store().load(&result.conversation_id) {
+                    conversation = switched;
+                }
+            }
+        }
+        // 同步聊天列表的选中项
+        if let Some(index) = self
+            .chat_item_list
+            .chat_history
+            .iter()
+            .position(|item| item.conversation.conversation_id == result.conversation_id)
+        {
+            self.chat_item_list.selected_conversation = index;
+        }
+        self.open_conversation(conversation, result.record_id);
+    }
+
+    /// 从数据库重新加载当前会话，用于编辑消息或切换分支后更新聊天记录
+    fn reload_conversation(&mut self) {
+        if let Ok(conversation) = conversation_store().load(&self.conversation_id) {
+            self.load_conversation(conversation);
         }
     }
 
@@ -882,8 +1700,30 @@ impl UI {
             event::KeyCode::F(1) => self.make_title_editable(),
             event::KeyCode::Esc => self.should_exit = true,
             event::KeyCode::Tab => self.next_component(),
+            event::KeyCode::Up if key.modifiers.contains(event::KeyModifiers::SHIFT) => self.select_message(false),
+            event::KeyCode::Down if key.modifiers.contains(event::KeyModifiers::SHIFT) => self.select_message(true),
             event::KeyCode::Up => self.up(),
             event::KeyCode::Down => self.down(),
+            event::KeyCode::Char('r') if key.modifiers.contains(event::KeyModifiers::CONTROL) => self.regenerate(),
+            event::KeyCode::Char('u') if key.modifiers.contains(event::KeyModifiers::CONTROL) => self.continue_reply(),
+            event::KeyCode::F(5) if key.modifiers.contains(event::KeyModifiers::SHIFT) => self.continue_reply(),
+            event::KeyCode::F(5) => self.regenerate(),
+            event::KeyCode::Char('e') if key.modifiers.contains(event::KeyModifiers::CONTROL) => self.edit_message(),
+            event::KeyCode::F(6) => self.edit_message(),
+            event::KeyCode::Char('o') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                self.chat_show.raw_markdown = !self.chat_show.raw_markdown
+            }
+            event::KeyCode::F(7) => self.chat_show.raw_markdown = !self.chat_show.raw_markdown,
+            event::KeyCode::Char('y') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                self.copy_message(None)
+            }
+            event::KeyCode::F(8) => self.copy_message(None),
+            event::KeyCode::Char('k') if key.modifiers.contains(event::KeyModifiers::CONTROL) => self.toggle_pinned(),
+            event::KeyCode::F(12) => self.toggle_pinned(),
+            // 数字键复制对应序号的代码块
+            event::KeyCode::Char(c @ '1'..='9') => self.copy_message(c.to_digit(10).map(|n| n as usize)),
+            event::KeyCode::Left => self.switch_branch_or_version(false),
+            event::KeyCode::Right => self.switch_branch_or_version(true),
             _ => {}
         }
     }
@@ -924,6 +1764,7 @@ impl UI {
 
     /// 聊天区域向上滚动
     fn up(&mut self) {
+        self.chat_show.auto_scroll = false;
         self.chat_show.scroll_offset = self.chat_show.scroll_offset.saturating_sub(1);
     }
 
@@ -936,48 +1777,346 @@ impl UI {
             .min(self.max_scroll_offset());
     }
 
+    /// 重新生成最后一条回复
+    fn regenerate(&mut self) {
+        // 正在接收消息或会话尚未保存时不可重新生成
+        if self.receiving_message || self.conversation_id.is_empty() {
+            return;
+        }
+        let Some(mut gemini) = self.gemini.clone() else {
+            return;
+        };
+        // 随请求发送的历史记录不包含最后一轮对话
+        let context = self.context_options(gemini.contents.len().saturating_sub(2));
+        let Some(chat_message) = self.chat_show.chat_history.last_mut() else {
+            return;
+        };
+        if !matches!(chat_message.sender, Bot) {
+            return;
+        }
+        // 移除最后一轮对话，重新发送其中的用户消息
+        gemini.contents.pop();
+        let Some(content) = gemini.contents.pop() else {
+            return;
+        };
+        keep_first_version(chat_message);
+        chat_message.message = String::new();
+        chat_message.date_time = Local::now();
+        chat_message.state = MessageState::Receiving;
+        chat_message.usage = None;
+        chat_message.finish = None;
+        self.receiving_message = true;
+        self.receiving_reply = ReceivingReply {
+            regenerate: true,
+            ..Default::default()
+        };
+        self.chat_worker
+            .resend(gemini, content, context, self.request_options.clone());
+        self.chat_show.auto_scroll = true;
+    }
+
+    /// 继续生成因达到最大输出 token 数量而被截断的最后一条回复，新内容拼接在原有回复之后，作为新的版本保存
+    fn continue_reply(&mut self) {
+        // 正在接收消息或会话尚未保存时不可继续生成
+        if self.receiving_message || self.conversation_id.is_empty() {
+            return;
+        }
+        let Some(gemini) = self.gemini.clone() else {
+            return;
+        };
+        // 被截断的回复作为历史记录随请求发送
+        let context = self.context_options(gemini.contents.len());
+        let Some(chat_message) = self.chat_show.chat_history.last_mut() else {
+            return;
+        };
+        let truncated = chat_message.finish.as_ref().is_some_and(FinishInfo::is_truncated);
+        if !matches!(chat_message.sender, Bot) || !truncated {
+            return;
+        }
+        keep_first_version(chat_message);
+        self.receiving_reply = ReceivingReply {
+            regenerate: true,
+            prefix: chat_message.message.clone(),
+            prefix_usage: chat_message.usage,
+            ..Default::default()
+        };
+        chat_message.date_time = Local::now();
+        chat_message.state = MessageState::Receiving;
+        chat_message.usage = None;
+        chat_message.finish = None;
+        self.receiving_message = true;
+        self.chat_worker.send(
+            gemini,
+            CONTINUE_PROMPT.into(),
+            Vec::new(),
+            context,
+            self.request_options.clone(),
+        );
+        self.chat_show.auto_scroll = true;
+    }
+
+    /// 选中上一条或下一条消息，并滚动到该消息
+    fn select_message(&mut self, forward: bool) {
+        let len = self.chat_show.chat_history.len();
+        if len == 0 {
+            return;
+        }
+        let index = match self.chat_show.selected_message {
+            Some(index) if forward => (index + 1).min(len - 1),
+            Some(index) => index.saturating_sub(1),
+            // 没有选中的消息时，从最后一条消息开始选择
+            None => len - 1,
+        };
+        self.chat_show.selected_message = Some(index);
+        self.chat_show.scroll_to_selected = true;
+    }
+
+    /// 复制选中的消息，没有选中的消息时复制最后一条消息，指定序号时只复制消息中对应的代码块
+    fn copy_message(&mut self, code_block: Option<usize>) {
+        let index = self
+            .chat_show
+            .selected_message
+            .or(self.chat_show.chat_history.len().checked_sub(1));
+        let Some(chat_message) = index.and_then(|index| self.chat_show.chat_history.get(index)) else {
+            return;
+        };
+        let (text, notice) = match code_block {
+            Some(n) => match code_blocks(&chat_message.message).get(n - 1) {
+                Some(code) => (code.clone(), format!("Copied code block {}", n)),
+                None => {
+                    self.chat_show.show_notice(format!("Code block {} not found", n));
+                    return;
+                }
+            },
+            None => (chat_message.message.clone(), "Copied message".into()),
+        };
+        let notice = match copy_to_clipboard(&text) {
+            Ok(_) => notice,
+            Err(e) => format!("Copy failed: {}", e),
+        };
+        self.chat_show.show_notice(notice);
+    }
+
+    /// 编辑选中的用户消息，将消息内容填入输入框，发送后将从该消息处创建新的分支
+    fn edit_message(&mut self) {
+        if self.receiving_message {
+            return;
+        }
+        let Some(index) = self.chat_show.selected_message else {
+            return;
+        };
+        let Some(chat_message) = self.chat_show.chat_history.get(index) else {
+            return;
+        };
+        let User(image_paths) = chat_message.sender.clone() else {
+            return;
+        };
+        self.input_field_component = TextField::new(chat_message.message.clone());
+        self.input_field_component.end_of_cursor();
+        self.image_paths = image_paths;
+        self.editing_message = Some(index);
+        self.focus_component = MainFocusComponent::InputField;
+    }
+
+    /// 取消编辑消息
+    fn cancel_edit(&mut self) {
+        self.editing_message = None;
+        self.input_field_component.clear();
+        self.image_paths.clear();
+    }
+
+    /// 选中的消息为存在多个分支的用户消息时切换分支，否则切换最后一条回复的版本
+    fn switch_branch_or_version(&mut self, forward: bool) {
+        if self.receiving_message {
+            return;
+        }
+        let chat_message = self
+            .chat_show
+            .selected_message
+            .and_then(|index| self.chat_show.chat_history.get(index));
+        match chat_message {
+            Some(chat_message) if matches!(chat_message.sender, User(_)) && chat_message.sibling_ids.len() > 1 => {
+                let sibling_ids = chat_message.sibling_ids.clone();
+                let index = sibling_ids
+                    .iter()
+                    .position(|id| *id == chat_message.record_id)
+                    .unwrap_or_default();
+                let index = if forward {
+                    (index + 1).min(sibling_ids.len() - 1)
+                } else {
+                    index.saturating_sub(1)
+                };
+                if sibling_ids[index] == chat_message.record_id {
+                    return;
+                }
+                if conversation_store()
+                    .switch_branch(&self.conversation_id, &sibling_ids[index])
+                    .is_ok()
+                {
+                    self.reload_conversation();
+                }
+            }
+            _ => self.switch_version(forward),
+        }
+    }
+
+    /// 切换最后一条回复展示的版本，选中的版本将作为下一轮对话的历史记录
+    fn switch_version(&mut self, forward: bool) {
+        if self.receiving_message {
+            return;
+        }
+        let Some(chat_message) = self.chat_show.chat_history.last_mut() else {
+            return;
+        };
+        if !matches!(chat_message.sender, Bot) || chat_message.versions.len() < 2 {
+            return;
+        }
+        let index = if forward {
+            (chat_message.version_index + 1).min(chat_message.versions.len() - 1)
+        } else {
+            chat_message.version_index.saturating_sub(1)
+        };
+        if index == chat_message.version_index {
+            return;
+        }
+        chat_message.version_index = index;
+        restore_version(chat_message);
+        let _ = conversation_store().select_message_version(&chat_message.record_id, index);
+        if let Some(gemini) = self.gemini.as_mut() {
+            gemini.contents.pop();
+            gemini.contents.push(model_content(chat_message.message.clone()));
+        }
+        self.validate_replies();
+    }
+
     /// 提交消息
-    fn submit_message(&mut self, tx: mpsc::Sender<ChatType>) {
-        let image_path = self.image_path.clone().unwrap_or_default();
+    fn submit_message(&mut self) {
+        // 上一条消息的回复尚未接收完成
+        if self.receiving_message {
+            return;
+        }
+        let image_paths = self.image_paths.clone();
         if !self.input_field_component.get_content().is_empty() {
-            if self.gemini.is_none() {
-                // 传入 key 创建客户端
-                self.restore_or_new_gemini(Some(self.input_field_component.get_content()));
-            } else {
+            if let Some(key_prompt) = self.key_prompt.take() {
+                // 输入框中为密钥或口令
+                self.submit_key_prompt(key_prompt, self.input_field_component.get_content());
+            } else if let Some(mut gemini) = self.gemini.clone() {
+                // 编辑消息时，丢弃该消息及之后的聊天记录，从该消息处创建新的分支
+                let edited = if let Some(index) = self.editing_message.take() {
+                    self.chat_show.chat_history.truncate(index);
+                    // 只有发送成功的消息才会被加入到历史记录中
+                    let count = self
+                        .chat_show
+                        .chat_history
+                        .iter()
+                        .filter(|chat_message| chat_message.success)
+                        .count();
+                    gemini.contents.truncate(count);
+                    self.gemini = Some(gemini.clone());
+                    self.chat_show.selected_message = None;
+                    true
+                } else {
+                    false
+                };
+                let context = self.context_options(gemini.contents.len());
                 self.chat_show.chat_history.push(ChatMessage {
                     success: true,
-                    sender: User(image_path.clone()),
+                    sender: User(image_paths.clone()),
                     message: self.input_field_component.get_content(),
                     date_time: Local::now(),
+                    ..Default::default()
                 });
-                // 将获取消息标志位置真，发送消息给下一次循环使用
+                // 添加一条空的回复消息，用于逐段展示接收到的回复
+                self.chat_show.chat_history.push(ChatMessage {
+                    success: true,
+                    sender: Bot,
+                    date_time: Local::now(),
+                    state: MessageState::Receiving,
+                    ..Default::default()
+                });
+                // 将获取消息标志位置真，由后台任务发送消息
                 self.receiving_message = true;
-                if image_path.is_empty() {
-                    let _ = tx.send(ChatType::Simple {
-                        message: self.input_field_component.get_content(),
-                    });
-                } else {
-                    let _ = tx.send(ChatType::Image {
-                        message: self.input_field_component.get_content(),
-                        image_path,
-                    });
-                    self.image_path = None;
-                }
+                self.receiving_reply = ReceivingReply {
+                    edited,
+                    ..Default::default()
+                };
+                self.chat_worker.send(
+                    gemini,
+                    self.input_field_component.get_content(),
+                    image_paths,
+                    context,
+                    self.request_options.clone(),
+                );
+                self.image_paths.clear();
             }
             self.input_field_component.clear();
-            // 滚动到最新的一条消息
-            self.chat_show.scroll_offset = self.chat_show.chat_history_area_height;
+            // 跟随最新的一条消息滚动
+            self.chat_show.auto_scroll = true;
         }
     }
 }
 
-/// 通过纯净的 Gemini API 获取对话摘要
-fn summary_by_gemini(key: String, message: String) -> String {
-    let mut pure_gemini = Gemini::new(key, LanguageModel::Gemini1_5Flash);
-    pure_gemini.set_system_instruction("请给我概括一下这段文字内容，不包含任意标点符号，不大于15字。".into());
-    if let Ok((s, _)) = pure_gemini.send_simple_message(message) {
-        s
-    } else {
-        "".into()
+/// 首次使用时的默认配置
+fn default_store_data() -> StoreData {
+    StoreData {
+        profile: DEFAULT_PROFILE_NAME.into(),
+        model: LanguageModel::Gemini1_5Flash,
+        system_instruction: Some(String::new()),
+        options: GenerationConfig::default(),
+        ..Default::default()
     }
 }
+
+/// 会话保存的模型、系统指令与生成配置，旧版本创建的会话返回空
+fn conversation_profile(conversation: &Conversation) -> Option<Profile> {
+    let model = conversation.conversation_model.clone()?;
+    let (options, response_schema) = conversation
+        .conversation_options
+        .as_deref()
+        .map(Profile::parse_options)
+        .unwrap_or_default();
+    Some(Profile {
+        model: model.into(),
+        system_instruction: conversation.conversation_system_instruction.clone(),
+        options,
+        response_schema,
+        ..Default::default()
+    })
+}
+
+/// 保留原有的回复作为第一个版本，以便在重新生成或继续生成失败、取消时恢复
+fn keep_first_version(chat_message: &mut ChatMessage) {
+    if chat_message.versions.is_empty() {
+        chat_message.versions.push(MessageVersion {
+            message: chat_message.message.clone(),
+            date_time: chat_message.date_time,
+            state: chat_message.state.clone(),
+            usage: chat_message.usage,
+            finish: chat_message.finish.clone(),
+            response_schema: chat_message.response_schema.clone(),
+            model: chat_message.model.clone(),
+        });
+    }
+}
+
+/// 将回复内容恢复为当前选中的版本
+fn restore_version(chat_message: &mut ChatMessage) {
+    if let Some(version) = chat_message.versions.get(chat_message.version_index).cloned() {
+        chat_message.message = version.message;
+        chat_message.date_time = version.date_time;
+        chat_message.state = version.state;
+        chat_message.usage = version.usage;
+        chat_message.finish = version.finish;
+        chat_message.response_schema = version.response_schema;
+        chat_message.model = version.model;
+    }
+    // 只有一个版本时不展示版本切换
+    if chat_message.versions.len() == 1 {
+        chat_message.versions.clear();
+    }
+}
+
+/// 格式化回复内容，去除末尾换行符，保留空行以免破坏 Markdown 中的段落、表格与代码块
+fn format_response(response: &str) -> String {
+    response.trim_end_matches('\n').to_owned()
+}
@@ -1,3 +1,5 @@
+use std::str::FromStr;
+
 use ratatui::{
     crossterm::event::{self, Event, KeyEventKind, KeyModifiers},
     layout::{
@@ -10,10 +12,22 @@ use ratatui::{
 };
 use strum::{EnumCount, FromRepr};
 
-use crate::utils::store_utils::{read_config, save_config, StoreData};
+use crate::utils::char_utils::mask;
+use crate::utils::schema_utils::parse_schema;
+use crate::utils::store_utils::{
+    read_config, save_config, ContextStrategy, KeySource, Profile, SafetyThreshold, StoreData,
+};
 
 use crate::ui::component::input::{input_trait::InputTextComponent, text_area::TextArea, text_field::TextField};
 
+/// 上下文处理方式的可选值
+const CONTEXT_STRATEGIES: &str = "drop or summarize";
+/// 安全阈值的可选值
+const SAFETY_THRESHOLDS: &str = "default, off, block_none, only_high, medium_and_above or low_and_above";
+
+/// 密钥来源为文件时的前缀
+const KEY_FILE_PREFIX: &str = "file:";
+
 /// 窗口UI
 pub struct SettingUI {
     /// 选中的输入框
@@ -22,6 +36,16 @@ pub struct SettingUI {
     components: Vec<(Constraint, Vec<SettingComponent>)>,
     /// 修改后的配置数据
     data: StoreData,
+    /// 是否显示密钥明文
+    reveal_key: bool,
+    /// 保存配置失败的错误信息
+    error: Option<String>,
+    /// 需要设置口令才能保存的配置
+    pub unsaved: Option<StoreData>,
+    /// 是否为会话设置窗口
+    for_conversation: bool,
+    /// 会话设置窗口保存后的会话配置
+    pub conversation_profile: Option<Profile>,
     /// 是否需要更新配置标志位
     pub update: bool,
     /// 是否应该退出程序
@@ -41,101 +65,165 @@ pub struct SettingComponent {
 /// 组件标识符枚举
 #[derive(Clone, EnumCount, FromRepr, PartialEq, Eq)]
 pub enum InputIdentifier {
+    Profile,
     Model,
     Key,
+    KeyCommand,
     SystemInstruction,
+    ResponseSchema,
     ResponseMineType,
     MaxOutputTokens,
     Temperature,
     TopP,
     TopK,
+    ContextBudget,
+    ContextStrategy,
+    Harassment,
+    HateSpeech,
+    SexuallyExplicit,
+    DangerousContent,
 }
 
 impl SettingUI {
     /// 启动此窗口UI
     pub fn new() -> Self {
-        let data = read_config().unwrap_or_default();
-        Self {
+        let mut data = read_config().unwrap_or_default();
+        // 只解密保存的密钥，外部命令与文件中的密钥不在设置页面中显示
+        if matches!(data.key_source, KeySource::Encrypted(_)) {
+            let _ = data.resolve_key();
+        }
+        let key_command = match &data.key_source {
+            KeySource::Command { command } => command.clone(),
+            KeySource::File { path } => format!("{}{}", KEY_FILE_PREFIX, path),
+            _ => String::new(),
+        };
+        let mut setting_ui = Self {
             select_input_field: InputIdentifier::SystemInstruction,
             update: false,
+            reveal_key: false,
+            error: None,
+            unsaved: None,
+            conversation_profile: None,
+            for_conversation: false,
             data: data.clone(),
             should_exit: false,
-            components: vec![
-                (
-                    Length(3),
-                    vec![
-                        SettingComponent {
-                            identifier: InputIdentifier::Model,
-                            label: "model".into(),
-                            layout: Length(30),
-                            input_component: Box::new(TextField::new(data.model.to_string())),
-                        },
-                        SettingComponent {
-                            identifier: InputIdentifier::Key,
-                            label: "key".into(),
-                            layout: Fill(20),
-                            input_component: Box::new(TextField::new(data.key)),
-                        },
-                    ],
-                ),
-                (
-                    Min(10),
-                    vec![SettingComponent {
-                        identifier: InputIdentifier::SystemInstruction,
-                        label: "system instruction".into(),
-                        layout: Fill(1),
-                        input_component: Box::new(TextArea::new(data.system_instruction.unwrap_or("".into()))),
-                    }],
-                ),
-                (
-                    Length(3),
-                    vec![
-                        SettingComponent {
-                            identifier: InputIdentifier::ResponseMineType,
-                            label: "response mine type".into(),
-                            layout: Fill(1),
-                            input_component: Box::new(TextField::new(
-                                data.options.response_mime_type.unwrap_or("".into()),
-                            )),
-                        },
-                        SettingComponent {
-                            identifier: InputIdentifier::MaxOutputTokens,
-                            label: "max output tokens".into(),
-                            layout: Fill(1),
-                            input_component: Box::new(TextField::new(
-                                data.options.max_output_tokens.unwrap_or(0).to_string(),
-                            )),
-                        },
-                    ],
-                ),
-                (
-                    Length(3),
-                    vec![
-                        SettingComponent {
-                            identifier: InputIdentifier::Temperature,
-                            label: "temperature".into(),
-                            layout: Fill(1),
-                            input_component: Box::new(TextField::new(
-                                data.options.temperature.unwrap_or(0.0).to_string(),
-                            )),
-                        },
-                        SettingComponent {
-                            identifier: InputIdentifier::TopP,
-                            label: "top p".into(),
-                            layout: Min(5),
-                            input_component: Box::new(TextField::new(data.options.top_p.unwrap_or(0.0).to_string())),
-                        },
-                        SettingComponent {
-                            identifier: InputIdentifier::TopK,
-                            label: "top k".into(),
-                            layout: Min(5),
-                            input_component: Box::new(TextField::new(data.options.top_k.unwrap_or(0).to_string())),
-                        },
-                    ],
-                ),
+            components: vec![],
+        };
+        // 第一行为配置方案名称、模型与密钥，其余为当前配置方案的内容
+        setting_ui.components.push((
+            Length(3),
+            vec![
+                SettingComponent {
+                    identifier: InputIdentifier::Profile,
+                    label: "profile".into(),
+                    layout: Length(20),
+                    input_component: Box::new(TextField::new(data.profile.clone())),
+                },
+                SettingComponent {
+                    identifier: InputIdentifier::Model,
+                    label: "model".into(),
+                    layout: Length(30),
+                    input_component: Box::new(TextField::new(data.model.to_string())),
+                },
+                SettingComponent {
+                    identifier: InputIdentifier::Key,
+                    label: "key".into(),
+                    layout: Fill(20),
+                    input_component: Box::new(TextField::new(data.key.clone())),
+                },
+                SettingComponent {
+                    identifier: InputIdentifier::KeyCommand,
+                    label: "key command or file:PATH".into(),
+                    layout: Fill(20),
+                    input_component: Box::new(TextField::new(key_command)),
+                },
             ],
-        }
+        ));
+        setting_ui.components.extend(profile_components(data.clone()));
+        // 最后一行为上下文窗口设置，对所有配置方案生效
+        setting_ui.components.push((
+            Length(3),
+            vec![
+                SettingComponent {
+                    identifier: InputIdentifier::ContextBudget,
+                    label: "context budget (tokens, 0 = unlimited)".into(),
+                    layout: Fill(1),
+                    input_component: Box::new(TextField::new(data.context.budget.to_string())),
+                },
+                SettingComponent {
+                    identifier: InputIdentifier::ContextStrategy,
+                    label: "context strategy (drop/summarize)".into(),
+                    layout: Fill(1),
+                    input_component: Box::new(TextField::new(data.context.strategy.to_string())),
+                },
+            ],
+        ));
+        // 各类别的安全阈值，对所有配置方案生效
+        setting_ui.components.push((
+            Length(3),
+            vec![
+                SettingComponent {
+                    identifier: InputIdentifier::Harassment,
+                    label: "safety: harassment".into(),
+                    layout: Fill(1),
+                    input_component: Box::new(TextField::new(data.safety.harassment.to_string())),
+                },
+                SettingComponent {
+                    identifier: InputIdentifier::HateSpeech,
+                    label: "safety: hate speech".into(),
+                    layout: Fill(1),
+                    input_component: Box::new(TextField::new(data.safety.hate_speech.to_string())),
+                },
+                SettingComponent {
+                    identifier: InputIdentifier::SexuallyExplicit,
+                    label: "safety: sexually explicit".into(),
+                    layout: Fill(1),
+                    input_component: Box::new(TextField::new(data.safety.sexually_explicit.to_string())),
+                },
+                SettingComponent {
+                    identifier: InputIdentifier::DangerousContent,
+                    label: "safety: dangerous content".into(),
+                    layout: Fill(1),
+                    input_component: Box::new(TextField::new(data.safety.dangerous_content.to_string())),
+                },
+            ],
+        ));
+        setting_ui
+    }
+
+    /// 启动会话设置窗口，只修改当前会话使用的模型、系统指令与生成配置，不修改配置文件
+    pub fn new_for_conversation(profile: Profile) -> Self {
+        let data = StoreData {
+            model: profile.model,
+            system_instruction: profile.system_instruction,
+            options: profile.options,
+            response_schema: profile.response_schema,
+            ..Default::default()
+        };
+        let mut setting_ui = Self {
+            select_input_field: InputIdentifier::SystemInstruction,
+            update: false,
+            reveal_key: false,
+            error: None,
+            unsaved: None,
+            conversation_profile: None,
+            for_conversation: true,
+            data: data.clone(),
+            should_exit: false,
+            components: vec![(
+                Length(3),
+                vec![SettingComponent {
+                    identifier: InputIdentifier::Model,
+                    label: "model".into(),
+                    layout: Fill(1),
+                    input_component: Box::new(TextField::new(data.model.to_string())),
+                }],
+            )],
+        };
+        setting_ui.components.extend(profile_components(data));
+        setting_ui
     }
+
     /// 处理用户输入
     pub fn handle_key(&mut self) {
         if let Ok(Event::Key(key)) = event::read() {
@@ -149,6 +237,10 @@ impl SettingUI {
                 event::KeyCode::Tab => self.next_input_field(),
                 event::KeyCode::Char('s') if key.modifiers.contains(event::KeyModifiers::CONTROL) => self.save_config(),
                 event::KeyCode::F(2) => self.save_config(),
+                event::KeyCode::Char('r') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                    self.reveal_key = !self.reveal_key
+                }
+                event::KeyCode::F(3) => self.reveal_key = !self.reveal_key,
                 event::KeyCode::Esc => self.should_exit = true,
                 event::KeyCode::Backspace => component.input_component.delete_pre_char(),
                 event::KeyCode::Delete => component.input_component.delete_suf_char(),
@@ -192,24 +284,45 @@ impl SettingUI {
         None
     }
 
-    /// 切换到下一个输入组件
+    /// 切换到下一个输入组件，跳过当前窗口中没有的组件
     fn next_input_field(&mut self) {
-        let current = self.select_input_field.clone() as usize;
-        let next = (current + 1) % InputIdentifier::COUNT;
-        self.select_input_field = InputIdentifier::from_repr(next).unwrap();
+        let mut next = self.select_input_field.clone() as usize;
+        loop {
+            next = (next + 1) % InputIdentifier::COUNT;
+            self.select_input_field = InputIdentifier::from_repr(next).unwrap();
+            if self.get_current_input_field().is_some() {
+                break;
+            }
+        }
     }
 
     /// 保存当前配置并退出配置窗口
     fn save_config(&mut self) {
+        // 存在无效输入时提示错误，保留原有配置
+        for component in self.components.iter().flat_map(|(_, line)| line.iter()) {
+            if let Some(e) = input_error(&component.identifier, &component.input_component.get_content()) {
+                let name = component.label.split(" (").next().unwrap_or_default();
+                self.error = Some(format!("Invalid {}: {}", name, e));
+                return;
+            }
+        }
+        let mut key_command = String::new();
+        let mut profile = String::new();
         // 遍历所有组件，将其现在显示的值更新到配置中
         for (_, line) in self.components.iter() {
             for component in line.iter() {
                 match component.identifier {
+                    InputIdentifier::Profile => profile = component.input_component.get_content(),
                     InputIdentifier::Model => self.data.model = component.input_component.get_content().into(),
                     InputIdentifier::Key => self.data.key = component.input_component.get_content(),
+                    InputIdentifier::KeyCommand => key_command = component.input_component.get_content(),
                     InputIdentifier::SystemInstruction => {
                         self.data.system_instruction = Some(component.input_component.get_content())
                     }
+                    InputIdentifier::ResponseSchema => {
+                        self.data.response_schema =
+                            parse_schema(&component.input_component.get_content()).unwrap_or_default()
+                    }
                     InputIdentifier::ResponseMineType => {
                         self.data.options.response_mime_type = Some(component.input_component.get_content())
                     }
@@ -227,14 +340,80 @@ impl SettingUI {
                     InputIdentifier::TopK => {
                         self.data.options.top_k = Some(component.input_component.get_content().parse().unwrap_or(0))
                     }
+                    InputIdentifier::ContextBudget => {
+                        self.data.context.budget = component.input_component.get_content().parse().unwrap_or(0)
+                    }
+                    InputIdentifier::ContextStrategy => {
+                        self.data.context.strategy =
+                            parse_choice(&component.input_component.get_content()).unwrap_or_default()
+                    }
+                    InputIdentifier::Harassment => {
+                        self.data.safety.harassment =
+                            parse_choice(&component.input_component.get_content()).unwrap_or_default()
+                    }
+                    InputIdentifier::HateSpeech => {
+                        self.data.safety.hate_speech =
+                            parse_choice(&component.input_component.get_content()).unwrap_or_default()
+                    }
+                    InputIdentifier::SexuallyExplicit => {
+                        self.data.safety.sexually_explicit =
+                            parse_choice(&component.input_component.get_content()).unwrap_or_default()
+                    }
+                    InputIdentifier::DangerousContent => {
+                        self.data.safety.dangerous_content =
+                            parse_choice(&component.input_component.get_content()).unwrap_or_default()
+                    }
                 }
             }
         }
-        save_config(self.data.clone()).unwrap();
+        // 会话设置只修改当前会话，不保存到配置文件
+        if self.for_conversation {
+            self.conversation_profile = Some(self.data.current_profile());
+            self.update = true;
+            self.should_exit = true;
+            return;
+        }
+        // 修改名称时重命名当前配置方案
+        if let Err(e) = self.data.rename_profile(&profile) {
+            self.error = Some(e.to_string());
+            return;
+        }
+        self.update_key_source(key_command.trim());
+        if self.data.needs_passphrase() {
+            // 由主页面提示设置口令后保存
+            self.unsaved = Some(self.data.clone());
+        } else if let Err(e) = save_config(self.data.clone()) {
+            self.error = Some(e.to_string());
+            return;
+        }
         self.update = true;
         self.should_exit = true;
     }
 
+    /// 根据输入的外部命令或文件路径更新密钥来源
+    fn update_key_source(&mut self, key_command: &str) {
+        if !key_command.is_empty() {
+            // 密钥由外部命令或文件提供，不再保存密钥
+            self.data.key.clear();
+            self.data.key_source = match key_command.strip_prefix(KEY_FILE_PREFIX) {
+                Some(path) => KeySource::File {
+                    path: path.trim().into(),
+                },
+                None => KeySource::Command {
+                    command: key_command.into(),
+                },
+            };
+        } else if !self.data.key.is_empty() {
+            // 保存配置时使用口令加密
+            if !matches!(self.data.key_source, KeySource::Encrypted(_)) {
+                self.data.key_source = KeySource::None;
+            }
+        } else if !self.data.is_locked() {
+            // 清空密钥后从环境变量中读取密钥，无法解密时保留原有的密钥
+            self.data.key_source = KeySource::None;
+        }
+    }
+
     /// 绘制配置窗口UI
     pub fn draw(&mut self, frame: &mut Frame) {
         let area = frame.area();
@@ -252,9 +431,15 @@ impl SettingUI {
         let right_paragraph = Paragraph::new("SAVE(F2)").style(Color::Green).right_aligned();
         frame.render_widget(right_paragraph, right);
 
-        let center_paragraph = Paragraph::new("System Setting")
-            .style(Style::default().fg(Color::LightBlue))
-            .centered();
+        // 保存失败时显示错误信息
+        let center_paragraph = match &self.error {
+            Some(error) => Paragraph::new(error.as_str()).style(Style::default().fg(Color::Red)),
+            None if self.for_conversation => {
+                Paragraph::new("Conversation Setting").style(Style::default().fg(Color::LightBlue))
+            }
+            None => Paragraph::new("System Setting").style(Style::default().fg(Color::LightBlue)),
+        }
+        .centered();
         frame.render_widget(center_paragraph, center);
     }
 
@@ -273,18 +458,35 @@ impl SettingUI {
                 // 设置输入框宽度
                 let width = (input_area.width as usize).saturating_sub(2);
                 component.input_component.set_width_height(width, height);
+                // 输入内容无效时显示为红色，并在标题中提示错误
+                let input_error = input_error(&component.identifier, &component.input_component.get_content());
                 // 预设输入框边框颜色，当输入框被选中时显示为绿色，否则显示为白色
-                let block_style = if self.select_input_field == component.identifier {
+                let block_style = if input_error.is_some() {
+                    Style::default().fg(Color::Red)
+                } else if self.select_input_field == component.identifier {
                     Style::default().fg(Color::Green)
                 } else {
                     Style::default().fg(Color::White)
                 };
+                // 密钥默认不显示明文
+                let (label, text) = if component.identifier == InputIdentifier::Key {
+                    let text = component.input_component.should_show_text();
+                    if self.reveal_key {
+                        (format!("{} (F3 Hide)", component.label), text)
+                    } else {
+                        (format!("{} (F3 Reveal)", component.label), mask(&text))
+                    }
+                } else if let Some(e) = input_error {
+                    (
+                        format!("{} ({})", component.label, e),
+                        component.input_component.should_show_text(),
+                    )
+                } else {
+                    (component.label.clone(), component.input_component.should_show_text())
+                };
                 // 预设输入框边框
-                let block = Block::default()
-                    .title(component.label.as_str())
-                    .style(block_style)
-                    .borders(Borders::ALL);
-                let input_paragraph = Paragraph::new(component.input_component.should_show_text())
+                let block = Block::default().title(label).style(block_style).borders(Borders::ALL);
+                let input_paragraph = Paragraph::new(text)
                     .block(block)
                     .wrap(Wrap { trim: false })
                     .style(Style::default().fg(Color::Yellow));
@@ -297,3 +499,98 @@ impl SettingUI {
         }
     }
 }
+
+/// 当前配置方案的系统指令、回复格式约束与生成配置输入组件
+fn profile_components(data: StoreData) -> Vec<(Constraint, Vec<SettingComponent>)> {
+    // 回复格式约束格式化后展示
+    let response_schema = data
+        .response_schema
+        .as_ref()
+        .and_then(|schema| serde_json::to_string_pretty(schema).ok())
+        .unwrap_or_default();
+    vec![
+        (
+            Min(10),
+            vec![
+                SettingComponent {
+                    identifier: InputIdentifier::SystemInstruction,
+                    label: "system instruction".into(),
+                    layout: Fill(1),
+                    input_component: Box::new(TextArea::new(data.system_instruction.unwrap_or("".into()))),
+                },
+                SettingComponent {
+                    identifier: InputIdentifier::ResponseSchema,
+                    label: "response schema (JSON, empty = none)".into(),
+                    layout: Fill(1),
+                    input_component: Box::new(TextArea::new(response_schema)),
+                },
+            ],
+        ),
+        (
+            Length(3),
+            vec![
+                SettingComponent {
+                    identifier: InputIdentifier::ResponseMineType,
+                    label: "response mine type".into(),
+                    layout: Fill(1),
+                    input_component: Box::new(TextField::new(data.options.response_mime_type.unwrap_or("".into()))),
+                },
+                SettingComponent {
+                    identifier: InputIdentifier::MaxOutputTokens,
+                    label: "max output tokens".into(),
+                    layout: Fill(1),
+                    input_component: Box::new(TextField::new(data.options.max_output_tokens.unwrap_or(0).to_string())),
+                },
+            ],
+        ),
+        (
+            Length(3),
+            vec![
+                SettingComponent {
+                    identifier: InputIdentifier::Temperature,
+                    label: "temperature".into(),
+                    layout: Fill(1),
+                    input_component: Box::new(TextField::new(data.options.temperature.unwrap_or(0.0).to_string())),
+                },
+                SettingComponent {
+                    identifier: InputIdentifier::TopP,
+                    label: "top p".into(),
+                    layout: Min(5),
+                    input_component: Box::new(TextField::new(data.options.top_p.unwrap_or(0.0).to_string())),
+                },
+                SettingComponent {
+                    identifier: InputIdentifier::TopK,
+                    label: "top k".into(),
+                    layout: Min(5),
+                    input_component: Box::new(TextField::new(data.options.top_k.unwrap_or(0).to_string())),
+                },
+            ],
+        ),
+    ]
+}
+
+/// 输入内容无效时的说明，无效的输入无法保存
+fn input_error(identifier: &InputIdentifier, content: &str) -> Option<String> {
+    match identifier {
+        InputIdentifier::ResponseSchema => parse_schema(content).err().map(|e| e.to_string()),
+        InputIdentifier::ContextStrategy => parse_choice::<ContextStrategy>(content)
+            .err()
+            .map(|_| format!("expected {}", CONTEXT_STRATEGIES)),
+        InputIdentifier::Harassment
+        | InputIdentifier::HateSpeech
+        | InputIdentifier::SexuallyExplicit
+        | InputIdentifier::DangerousContent => parse_choice::<SafetyThreshold>(content)
+            .err()
+            .map(|_| format!("expected {}", SAFETY_THRESHOLDS)),
+        _ => None,
+    }
+}
+
+/// 解析可选值，不区分大小写，为空时使用默认值
+fn parse_choice<T: FromStr + Default>(content: &str) -> Result<T, T::Err> {
+    let content = content.trim();
+    if content.is_empty() {
+        return Ok(T::default());
+    }
+    content.parse()
+}
@@ -8,57 +8,152 @@ use ratatui::{
         Flex, Layout, Rect,
     },
     style::{Color, Style, Stylize},
+    text::{Line, Text},
     widgets::{
         block::{Position, Title},
         Block, Borders, Paragraph, Widget, Wrap,
     },
 };
 
-use crate::{model::view::ChatMessage, utils::char_utils::s_length};
+use crate::{
+    model::view::{ChatMessage, MessageState},
+    utils::{char_utils::s_length, preview_utils::render_half_block},
+};
 
-use crate::model::view::Sender::{Bot, Never, User};
+use crate::model::view::Sender::{Bot, Never, ToolCall, ToolResult, User};
 
 use super::component::popup::delete_popup::ButtonType::{Cancel, Confirm};
 use super::component::{popup::delete_popup::DeletePopup, scroll::chat_item_list::SelectableConversation};
 
+impl ChatMessage {
+    /// 用户消息的标题
+    fn user_title(&self) -> String {
+        let User(image_paths) = &self.sender else {
+            return String::new();
+        };
+        let title = match image_paths.len() {
+            0 => "Simple".into(),
+            1 => format!("Attachment {}", image_paths[0]),
+            n => format!("{} Attachments {}", n, image_paths.join(", ")),
+        };
+        // 被取消的消息在标题中标注
+        let title = if matches!(self.state, MessageState::Cancelled) {
+            format!("{} (Cancelled)", title)
+        } else {
+            title
+        };
+        let title = match self.context_tag() {
+            Some(tag) => format!("{} ({})", title, tag),
+            None => title,
+        };
+        // 存在多个分支时，标题中展示当前分支
+        if self.sibling_ids.len() > 1 {
+            let index = self
+                .sibling_ids
+                .iter()
+                .position(|id| *id == self.record_id)
+                .unwrap_or_default();
+            format!("{} < {}/{} >", title, index + 1, self.sibling_ids.len())
+        } else {
+            title
+        }
+    }
+
+    /// 固定或已被移出上下文的消息在标题中的标注
+    fn context_tag(&self) -> Option<&'static str> {
+        if self.pinned {
+            Some("Pinned")
+        } else if self.out_of_context {
+            Some("Out of context")
+        } else {
+            None
+        }
+    }
+
+    /// 回复结束原因的标注，正常结束且没有需要提示的安全评级时为空
+    fn finish_badge(&self) -> Option<String> {
+        let finish = self.finish.as_ref()?;
+        if finish.is_truncated() {
+            Some(format!("{} (Shift+F5 Continue)", finish))
+        } else if !finish.is_normal() {
+            Some(finish.to_string())
+        } else {
+            let flagged = finish.flagged_ratings();
+            (!flagged.is_empty()).then(|| flagged.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))
+        }
+    }
+
+    /// 消息内容的样式，已被移出上下文的消息以暗色展示
+    fn content_style(&self, color: Color) -> Style {
+        if self.out_of_context {
+            Style::default().fg(Color::DarkGray).dim()
+        } else {
+            Style::default().fg(color)
+        }
+    }
+
+    /// 用户消息的内容区域、头像区域以及时间区域
+    fn user_areas(&self, area: Rect, title: &str) -> (Rect, Rect, Rect) {
+        // 拿到所有消息中最长一行的宽度
+        let x = self
+            .message
+            .clone()
+            .lines()
+            .map(Into::into)
+            .map(s_length)
+            .max()
+            .unwrap_or_default();
+        // 拿到最大宽度，包含图片预览的宽度
+        let preview_width = self
+            .previews
+            .iter()
+            .map(|preview| preview.width)
+            .max()
+            .unwrap_or_default();
+        let width = max(max(x, s_length(title.into())) as u16, preview_width);
+        // 魔法数 5 为左右边框宽度 1 + 1 加上头像区域宽度 3
+        // 此处之所以和 21 比较，因为21是时间显示区域的宽度，不得少于这个宽度，否则时间显示会有问题
+        let [right] = Layout::horizontal([Max(max(width + 5, 21))])
+            .flex(Flex::End)
+            .areas(area);
+        let [top, time_area] = Layout::vertical([Fill(1), Length(1)]).areas(right);
+        let [content_area, avatar_area] = Layout::horizontal([Max(width + 2), Length(3)])
+            .flex(Flex::End)
+            .areas(top);
+        (content_area, avatar_area, time_area)
+    }
+
+    /// 用户消息中每一张图片预览的区域，依次排列在文本下方
+    pub fn preview_areas(&self, area: Rect) -> Vec<Rect> {
+        let (content_area, _, _) = self.user_areas(area, &self.user_title());
+        // 去掉上边框与文本所占的行
+        let mut y = content_area.y + 1 + self.message.lines().count() as u16;
+        self.previews
+            .iter()
+            .map(|preview| {
+                let preview_area = Rect::new(content_area.x + 1, y, preview.width, preview.height);
+                y += preview.height;
+                preview_area
+            })
+            .collect()
+    }
+}
+
 impl Widget for ChatMessage {
     fn render(self, area: Rect, buf: &mut Buffer)
     where
         Self: Sized,
     {
         match self.sender {
-            User(image_path) => {
-                // 拿到所有消息中最长一行的宽度
-                let x = self
-                    .message
-                    .clone()
-                    .lines()
-                    .map(Into::into)
-                    .map(s_length)
-                    .max()
-                    .unwrap_or_default();
-                // 标题
-                let title = if image_path.is_empty() {
-                    "Simple".into()
-                } else {
-                    format!("Image {}", image_path)
-                };
-                // 拿到最大宽度
-                let width = max(x, s_length(title.clone())) as u16;
-                // 魔法数 5 为左右边框宽度 1 + 1 加上头像区域宽度 3
-                // 此处之所以和 21 比较，因为21是时间显示区域的宽度，不得少于这个宽度，否则时间显示会有问题
-                let [right] = Layout::horizontal([Max(max(width + 5, 21))])
-                    .flex(Flex::End)
-                    .areas(area);
-                let [top, time_area] = Layout::vertical([Fill(1), Length(1)]).areas(right);
+            User(_) => {
+                let title = self.user_title();
+                let (content_area, avatar_area, time_area) = self.user_areas(area, &title);
+                let preview_areas = self.preview_areas(area);
                 // 渲染时间
                 let time_paragraph = Paragraph::new(self.date_time.format(" %Y/%m/%d %H:%M:%S ").to_string())
                     .style(Color::Blue)
                     .right_aligned();
                 time_paragraph.render(time_area, buf);
-                let [content_area, avatar_area] = Layout::horizontal([Max(width + 2), Length(3)])
-                    .flex(Flex::End)
-                    .areas(top);
                 // 渲染头像
                 let avatar_paragraph = Paragraph::new("\n👤").left_aligned();
                 avatar_paragraph.render(avatar_area, buf);
@@ -71,44 +166,177 @@ impl Widget for ChatMessage {
                 } else {
                     Block::default().title(title).red().borders(Borders::ALL)
                 };
+                // 选中的消息高亮边框
+                let message_block = if self.selected {
+                    message_block.border_style(Color::Blue)
+                } else {
+                    message_block
+                };
+                let style = self.content_style(Color::Cyan);
                 let message_paragraph = Paragraph::new(self.message)
                     .wrap(Wrap { trim: false })
-                    .style(Color::Cyan)
+                    .style(style)
                     .block(message_block)
                     .left_aligned();
                 message_paragraph.render(content_area, buf);
+                // 在文本下方渲染图片预览，使用图形协议时会在绘制完成后覆盖在其上方
+                for (preview, preview_area) in self.previews.iter().zip(preview_areas) {
+                    render_half_block(preview, preview_area, buf);
+                }
             }
             Bot => {
                 // 拿到所有消息中最长一行的宽度
-                let width = self
+                let x = match &self.rendered {
+                    Some(text) => text.lines.iter().map(Line::width).max().unwrap_or_default(),
+                    None => self
+                        .message
+                        .clone()
+                        .lines()
+                        .map(Into::into)
+                        .map(s_length)
+                        .max()
+                        .unwrap_or_default(),
+                };
+                // 标题，标识正在接收、被中断或被取消的回复
+                let title = match self.state {
+                    MessageState::Receiving => "Receiving...".into(),
+                    MessageState::Partial => "Partial".into(),
+                    MessageState::Cancelled => "Cancelled".into(),
+                    _ => String::new(),
+                };
+                // 被截断、被拦截或安全评级较高的回复在标题中标注原因，不符合回复格式约束的回复标注不符合之处
+                let badges: Vec<String> = [
+                    self.finish_badge(),
+                    self.schema_mismatch
+                        .as_ref()
+                        .map(|mismatch| format!("Schema Mismatch: {}", mismatch)),
+                ]
+                .into_iter()
+                .flatten()
+                .collect();
+                let badge = (!badges.is_empty()).then(|| badges.join(" "));
+                let title = match &badge {
+                    Some(badge) => format!("{} {}", title, badge).trim().to_owned(),
+                    None => title,
+                };
+                // 固定或已被移出上下文的回复在标题中标注
+                let title = match self.context_tag() {
+                    Some(tag) => format!("{} {}", title, tag).trim().to_owned(),
+                    None => title,
+                };
+                // 存在多个版本时，标题中展示当前版本
+                let title = if self.versions.len() > 1 {
+                    let version = format!("< {}/{} >", self.version_index + 1, self.versions.len());
+                    format!("{} {}", title, version).trim().to_owned()
+                } else {
+                    title
+                };
+                // 拿到最大宽度
+                let width = max(x, s_length(title.clone())) as u16;
+                // 时间之后附带本条回复消耗的 token 数量
+                let time_text = match self.usage {
+                    Some(usage) => format!(
+                        "{}· ↑{} ↓{} tokens ",
+                        self.date_time.format(" %Y/%m/%d %H:%M:%S "),
+                        usage.prompt_tokens,
+                        usage.response_tokens
+                    ),
+                    None => self.date_time.format(" %Y/%m/%d %H:%M:%S ").to_string(),
+                };
+                // 魔法数 5 为左右边框宽度 1 + 1 加上头像区域宽度 3
+                let [left] = Layout::horizontal([Max(max(width + 5, s_length(time_text.clone()) as u16))])
+                    .flex(Flex::Start)
+                    .areas(area);
+                let [top, time_area] = Layout::vertical([Fill(1), Length(1)]).areas(left);
+                // 渲染时间
+                let time_paragraph = Paragraph::new(time_text).style(Color::Blue).left_aligned();
+                time_paragraph.render(time_area, buf);
+                let [avatar_area, content_area] = Layout::horizontal([Length(3), Max(width + 2)])
+                    .flex(Flex::Start)
+                    .areas(top);
+                // 渲染头像
+                let avatar_paragraph = Paragraph::new("\n🤖").right_aligned();
+                avatar_paragraph.render(avatar_area, buf);
+                // 渲染消息内容
+                let message_block =
+                    if matches!(self.state, MessageState::Partial | MessageState::Cancelled) || badge.is_some() {
+                        Block::default().title(title).red().borders(Borders::ALL)
+                    } else {
+                        Block::default()
+                            .title(title)
+                            .style(Color::DarkGray)
+                            .borders(Borders::ALL)
+                    };
+                // 选中的消息高亮边框
+                let message_block = if self.selected {
+                    message_block.border_style(Color::Blue)
+                } else {
+                    message_block
+                };
+                let style = self.content_style(Color::Yellow);
+                // 优先展示渲染后的内容
+                let text = self.rendered.unwrap_or_else(|| Text::from(self.message));
+                let message_paragraph = Paragraph::new(text)
+                    .wrap(Wrap { trim: false })
+                    .style(style)
+                    .block(message_block)
+                    .left_aligned();
+                message_paragraph.render(content_area, buf);
+            }
+            ToolCall(_) | ToolResult(_) => {
+                let x = self
                     .message
                     .clone()
                     .lines()
                     .map(Into::into)
                     .map(s_length)
                     .max()
-                    .unwrap_or_default() as u16;
+                    .unwrap_or_default();
+                let title = match (&self.sender, &self.state) {
+                    (ToolCall(_), _) => "Tool Call",
+                    (_, MessageState::Cancelled) => "Tool Result (Cancelled)",
+                    _ => "Tool Result",
+                };
+                // 固定或已被移出上下文的消息在标题中标注
+                let title = match self.context_tag() {
+                    Some(tag) => format!("{} {}", title, tag),
+                    None => title.into(),
+                };
+                let width = max(x, s_length(title.clone())) as u16;
+                let time_text = self.date_time.format(" %Y/%m/%d %H:%M:%S ").to_string();
                 // 魔法数 5 为左右边框宽度 1 + 1 加上头像区域宽度 3
-                let [left] = Layout::horizontal([Max(max(width + 5, 21))])
+                let [left] = Layout::horizontal([Max(max(width + 5, s_length(time_text.clone()) as u16))])
                     .flex(Flex::Start)
                     .areas(area);
                 let [top, time_area] = Layout::vertical([Fill(1), Length(1)]).areas(left);
                 // 渲染时间
-                let time_paragraph = Paragraph::new(self.date_time.format(" %Y/%m/%d %H:%M:%S ").to_string())
-                    .style(Color::Blue)
-                    .left_aligned();
+                let time_paragraph = Paragraph::new(time_text).style(Color::Blue).left_aligned();
                 time_paragraph.render(time_area, buf);
                 let [avatar_area, content_area] = Layout::horizontal([Length(3), Max(width + 2)])
                     .flex(Flex::Start)
                     .areas(top);
                 // 渲染头像
-                let avatar_paragraph = Paragraph::new("\n🤖").right_aligned();
+                let avatar_paragraph = Paragraph::new("\n🔧").right_aligned();
                 avatar_paragraph.render(avatar_area, buf);
                 // 渲染消息内容
-                let message_block = Block::default().style(Color::DarkGray).borders(Borders::ALL);
+                let message_block = if self.success {
+                    Block::default()
+                        .title(title)
+                        .style(Color::DarkGray)
+                        .borders(Borders::ALL)
+                } else {
+                    Block::default().title(title).red().borders(Borders::ALL)
+                };
+                // 选中的消息高亮边框
+                let message_block = if self.selected {
+                    message_block.border_style(Color::Blue)
+                } else {
+                    message_block
+                };
+                let style = self.content_style(Color::Magenta);
                 let message_paragraph = Paragraph::new(self.message)
                     .wrap(Wrap { trim: false })
-                    .style(Color::Yellow)
+                    .style(style)
                     .block(message_block)
                     .left_aligned();
                 message_paragraph.render(content_area, buf);
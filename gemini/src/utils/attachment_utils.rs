@@ -0,0 +1,243 @@
+use std::{
+    fs,
+    fs::{create_dir_all, File},
+    io::Write,
+    path::Path,
+    sync::LazyLock,
+};
+
+use anyhow::{bail, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use gemini_api::{body::Part, utils::image::blocking::get_image_type_and_base64_string};
+use reqwest::{blocking::Client, header::CONTENT_TYPE};
+
+use crate::model::db::ImageRecord;
+
+use super::{image_utils::cache_image, path_utils::cache_file};
+
+/// 文本附件的大小上限，超过时不再发送
+pub const TEXT_ATTACHMENT_LIMIT: usize = 256 * 1024;
+/// 内联数据附件的大小上限，Gemini API 单次请求不能超过 20MB
+pub const INLINE_ATTACHMENT_LIMIT: usize = 20 * 1024 * 1024;
+/// 图片压缩后的格式
+const COMPRESSED_IMAGE_TYPE: &str = "image/jpeg";
+/// 无法识别的文件类型
+const UNKNOWN_TYPE: &str = "application/octet-stream";
+
+/// 扩展名与文件类型的对应关系
+const MIME_TYPES: &[(&str, &str)] = &[
+    // 图片
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("png", "image/png"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("bmp", "image/bmp"),
+    ("heic", "image/heic"),
+    ("heif", "image/heif"),
+    // 文档
+    ("pdf", "application/pdf"),
+    // 音频
+    ("mp3", "audio/mp3"),
+    ("wav", "audio/wav"),
+    ("aac", "audio/aac"),
+    ("ogg", "audio/ogg"),
+    ("flac", "audio/flac"),
+    ("aiff", "audio/aiff"),
+    ("m4a", "audio/mp4"),
+    // 文本
+    ("txt", "text/plain"),
+    ("log", "text/plain"),
+    ("md", "text/markdown"),
+    ("csv", "text/csv"),
+    ("html", "text/html"),
+    ("htm", "text/html"),
+    ("css", "text/css"),
+    ("xml", "text/xml"),
+    ("json", "application/json"),
+    ("js", "text/javascript"),
+    ("ts", "text/x-typescript"),
+    ("py", "text/x-python"),
+    ("rs", "text/x-rust"),
+    ("go", "text/x-go"),
+    ("java", "text/x-java"),
+    ("kt", "text/x-kotlin"),
+    ("c", "text/x-c"),
+    ("h", "text/x-c"),
+    ("cpp", "text/x-c++"),
+    ("hpp", "text/x-c++"),
+    ("cs", "text/x-csharp"),
+    ("sh", "text/x-shellscript"),
+    ("sql", "text/x-sql"),
+    ("toml", "text/x-toml"),
+    ("yaml", "text/x-yaml"),
+    ("yml", "text/x-yaml"),
+];
+
+/// 附件种类，决定附件发送给 Gemini 的方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttachmentKind {
+    /// 图片，压缩后以内联数据发送
+    Image,
+    /// 文本或源代码，以文本片段发送
+    Text,
+    /// PDF 或音频，原样以内联数据发送
+    Inline,
+}
+
+impl AttachmentKind {
+    /// 根据文件类型判断附件种类
+    pub fn from_mime_type(mime_type: &str) -> Option<Self> {
+        if mime_type.starts_with("image/") {
+            Some(Self::Image)
+        } else if mime_type.starts_with("text/") || mime_type == "application/json" {
+            Some(Self::Text)
+        } else if mime_type.starts_with("audio/") || mime_type == "application/pdf" {
+            Some(Self::Inline)
+        } else {
+            None
+        }
+    }
+}
+
+/// 附件下载的请求客户端
+static CLIENT: LazyLock<Client> = LazyLock::new(Client::new);
+
+/// 是否为网络地址
+fn is_url(path: &str) -> bool {
+    path.starts_with("https://") || path.starts_with("http://")
+}
+
+/// 附件的文件名，用于在文本片段中标识附件
+fn file_name_of(path: &str) -> &str {
+    let path = path.split(['?', '#']).next().unwrap_or(path);
+    path.rsplit(['/', '\\']).next().unwrap_or(path)
+}
+
+/// 根据扩展名识别文件类型，无法识别时返回空
+pub fn mime_type_by_extension(path: &str) -> Option<&'static str> {
+    let extension = Path::new(file_name_of(path)).extension()?.to_str()?.to_lowercase();
+    MIME_TYPES
+        .iter()
+        .find(|(ext, _)| *ext == extension)
+        .map(|(_, mime_type)| *mime_type)
+}
+
+/// 识别文件类型，扩展名无法识别时根据文件内容判断是否为文本
+fn detect_mime_type(path: &str, data: &[u8], content_type: Option<String>) -> String {
+    if let Some(mime_type) = mime_type_by_extension(path) {
+        return mime_type.into();
+    }
+    // 网络地址使用响应头中的类型
+    if let Some(content_type) = content_type {
+        let mime_type = content_type.split(';').next().unwrap_or_default().trim().to_lowercase();
+        if AttachmentKind::from_mime_type(&mime_type).is_some() {
+            return mime_type;
+        }
+    }
+    if !data.contains(&0) && std::str::from_utf8(data).is_ok() {
+        "text/plain".into()
+    } else {
+        UNKNOWN_TYPE.into()
+    }
+}
+
+/// 读取本地文件或下载网络文件，同时返回网络响应头中的文件类型
+fn fetch(path: &str) -> Result<(Vec<u8>, Option<String>)> {
+    if is_url(path) {
+        let response = CLIENT.get(path).send()?;
+        if !response.status().is_success() {
+            bail!("Failed to download attachment")
+        }
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned);
+        Ok((response.bytes()?.to_vec(), content_type))
+    } else {
+        Ok((fs::read(path)?, None))
+    }
+}
+
+/// 根据附件种类构建发送给 Gemini 的数据片段
+fn build_part(path: &str, mime_type: &str, data: Vec<u8>) -> Result<Part> {
+    match AttachmentKind::from_mime_type(mime_type) {
+        Some(AttachmentKind::Text) => {
+            if data.len() > TEXT_ATTACHMENT_LIMIT {
+                bail!(
+                    "Text attachment {} exceeds {} KB",
+                    file_name_of(path),
+                    TEXT_ATTACHMENT_LIMIT / 1024
+                )
+            }
+            let text = String::from_utf8_lossy(&data);
+            Ok(Part::Text(format!(
+                "File: {}\n```\n{}\n```",
+                file_name_of(path),
+                text.trim_end()
+            )))
+        }
+        Some(_) => {
+            if data.len() > INLINE_ATTACHMENT_LIMIT {
+                bail!(
+                    "Attachment {} exceeds {} MB",
+                    file_name_of(path),
+                    INLINE_ATTACHMENT_LIMIT / 1024 / 1024
+                )
+            }
+            Ok(Part::InlineData {
+                mime_type: mime_type.into(),
+                data: STANDARD.encode(data),
+            })
+        }
+        None => bail!("Unsupported attachment type: {}", file_name_of(path)),
+    }
+}
+
+/// 读取附件并构建发送给 Gemini 的数据片段
+pub fn read_attachment(path: String) -> Result<Part> {
+    // 图片沿用原有的读取方式
+    if mime_type_by_extension(&path).and_then(AttachmentKind::from_mime_type) == Some(AttachmentKind::Image) {
+        let (mime_type, data) = get_image_type_and_base64_string(path)?;
+        return Ok(Part::InlineData { mime_type, data });
+    }
+    let (data, content_type) = fetch(&path)?;
+    let mime_type = detect_mime_type(&path, &data, content_type);
+    build_part(&path, &mime_type, data)
+}
+
+/// 将附件保存到缓存目录，图片会被压缩，返回保存后的文件类型
+pub fn cache_attachment(path: String, file_name: String) -> Result<String> {
+    if mime_type_by_extension(&path).and_then(AttachmentKind::from_mime_type) == Some(AttachmentKind::Image) {
+        cache_image(path, file_name)?;
+        return Ok(COMPRESSED_IMAGE_TYPE.into());
+    }
+    let (data, content_type) = fetch(&path)?;
+    let mime_type = detect_mime_type(&path, &data, content_type);
+    if AttachmentKind::from_mime_type(&mime_type).is_none() {
+        bail!("Unsupported attachment type: {}", file_name_of(&path))
+    }
+    let output_path = cache_file(&file_name);
+    create_dir_all(output_path.parent().unwrap())?;
+    let mut file = File::create(output_path)?;
+    file.write_all(&data)?;
+    Ok(mime_type)
+}
+
+/// 读取缓存的附件原始内容
+pub fn read_attachment_cache(file_name: &str) -> Result<Vec<u8>> {
+    Ok(fs::read(cache_file(file_name))?)
+}
+
+/// 根据附件记录重新构建数据片段，缓存丢失时重新读取原始文件并缓存
+pub fn rebuild_attachment(image_record: &ImageRecord) -> Result<Part> {
+    match read_attachment_cache(&image_record.image_record_id) {
+        Ok(data) => build_part(&image_record.image_path, &image_record.image_type, data),
+        Err(_) => {
+            let part = read_attachment(image_record.image_path.clone())?;
+            let _ = cache_attachment(image_record.image_path.clone(), image_record.image_record_id.clone());
+            Ok(part)
+        }
+    }
+}
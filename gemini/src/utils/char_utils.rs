@@ -82,3 +82,14 @@ fn is_chinese_punctuation(c: char) -> bool {
 pub(crate) fn s_length(str: String) -> usize {
     str.chars().map(c_len).sum()
 }
+
+/// 将字符串替换为相同宽度的掩码，用于隐藏密钥与口令
+///
+/// # Examples
+/// ```
+/// assert_eq!(mask("abc"), "***");
+/// assert_eq!(mask("你好"), "****");
+/// ```
+pub(crate) fn mask(str: &str) -> String {
+    str.chars().map(|c| "*".repeat(c_len(c))).collect()
+}
@@ -0,0 +1,21 @@
+use std::io::{stdout, Write};
+
+use anyhow::Result;
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+/// 通过 OSC 52 终端转义序列将文本复制到系统剪贴板
+///
+/// 由终端负责写入剪贴板，因此通过 SSH 连接时同样可用，且不依赖图形界面
+pub fn copy_to_clipboard(text: &str) -> Result<()> {
+    let sequence = format!("\x1b]52;c;{}\x07", STANDARD.encode(text));
+    // tmux 中需要使用 DCS 透传序列，才能将转义序列传递给外层终端
+    let sequence = if std::env::var("TMUX").is_ok() {
+        format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"))
+    } else {
+        sequence
+    };
+    let mut stdout = stdout();
+    stdout.write_all(sequence.as_bytes())?;
+    stdout.flush()?;
+    Ok(())
+}
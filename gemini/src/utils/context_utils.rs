@@ -0,0 +1,310 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    ops::Range,
+};
+
+use anyhow::{bail, Result};
+use gemini_api::{
+    body::{request::GenerationConfig, Content, Part, Role},
+    model::blocking::Gemini,
+};
+
+use super::{
+    gemini_utils::{count_tokens, send_message_stream, user_content, RequestOptions},
+    store_utils::{ContextConfig, ContextStrategy},
+    tool_utils::{is_function_response, tool_data},
+};
+
+/// 总结被移出上下文的对话时使用的系统指令
+const SUMMARY_INSTRUCTION: &str =
+    "请总结以下对话内容，保留其中的事实、结论、约定与尚未完成的事项，不要添加对话中没有的内容。";
+
+/// 每个附件估算的 token 数量
+const ATTACHMENT_TOKENS: u32 = 258;
+
+/// 发送请求时的上下文设置
+#[derive(Clone, Default)]
+pub struct ContextOptions {
+    /// 上下文窗口设置
+    pub config: ContextConfig,
+    /// 历史记录中的每条消息是否被固定，与 `gemini.contents` 一一对应
+    pub pinned: Vec<bool>,
+    /// 上一次请求生成的总结，被总结的对话仍被移出上下文时可以复用
+    pub summary: Option<ContextSummary>,
+}
+
+/// 被移出上下文的对话的总结
+#[derive(Clone, Debug)]
+pub struct ContextSummary {
+    /// 已被总结的每条消息的摘要值，用于判断总结是否仍然有效
+    covered: Vec<u64>,
+    /// 总结内容
+    pub text: String,
+}
+
+/// 一次请求实际使用的上下文
+#[derive(Clone, Debug, Default)]
+pub struct ContextWindow {
+    /// 被移出上下文的历史记录序号，与 `gemini.contents` 一一对应
+    pub dropped: Vec<usize>,
+    /// 被移出部分的总结，只有总结策略才会有值
+    pub summary: Option<ContextSummary>,
+}
+
+/// 请求超出预算时，将最早的未固定对话移出上下文，返回实际用于发送请求的客户端与移出的结果
+///
+/// 总 token 数量通过 API 计算，失败时使用估算值，每一轮对话的 token 数量为估算值。
+/// 系统指令与新消息始终保留，只剩固定的对话时即使仍超出预算也照常发送
+pub fn fit_context(
+    gemini: &Gemini,
+    content: &Content,
+    options: &ContextOptions,
+    request: &RequestOptions,
+) -> (Gemini, ContextWindow) {
+    let budget = options.config.budget;
+    if budget == 0 || gemini.contents.is_empty() {
+        return (gemini.clone(), ContextWindow::default());
+    }
+    let total = count_tokens(gemini, content.clone(), request).unwrap_or_else(|_| estimate_request(gemini, content));
+    if total <= budget {
+        return (gemini.clone(), ContextWindow::default());
+    }
+    let dropped = select_dropped(&gemini.contents, &options.pinned, total - budget);
+    if dropped.is_empty() {
+        return (gemini.clone(), ContextWindow::default());
+    }
+    let mut trimmed = gemini.clone();
+    trimmed.contents = gemini
+        .contents
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| !dropped.contains(index))
+        .map(|(_, content)| content.clone())
+        .collect();
+    // 总结失败时退化为直接丢弃
+    let summary = match options.config.strategy {
+        ContextStrategy::Drop => None,
+        ContextStrategy::Summarize => summarize(gemini, &dropped, options.summary.as_ref(), request).ok(),
+    };
+    if let Some(summary) = &summary {
+        let instruction = match gemini.system_instruction.as_deref().filter(|s| !s.is_empty()) {
+            Some(instruction) => format!("{}\n\n之前对话的总结：\n{}", instruction, summary.text),
+            None => format!("之前对话的总结：\n{}", summary.text),
+        };
+        trimmed.set_system_instruction(instruction);
+    }
+    (trimmed, ContextWindow { dropped, summary })
+}
+
+/// 将历史记录按轮次分组，每一轮从用户消息开始，包含其后的所有回复，工具执行结果与工具调用属于同一轮
+fn turns(contents: &[Content]) -> Vec<Range<usize>> {
+    let mut turns: Vec<Range<usize>> = Vec::new();
+    for (index, content) in contents.iter().enumerate() {
+        match turns.last_mut() {
+            Some(turn) if !matches!(content.role, Some(Role::User)) || is_function_response(content) => {
+                turn.end = index + 1
+            }
+            _ => turns.push(index..index + 1),
+        }
+    }
+    turns
+}
+
+/// 从最早的一轮开始选出需要移出上下文的历史记录，直到移出的 token 数量不少于超出的数量，包含固定消息的一轮对话会被跳过
+fn select_dropped(contents: &[Content], pinned: &[bool], excess: u32) -> Vec<usize> {
+    let mut dropped = Vec::new();
+    let mut removed = 0;
+    for turn in turns(contents) {
+        if removed >= excess {
+            break;
+        }
+        if turn.clone().any(|index| pinned.get(index).copied().unwrap_or_default()) {
+            continue;
+        }
+        removed += turn.clone().map(|index| estimate_tokens(&contents[index])).sum::<u32>();
+        dropped.extend(turn);
+    }
+    dropped
+}
+
+/// 总结被移出上下文的对话，上一次的总结仍然有效时只总结新移出的对话
+fn summarize(
+    gemini: &Gemini,
+    dropped: &[usize],
+    previous: Option<&ContextSummary>,
+    request: &RequestOptions,
+) -> Result<ContextSummary> {
+    let covered: Vec<u64> = dropped.iter().map(|&index| digest(&gemini.contents[index])).collect();
+    // 上一次总结的对话都仍被移出时，总结仍然有效
+    let previous = previous.filter(|previous| previous.covered.iter().all(|hash| covered.contains(hash)));
+    if let Some(previous) = previous.filter(|previous| previous.covered.len() == covered.len()) {
+        return Ok(previous.clone());
+    }
+    let mut transcript = String::new();
+    if let Some(previous) = previous {
+        transcript.push_str("之前对话的总结：\n");
+        transcript.push_str(&previous.text);
+        transcript.push_str("\n\n");
+    }
+    for (&index, hash) in dropped.iter().zip(covered.iter()) {
+        if previous.is_some_and(|previous| previous.covered.contains(hash)) {
+            continue;
+        }
+        let content = &gemini.contents[index];
+        let speaker = match content.role {
+            Some(Role::Model) => "助手",
+            _ => "用户",
+        };
+        transcript.push_str(&format!("{}：{}\n\n", speaker, text_of(content)));
+    }
+    let mut summarizer = Gemini::rebuild(
+        gemini.key.clone(),
+        gemini.model.clone(),
+        Vec::new(),
+        GenerationConfig::default(),
+    );
+    summarizer.set_system_instruction(SUMMARY_INSTRUCTION.into());
+    // 总结时不声明本地工具
+    let request = RequestOptions {
+        safety: request.safety.clone(),
+        ..Default::default()
+    };
+    let text = send_message_stream(&summarizer, user_content(transcript, Vec::new())?, &request)?
+        .map(|chunk| chunk.map(|chunk| chunk.text))
+        .collect::<Result<String>>()?;
+    if text.trim().is_empty() {
+        bail!("Empty Summary")
+    }
+    Ok(ContextSummary {
+        covered,
+        text: text.trim().to_owned(),
+    })
+}
+
+/// 消息中的文本，工具片段使用其 JSON 数据，附件以占位符代替
+fn text_of(content: &Content) -> String {
+    content
+        .parts
+        .iter()
+        .map(|part| match part {
+            Part::Text(text) => text.as_str(),
+            part => tool_data(part).unwrap_or("[附件]"),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// 消息内容的摘要值
+fn digest(content: &Content) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    serde_json::to_string(content).unwrap_or_default().hash(&mut hasher);
+    hasher.finish()
+}
+
+/// 估算整个请求的 token 数量
+fn estimate_request(gemini: &Gemini, content: &Content) -> u32 {
+    let instruction = gemini.system_instruction.as_deref().map_or(0, estimate_text);
+    instruction
+        + gemini
+            .contents
+            .iter()
+            .chain([content])
+            .map(estimate_tokens)
+            .sum::<u32>()
+}
+
+/// 估算消息的 token 数量
+fn estimate_tokens(content: &Content) -> u32 {
+    content
+        .parts
+        .iter()
+        .map(|part| match part {
+            Part::Text(text) => estimate_text(text),
+            part => tool_data(part).map_or(ATTACHMENT_TOKENS, estimate_text),
+        })
+        .sum()
+}
+
+/// 估算文本的 token 数量，ASCII 字符约 4 个一个 token，其它字符约一个字符一个 token
+fn estimate_text(text: &str) -> u32 {
+    let ascii = text.chars().filter(char::is_ascii).count() as u32;
+    let others = text.chars().count() as u32 - ascii;
+    ascii.div_ceil(4) + others
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::model::view::{FunctionCall, FunctionResponse};
+    use crate::utils::{
+        gemini_utils::model_content,
+        tool_utils::{function_call_content, function_response_content},
+    };
+
+    fn user(text: &str) -> Content {
+        Content {
+            parts: vec![Part::Text(text.into())],
+            role: Some(Role::User),
+        }
+    }
+
+    /// 一问一答的多轮对话，每条消息 10 个 token
+    fn conversation(turn_count: usize) -> Vec<Content> {
+        (0..turn_count)
+            .flat_map(|_| [user(&"q".repeat(40)), model_content("a".repeat(40))])
+            .collect()
+    }
+
+    #[test]
+    fn tool_messages_stay_in_turn() {
+        let call = FunctionCall {
+            name: "get_current_time".into(),
+            args: json!({}),
+        };
+        let response = FunctionResponse {
+            name: "get_current_time".into(),
+            response: json!({ "output": "2026-10-17T09:00:00+08:00" }),
+        };
+        let contents = vec![
+            user("what time is it?"),
+            function_call_content(&[call]),
+            function_response_content(&[response]),
+            model_content("It is nine.".into()),
+            user("thanks"),
+            model_content("You're welcome.".into()),
+        ];
+        assert_eq!(turns(&contents), [0..4, 4..6]);
+        // 工具执行结果不会单独被移出上下文
+        assert_eq!(select_dropped(&contents, &[false; 6], 1), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn pinned_turns_are_kept() {
+        let contents = conversation(3);
+        let pinned = [false, false, false, true, false, false];
+        assert_eq!(select_dropped(&contents, &pinned, u32::MAX), [0, 1, 4, 5]);
+    }
+
+    #[test]
+    fn dropping_stops_when_excess_is_covered() {
+        let contents = conversation(3);
+        assert_eq!(estimate_tokens(&contents[0]), 10);
+        assert!(select_dropped(&contents, &[false; 6], 0).is_empty());
+        assert_eq!(select_dropped(&contents, &[false; 6], 15), [0, 1]);
+        assert_eq!(select_dropped(&contents, &[false; 6], 20), [0, 1]);
+        assert_eq!(select_dropped(&contents, &[false; 6], 21), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn estimate_non_ascii_text() {
+        assert_eq!(estimate_text(""), 0);
+        assert_eq!(estimate_text("abcd"), 1);
+        assert_eq!(estimate_text("abcde"), 2);
+        assert_eq!(estimate_text("你好"), 2);
+        assert_eq!(estimate_text("hi 你好，世界"), 6);
+        assert_eq!(estimate_text("é"), 1);
+    }
+}
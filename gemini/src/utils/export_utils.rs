@@ -0,0 +1,364 @@
+use std::{
+    collections::BTreeMap,
+    fs::{create_dir_all, File},
+    io::Write,
+    path::PathBuf,
+};
+
+use anyhow::Result;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, Local};
+use pulldown_cmark::{html::push_html, CowStr, Event, LinkType, Options, Parser, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumCount, EnumString, FromRepr};
+
+use crate::model::{
+    db::{Conversation, ImageRecord, MessageRecord},
+    view::{MessageState, Sender},
+};
+
+use crate::store::ConversationStore;
+
+use super::{attachment_utils::read_attachment_cache, db_utils::conversation_store, path_utils::export_dir};
+
+/// 导出的 JSON 文档格式版本
+pub(crate) const EXPORT_VERSION: u32 = 1;
+/// 时间的展示格式
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// 导出格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Display, EnumString, EnumCount, FromRepr)]
+pub enum ExportFormat {
+    #[default]
+    #[strum(to_string = "Markdown", serialize = "markdown", serialize = "md")]
+    Markdown,
+    #[strum(to_string = "JSON", serialize = "json")]
+    Json,
+    #[strum(to_string = "HTML", serialize = "html")]
+    Html,
+}
+
+impl ExportFormat {
+    /// 导出文件的扩展名
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "md",
+            ExportFormat::Json => "json",
+            ExportFormat::Html => "html",
+        }
+    }
+}
+
+/// 导出的 JSON 文档
+#[derive(Serialize)]
+struct ExportDocument<'a> {
+    /// 文档格式版本
+    version: u32,
+    /// 导出时间
+    exported_at: DateTime<Local>,
+    /// 会话及其聊天记录
+    conversation: &'a Conversation,
+    /// 附件数据，键为附件记录 id
+    images: BTreeMap<String, ExportImage>,
+}
+
+/// 导出的附件数据
+#[derive(Serialize, Deserialize)]
+pub(crate) struct ExportImage {
+    /// 附件类型
+    pub mime_type: String,
+    /// Base64 编码的附件内容
+    pub data: String,
+}
+
+/// 查询要导出的会话，JSON 格式包含所有分支上的聊天记录，其它格式只包含当前分支
+pub fn load_for_export(conversation_id: &str, format: ExportFormat) -> Result<Conversation> {
+    match format {
+        ExportFormat::Json => conversation_store().load_all(conversation_id),
+        _ => conversation_store().load(conversation_id),
+    }
+}
+
+/// 将会话导出为指定格式的文本，会话需通过 `load_for_export` 查询
+pub fn export_conversation(conversation: &Conversation, format: ExportFormat) -> Result<String> {
+    match format {
+        ExportFormat::Markdown => Ok(to_markdown(conversation)),
+        ExportFormat::Json => to_json(conversation),
+        ExportFormat::Html => Ok(to_html(conversation)),
+    }
+}
+
+/// 将会话导出到文件，未指定路径时保存到数据目录的 export 目录下，返回导出文件的路径
+pub fn export_to_file(conversation: &Conversation, format: ExportFormat, path: Option<PathBuf>) -> Result<PathBuf> {
+    let path = match path {
+        Some(path) => path,
+        None => export_dir().join(export_file_name(conversation, format)),
+    };
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        create_dir_all(parent)?;
+    }
+    let content = export_conversation(conversation, format)?;
+    let mut file = File::create(&path)?;
+    file.write_all(content.as_bytes())?;
+    Ok(path)
+}
+
+/// 导出文件名，由标题与会话 id 组成，去除文件名中不允许出现的字符
+fn export_file_name(conversation: &Conversation, format: ExportFormat) -> String {
+    let title: String = conversation
+        .conversation_title
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let title = title.trim();
+    if title.is_empty() {
+        format!("{}.{}", conversation.conversation_id, format.extension())
+    } else {
+        format!("{}_{}.{}", title, conversation.conversation_id, format.extension())
+    }
+}
+
+/// 会话标题，标题为空时使用默认值
+fn display_title(conversation: &Conversation) -> &str {
+    if conversation.conversation_title.is_empty() {
+        "Untitled"
+    } else {
+        &conversation.conversation_title
+    }
+}
+
+/// 发送者名称
+fn sender_name(record: &MessageRecord) -> &'static str {
+    match record.record_sender {
+        Sender::User(_) => "User",
+        Sender::Bot => "Gemini",
+        Sender::ToolCall(_) => "Tool Call",
+        Sender::ToolResult(_) => "Tool Result",
+        Sender::Never => "Unknown",
+    }
+}
+
+/// 记录状态的说明，完整接收的记录不展示
+fn state_label(record: &MessageRecord) -> Option<&'static str> {
+    match record.record_state {
+        MessageState::Partial => Some("Partial"),
+        MessageState::Cancelled => Some("Cancelled"),
+        _ => None,
+    }
+}
+
+/// 读取缓存的附件，返回附件类型与 Base64 编码的内容
+fn read_record_image(image_record: &ImageRecord) -> Option<(String, String)> {
+    let data = read_attachment_cache(&image_record.image_record_id).ok()?;
+    Some((image_record.image_type.clone(), STANDARD.encode(data)))
+}
+
+/// 附件是否为图片
+fn is_image(image_record: &ImageRecord) -> bool {
+    image_record.image_type.starts_with("image/")
+}
+
+/// 导出为 Markdown
+fn to_markdown(conversation: &Conversation) -> String {
+    let mut markdown = format!(
+        "# {}\n\n- Start: {}\n- Modify: {}\n",
+        display_title(conversation),
+        conversation.conversation_start_time.format(TIME_FORMAT),
+        conversation.conversation_modify_time.format(TIME_FORMAT),
+    );
+    for record in conversation.conversation_records.iter() {
+        markdown.push_str(&format!(
+            "\n## {} · {}",
+            sender_name(record),
+            record.record_time.format(TIME_FORMAT)
+        ));
+        if let Some(label) = state_label(record) {
+            markdown.push_str(&format!(" ({})", label));
+        }
+        markdown.push_str("\n\n");
+        for image_record in record.image_records.iter() {
+            if is_image(image_record) {
+                markdown.push_str(&format!("![image](<{}>)\n\n", image_record.image_path));
+            } else {
+                markdown.push_str(&format!("[attachment](<{}>)\n\n", image_record.image_path));
+            }
+        }
+        markdown.push_str(record.record_content.trim_end());
+        markdown.push('\n');
+    }
+    markdown
+}
+
+/// 导出为 JSON，包含所有分支上的完整记录信息以及图片数据
+fn to_json(conversation: &Conversation) -> Result<String> {
+    let images = conversation
+        .conversation_records
+        .iter()
+        .flat_map(|record| record.image_records.iter())
+        .filter_map(|image_record| {
+            let (mime_type, data) = read_record_image(image_record)?;
+            Some((image_record.image_record_id.clone(), ExportImage { mime_type, data }))
+        })
+        .collect();
+    let document = ExportDocument {
+        version: EXPORT_VERSION,
+        exported_at: Local::now(),
+        conversation,
+        images,
+    };
+    Ok(serde_json::to_string_pretty(&document)?)
+}
+
+/// HTML 文档样式
+const HTML_STYLE: &str = r#"
+body { max-width: 860px; margin: 0 auto; padding: 24px; font-family: -apple-system, "Segoe UI", "Microsoft YaHei", sans-serif; background: #f6f7f9; color: #1f2328; }
+h1 { margin-bottom: 4px; }
+.meta { color: #6e7781; font-size: 13px; margin-bottom: 24px; }
+.message { border-radius: 8px; padding: 12px 16px; margin: 12px 0; background: #fff; border: 1px solid #d0d7de; }
+.message.user { background: #e7f3ff; border-color: #b6d7ff; }
+.header { font-size: 13px; color: #57606a; margin-bottom: 8px; }
+.header .sender { font-weight: bold; color: #1f2328; }
+.header .state { color: #cf222e; }
+.text { white-space: pre-wrap; word-break: break-word; }
+img { max-width: 100%; border-radius: 4px; margin-bottom: 8px; }
+pre { background: #2b303b; color: #c0c5ce; padding: 12px; border-radius: 6px; overflow-x: auto; }
+code { font-family: "JetBrains Mono", Consolas, monospace; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #d0d7de; padding: 4px 8px; }
+blockquote { margin: 0; padding-left: 12px; border-left: 4px solid #d0d7de; color: #57606a; }
+"#;
+
+/// 导出为单文件 HTML，图片以 Base64 形式内嵌
+fn to_html(conversation: &Conversation) -> String {
+    let title = escape_html(display_title(conversation));
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n<h1>{}</h1>\n<div class=\"meta\">Start: {} · Modify: {}</div>\n",
+        title,
+        HTML_STYLE,
+        title,
+        conversation.conversation_start_time.format(TIME_FORMAT),
+        conversation.conversation_modify_time.format(TIME_FORMAT),
+    );
+    for record in conversation.conversation_records.iter() {
+        let class = match record.record_sender {
+            Sender::User(_) => "message user",
+            _ => "message",
+        };
+        html.push_str(&format!(
+            "<div class=\"{}\">\n<div class=\"header\"><span class=\"sender\">{}</span> · {}",
+            class,
+            sender_name(record),
+            record.record_time.format(TIME_FORMAT)
+        ));
+        if let Some(label) = state_label(record) {
+            html.push_str(&format!(" <span class=\"state\">({})</span>", label));
+        }
+        html.push_str("</div>\n");
+        // 图片直接展示，其它附件内嵌为下载链接，读取失败时保留原始路径
+        for image_record in record.image_records.iter() {
+            match read_record_image(image_record) {
+                Some((mime_type, data)) if is_image(image_record) => html.push_str(&format!(
+                    "<img src=\"data:{};base64,{}\" alt=\"image\">\n",
+                    escape_html(&mime_type),
+                    data
+                )),
+                Some((mime_type, data)) => html.push_str(&format!(
+                    "<div class=\"text\"><a href=\"data:{};base64,{}\" download>[attachment: {}]</a></div>\n",
+                    escape_html(&mime_type),
+                    data,
+                    escape_html(&image_record.image_path)
+                )),
+                None => html.push_str(&format!(
+                    "<div class=\"text\">[attachment: {}]</div>\n",
+                    escape_html(&image_record.image_path)
+                )),
+            }
+        }
+        match record.record_sender {
+            // 回复内容为 Markdown，转换为 HTML
+            Sender::Bot => {
+                html.push_str("<div class=\"markdown\">\n");
+                html.push_str(&markdown_to_html(&record.record_content));
+                html.push_str("</div>\n");
+            }
+            _ => html.push_str(&format!(
+                "<div class=\"text\">{}</div>\n",
+                escape_html(&record.record_content)
+            )),
+        }
+        html.push_str("</div>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// 将 Markdown 转换为 HTML，其中的原始 HTML 按文本处理，避免在导出文件中执行脚本
+///
+/// 链接只保留 http、https 与 mailto 地址，图片转换为链接，导出的文件不会加载外部内容
+fn markdown_to_html(markdown: &str) -> String {
+    let parser =
+        Parser::new_ext(markdown, Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH).map(|event| match event {
+            Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+            Event::Start(
+                Tag::Link {
+                    link_type,
+                    dest_url,
+                    title,
+                    id,
+                }
+                | Tag::Image {
+                    link_type,
+                    dest_url,
+                    title,
+                    id,
+                },
+            ) => Event::Start(Tag::Link {
+                link_type,
+                dest_url: safe_url(link_type, dest_url),
+                title,
+                id,
+            }),
+            Event::End(TagEnd::Image) => Event::End(TagEnd::Link),
+            event => event,
+        });
+    let mut html = String::new();
+    push_html(&mut html, parser);
+    html
+}
+
+/// 不安全的链接地址替换为空
+fn safe_url(link_type: LinkType, url: CowStr) -> CowStr {
+    // 邮箱自动链接在输出时添加 mailto 前缀
+    if link_type == LinkType::Email {
+        return url;
+    }
+    let scheme = url.trim_start().to_ascii_lowercase();
+    if ["http://", "https://", "mailto:"]
+        .iter()
+        .any(|prefix| scheme.starts_with(prefix))
+    {
+        url
+    } else {
+        CowStr::Borrowed("")
+    }
+}
+
+/// 转义 HTML 特殊字符
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,351 @@
+use std::{
+    io::{BufRead, BufReader},
+    sync::LazyLock,
+};
+
+use anyhow::{Error, Result};
+use gemini_api::{
+    body::{request::GenerationConfig, Content, Part, Role},
+    model::blocking::Gemini,
+    param::LanguageModel,
+};
+use reqwest::blocking::{Client, Response};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::model::view::{FinishInfo, FunctionCall, SafetyRating, TokenUsage};
+
+use super::{
+    attachment_utils::read_attachment,
+    store_utils::{SafetyConfig, ToolConfig},
+    tool_utils::{request_content, tool_declarations},
+};
+
+/// Gemini API 地址
+const GEMINI_API_URL: &str = "https://generativelanguage.googleapis.com/v1beta/models";
+/// 传递密钥的请求头
+const API_KEY_HEADER: &str = "x-goog-api-key";
+
+/// 流式请求客户端，回复可能持续较长时间，因此不设置超时时间
+static CLIENT: LazyLock<Client> = LazyLock::new(|| Client::builder().timeout(None).build().unwrap());
+
+/// 请求附带的安全设置、本地工具与回复格式约束
+#[derive(Clone, Default)]
+pub struct RequestOptions {
+    /// 各类别的安全阈值
+    pub safety: SafetyConfig,
+    /// 本地工具设置，未启用时不声明工具
+    pub tools: ToolConfig,
+    /// 回复格式约束，随生成配置一起发送
+    pub response_schema: Option<Value>,
+}
+
+/// 流式生成内容请求体
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GenerateContentRequest {
+    contents: Vec<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system_instruction: Option<Value>,
+    generation_config: Value,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    safety_settings: Vec<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<Value>,
+}
+
+/// 生成内容响应体，流式响应中每一个事件为一个响应体
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct GenerateContentResponse {
+    #[serde(default)]
+    candidates: Vec<Candidate>,
+    usage_metadata: Option<UsageMetadata>,
+    prompt_feedback: Option<PromptFeedback>,
+}
+
+/// 请求内容的安全反馈，请求被拦截时没有候选回复
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PromptFeedback {
+    block_reason: Option<String>,
+    #[serde(default)]
+    safety_ratings: Vec<SafetyRating>,
+}
+
+/// token 用量，流式响应中为截至当前事件的累计用量
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UsageMetadata {
+    #[serde(default)]
+    prompt_token_count: u32,
+    #[serde(default)]
+    candidates_token_count: u32,
+}
+
+/// 候选回复
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Candidate {
+    content: Option<CandidateContent>,
+    /// 结束原因，只有最后一个事件中才会有值
+    finish_reason: Option<String>,
+    #[serde(default)]
+    safety_ratings: Vec<SafetyRating>,
+}
+
+/// 候选回复内容
+#[derive(Deserialize)]
+struct CandidateContent {
+    #[serde(default)]
+    parts: Vec<CandidatePart>,
+}
+
+/// 候选回复内容片段
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CandidatePart {
+    text: Option<String>,
+    function_call: Option<FunctionCall>,
+}
+
+/// 计算 token 数量响应体
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CountTokensResponse {
+    total_tokens: u32,
+}
+
+/// 错误响应体
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: ErrorDetail,
+}
+
+/// 错误信息
+#[derive(Deserialize)]
+struct ErrorDetail {
+    message: String,
+}
+
+/// 流式响应中的一段回复
+pub struct StreamChunk {
+    /// 新接收到的文本
+    pub text: String,
+    /// 截至当前的 token 用量，以最后一次返回的用量为准
+    pub usage: Option<TokenUsage>,
+    /// 回复结束的原因与安全评级，只有最后一段回复才会有值
+    pub finish: Option<FinishInfo>,
+    /// 模型请求调用的本地工具
+    pub function_calls: Vec<FunctionCall>,
+}
+
+/// 流式响应，每次迭代返回一段新接收到的回复
+pub struct GeminiStream {
+    reader: BufReader<Response>,
+}
+
+impl Iterator for GeminiStream {
+    type Item = Result<StreamChunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            match self.reader.read_line(&mut line) {
+                // 连接关闭，响应结束
+                Ok(0) => return None,
+                Ok(_) => {
+                    // 只处理 SSE 中的数据行，忽略空行与注释
+                    let Some(data) = line.trim_end().strip_prefix("data:") else {
+                        continue;
+                    };
+                    return match serde_json::from_str::<GenerateContentResponse>(data.trim()) {
+                        Ok(response) => Some(response.chunk()),
+                        Err(e) => Some(Err(e.into())),
+                    };
+                }
+                Err(e) => return Some(Err(e.into())),
+            }
+        }
+    }
+}
+
+impl GenerateContentResponse {
+    /// 转换为一段回复，请求内容被拦截时返回拦截原因
+    fn chunk(&self) -> Result<StreamChunk> {
+        if let Some(feedback) = &self.prompt_feedback {
+            if let Some(block_reason) = &feedback.block_reason {
+                let info = FinishInfo {
+                    reason: block_reason.clone(),
+                    safety_ratings: feedback.safety_ratings.clone(),
+                };
+                return Err(Error::msg(format!("Prompt Blocked: {}", info)));
+            }
+        }
+        Ok(StreamChunk {
+            text: self.text(),
+            usage: self.usage(),
+            finish: self.finish(),
+            function_calls: self.function_calls(),
+        })
+    }
+
+    /// 拼接第一个候选回复中的所有文本
+    fn text(&self) -> String {
+        self.candidates
+            .first()
+            .and_then(|candidate| candidate.content.as_ref())
+            .map(|content| {
+                content
+                    .parts
+                    .iter()
+                    .filter_map(|part| part.text.clone())
+                    .collect::<String>()
+            })
+            .unwrap_or_default()
+    }
+
+    /// 第一个候选回复中请求调用的所有工具
+    fn function_calls(&self) -> Vec<FunctionCall> {
+        self.candidates
+            .first()
+            .and_then(|candidate| candidate.content.as_ref())
+            .map(|content| {
+                content
+                    .parts
+                    .iter()
+                    .filter_map(|part| part.function_call.clone())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// 第一个候选回复结束的原因与安全评级
+    fn finish(&self) -> Option<FinishInfo> {
+        let candidate = self.candidates.first()?;
+        Some(FinishInfo {
+            reason: candidate.finish_reason.clone()?,
+            safety_ratings: candidate.safety_ratings.clone(),
+        })
+    }
+
+    /// 本次响应中的 token 用量
+    fn usage(&self) -> Option<TokenUsage> {
+        self.usage_metadata.as_ref().map(|usage| TokenUsage {
+            prompt_tokens: usage.prompt_token_count,
+            response_tokens: usage.candidates_token_count,
+        })
+    }
+}
+
+/// 以流式方式发送消息，返回的流中逐段包含回复内容
+///
+/// 不会修改 `gemini.contents`，请求成功完成后需由调用方将用户消息与回复追加到历史记录中
+pub fn send_message_stream(gemini: &Gemini, content: Content, options: &RequestOptions) -> Result<GeminiStream> {
+    let url = format!("{}/{}:streamGenerateContent?alt=sse", GEMINI_API_URL, gemini.model);
+    // 密钥通过请求头传递，避免出现在错误信息中的地址里
+    let response = CLIENT
+        .post(url)
+        .header(API_KEY_HEADER, &gemini.key)
+        .json(&generate_content_request(gemini, content, options))
+        .send()?;
+    Ok(GeminiStream {
+        reader: BufReader::new(check_response(response)?),
+    })
+}
+
+/// 计算发送消息时请求的 token 数量，包含系统指令、全部历史记录与新消息
+pub fn count_tokens(gemini: &Gemini, content: Content, options: &RequestOptions) -> Result<u32> {
+    let mut request = serde_json::to_value(generate_content_request(gemini, content, options))?;
+    request["model"] = Value::String(format!("models/{}", gemini.model));
+    let url = format!("{}/{}:countTokens", GEMINI_API_URL, gemini.model);
+    let response = CLIENT
+        .post(url)
+        .header(API_KEY_HEADER, &gemini.key)
+        .json(&serde_json::json!({ "generateContentRequest": request }))
+        .send()?;
+    Ok(check_response(response)?.json::<CountTokensResponse>()?.total_tokens)
+}
+
+/// 构建生成内容请求体，新消息追加在历史记录之后
+fn generate_content_request(gemini: &Gemini, content: Content, options: &RequestOptions) -> GenerateContentRequest {
+    let contents = gemini.contents.iter().chain([&content]).map(request_content).collect();
+    // 系统指令为空时不传递
+    let system_instruction = gemini
+        .system_instruction
+        .clone()
+        .filter(|instruction| !instruction.is_empty())
+        .map(|instruction| serde_json::json!({ "parts": [{ "text": instruction }] }));
+    GenerateContentRequest {
+        contents,
+        system_instruction,
+        generation_config: generation_config(&gemini.options, options.response_schema.as_ref()),
+        safety_settings: options.safety.settings(),
+        tools: tool_declarations(&options.tools),
+    }
+}
+
+/// 请求失败时返回响应体中的错误信息
+fn check_response(response: Response) -> Result<Response> {
+    if response.status().is_success() {
+        return Ok(response);
+    }
+    let status = response.status();
+    let message = response
+        .json::<ErrorResponse>()
+        .map(|e| e.error.message)
+        .unwrap_or_else(|_| status.to_string());
+    Err(Error::msg(message))
+}
+
+/// 构建用户消息，每一个附件作为一个单独的数据片段附带在文本之后
+pub fn user_content(message: String, image_paths: Vec<String>) -> Result<Content> {
+    let mut parts = vec![Part::Text(message)];
+    for image_path in image_paths {
+        parts.push(read_attachment(image_path)?);
+    }
+    Ok(Content {
+        parts,
+        role: Some(Role::User),
+    })
+}
+
+/// 构建模型回复消息
+pub fn model_content(message: String) -> Content {
+    Content {
+        parts: vec![Part::Text(message)],
+        role: Some(Role::Model),
+    }
+}
+
+/// 通过纯净的 Gemini API 获取对话摘要
+pub fn summary_by_gemini(key: String, message: String) -> String {
+    let mut pure_gemini = Gemini::new(key, LanguageModel::Gemini1_5Flash);
+    pure_gemini.set_system_instruction("请给我概括一下这段文字内容，不包含任意标点符号，不大于15字。".into());
+    if let Ok((s, _)) = pure_gemini.send_simple_message(message) {
+        s
+    } else {
+        "".into()
+    }
+}
+
+/// 转换生成配置，设置页面中未填写的值会被保存为空字符串或 0，这些值不传递给 API
+///
+/// 设置了回复格式约束时一并传递，API 只接受与约束搭配的 `application/json`，此时忽略填写的回复类型
+fn generation_config(options: &GenerationConfig, response_schema: Option<&Value>) -> Value {
+    let mut value = serde_json::to_value(options).unwrap_or_default();
+    if let Value::Object(map) = &mut value {
+        map.retain(|_, v| match v {
+            Value::Null => false,
+            Value::String(s) => !s.is_empty(),
+            Value::Number(n) => n.as_f64() != Some(0.0),
+            _ => true,
+        });
+        if let Some(schema) = response_schema {
+            map.insert("responseSchema".into(), schema.clone());
+            map.insert("responseMimeType".into(), Value::String("application/json".into()));
+        }
+    }
+    value
+}
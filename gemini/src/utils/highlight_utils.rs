@@ -0,0 +1,84 @@
+use std::sync::LazyLock;
+
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::Span,
+};
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{FontStyle, Style as SyntectStyle, Theme, ThemeSet},
+    parsing::{SyntaxReference, SyntaxSet},
+    util::LinesWithEndings,
+};
+
+/// 代码高亮使用的主题
+const THEME_NAME: &str = "base16-ocean.dark";
+
+/// 内置的语法定义
+static SYNTAX_SET: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+
+/// 代码高亮主题
+static THEME: LazyLock<Theme> = LazyLock::new(|| {
+    let mut theme_set = ThemeSet::load_defaults();
+    theme_set.themes.remove(THEME_NAME).unwrap_or_default()
+});
+
+/// 按语言对代码进行高亮，返回每一行代码的文本片段，不包含换行符
+///
+/// 语言为代码块的信息字符串，可以是语言名称或文件扩展名，无法识别时尝试根据第一行内容推断，仍无法识别则不高亮
+pub fn highlight_code(code: &str, language: &str) -> Vec<Vec<Span<'static>>> {
+    let Some(syntax) = find_syntax(code, language) else {
+        return code
+            .lines()
+            .map(|line| vec![Span::styled(line.to_owned(), plain_style())])
+            .collect();
+    };
+    let mut highlighter = HighlightLines::new(syntax, &THEME);
+    LinesWithEndings::from(code)
+        .map(|line| match highlighter.highlight_line(line, &SYNTAX_SET) {
+            Ok(ranges) => ranges
+                .into_iter()
+                .map(|(style, text)| Span::styled(text.trim_end_matches(['\n', '\r']).to_owned(), convert_style(style)))
+                .filter(|span| !span.content.is_empty())
+                .collect(),
+            // 高亮失败时按原文展示
+            Err(_) => vec![Span::styled(
+                line.trim_end_matches(['\n', '\r']).to_owned(),
+                plain_style(),
+            )],
+        })
+        .collect()
+}
+
+/// 查找代码对应的语法定义
+fn find_syntax<'a>(code: &str, language: &str) -> Option<&'a SyntaxReference> {
+    let syntax = if language.is_empty() {
+        None
+    } else {
+        SYNTAX_SET
+            .find_syntax_by_token(language)
+            .or_else(|| SYNTAX_SET.find_syntax_by_extension(language))
+    };
+    syntax.or_else(|| SYNTAX_SET.find_syntax_by_first_line(code.lines().next().unwrap_or_default()))
+}
+
+/// 将高亮主题中的样式转换为终端样式，不使用主题背景色
+fn convert_style(style: SyntectStyle) -> Style {
+    let foreground = style.foreground;
+    let mut result = Style::default().fg(Color::Rgb(foreground.r, foreground.g, foreground.b));
+    if style.font_style.contains(FontStyle::BOLD) {
+        result = result.add_modifier(Modifier::BOLD);
+    }
+    if style.font_style.contains(FontStyle::ITALIC) {
+        result = result.add_modifier(Modifier::ITALIC);
+    }
+    if style.font_style.contains(FontStyle::UNDERLINE) {
+        result = result.add_modifier(Modifier::UNDERLINED);
+    }
+    result
+}
+
+/// 未能识别语言的代码样式
+fn plain_style() -> Style {
+    Style::default().fg(Color::White)
+}
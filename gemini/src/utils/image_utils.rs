@@ -1,19 +1,18 @@
 use anyhow::{bail, Result};
-use gemini_api::utils::image::blocking::get_image_type_and_base64_string;
 use image::codecs::jpeg::JpegEncoder;
 use image::GenericImageView;
 use reqwest::blocking::Client;
-use std::env;
 use std::fs::{create_dir_all, File};
-use std::io::BufWriter;
+use std::io::{BufWriter, Write};
 use std::path::Path;
 use std::sync::LazyLock;
 
+use super::path_utils::cache_file;
+
 /// 保存图片
 pub fn cache_image(url: String, file_name: String) -> Result<()> {
     // 构建文件目录以及名称
-    let exe_path = env::current_exe()?;
-    let output_path = exe_path.parent().unwrap().join("data").join(file_name);
+    let output_path = cache_file(&file_name);
     create_dir_all(output_path.parent().unwrap())?;
     if url.starts_with("https://") || url.starts_with("http://") {
         // 下载网络图片并压缩
@@ -46,19 +45,20 @@ where
     Ok(())
 }
 
-/// 读取图片
-pub fn read_image_cache(file_name: String) -> Result<(String, String)> {
+/// 将附件数据直接写入缓存，用于导入会话
+pub fn save_image_cache(file_name: String, data: &[u8]) -> Result<()> {
     // 构建文件目录以及名称
-    let exe_path = env::current_exe()?;
-    let file_path = exe_path.parent().unwrap().join("data").join(file_name);
-    get_image_type_and_base64_string(file_path.to_str().unwrap_or_default().into())
+    let file_path = cache_file(&file_name);
+    create_dir_all(file_path.parent().unwrap())?;
+    let mut file = File::create(file_path)?;
+    file.write_all(data)?;
+    Ok(())
 }
 
 /// 删除图片
 pub fn delete_image_cache(file_name: String) -> Result<()> {
     // 构建文件目录以及名称
-    let exe_path = env::current_exe()?;
-    let file_path = exe_path.parent().unwrap().join("data").join(file_name);
+    let file_path = cache_file(&file_name);
     if file_path.exists() {
         std::fs::remove_file(file_path)?;
     }
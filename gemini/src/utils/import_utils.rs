@@ -0,0 +1,549 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+use anyhow::{bail, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, Local};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use strum::Display;
+
+use crate::model::{
+    db::{Conversation, MessageRecord},
+    view::{MessageState, Sender},
+};
+use crate::store::ConversationStore;
+
+use super::export_utils::{ExportImage, EXPORT_VERSION};
+
+/// 导入文件的格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+pub enum ImportFormat {
+    /// 本程序导出的 JSON 文档
+    #[strum(to_string = "Gemini JSON")]
+    Transcript,
+    /// ChatGPT 导出的 conversations.json
+    #[strum(to_string = "ChatGPT")]
+    ChatGpt,
+}
+
+/// 单个会话的导入状态
+#[derive(Debug, Clone)]
+pub enum ImportStatus {
+    /// 已导入，携带新会话的 id
+    Imported(String),
+    /// 试运行时将会被导入
+    WouldImport,
+    /// 与已有会话重复，携带已有会话的 id
+    Duplicate(String),
+    /// 导入失败，携带错误信息
+    Failed(String),
+}
+
+/// 单个会话的导入结果
+#[derive(Debug, Clone)]
+pub struct ImportEntry {
+    /// 会话标题
+    pub title: String,
+    /// 聊天记录数量
+    pub record_count: usize,
+    /// 导入状态
+    pub status: ImportStatus,
+}
+
+/// 导入报告
+#[derive(Debug, Clone)]
+pub struct ImportReport {
+    /// 导入文件的格式
+    pub format: ImportFormat,
+    /// 每个会话的导入结果
+    pub entries: Vec<ImportEntry>,
+}
+
+impl ImportReport {
+    /// 已导入或试运行时将会被导入的会话数量
+    pub fn imported_count(&self) -> usize {
+        self.count(|status| matches!(status, ImportStatus::Imported(_) | ImportStatus::WouldImport))
+    }
+
+    /// 重复的会话数量
+    pub fn duplicate_count(&self) -> usize {
+        self.count(|status| matches!(status, ImportStatus::Duplicate(_)))
+    }
+
+    /// 导入失败的会话数量
+    pub fn failed_count(&self) -> usize {
+        self.count(|status| matches!(status, ImportStatus::Failed(_)))
+    }
+
+    fn count<F>(&self, predicate: F) -> usize
+    where
+        F: Fn(&ImportStatus) -> bool,
+    {
+        self.entries.iter().filter(|entry| predicate(&entry.status)).count()
+    }
+}
+
+/// 本程序导出的 JSON 文档
+#[derive(Deserialize)]
+struct TranscriptDocument {
+    /// 文档格式版本
+    version: u32,
+    /// 会话及其聊天记录
+    conversation: Conversation,
+    /// 图片数据，键为图片记录 id
+    #[serde(default)]
+    images: HashMap<String, ExportImage>,
+}
+
+/// 解析后待导入的会话
+struct ImportItem {
+    /// 会话及其聊天记录
+    conversation: Conversation,
+    /// 解码后的图片数据，键为图片记录 id
+    images: HashMap<String, Vec<u8>>,
+}
+
+/// 从文件中导入会话到 `store`，试运行时只检查不写入
+///
+/// 支持本程序导出的 JSON 文档（单个或数组）以及 ChatGPT 导出的 conversations.json
+pub fn import_file<P>(store: &impl ConversationStore, path: P, dry_run: bool) -> Result<ImportReport>
+where
+    P: AsRef<Path>,
+{
+    let content = fs::read_to_string(path)?;
+    let value: Value = serde_json::from_str(&content)?;
+    let (format, items) = parse_import_value(value)?;
+    // 同一文件中的重复会话，使用开始时间与第一条记录内容识别
+    let mut seen = HashSet::new();
+    let mut entries = Vec::new();
+    for item in items {
+        let (title, item) = match item {
+            Ok(item) => (item.conversation.conversation_title.clone(), item),
+            Err((title, e)) => {
+                entries.push(ImportEntry {
+                    title,
+                    record_count: 0,
+                    status: ImportStatus::Failed(e.to_string()),
+                });
+                continue;
+            }
+        };
+        let conversation = &item.conversation;
+        let record_count = conversation.conversation_records.len();
+        let fingerprint = (
+            conversation.conversation_start_time.timestamp(),
+            conversation
+                .conversation_records
+                .first()
+                .map(|record| record.record_content.clone())
+                .unwrap_or_default(),
+        );
+        let status = if record_count == 0 {
+            ImportStatus::Failed("Empty conversation".into())
+        } else if !seen.insert(fingerprint) {
+            ImportStatus::Duplicate(String::new())
+        } else {
+            match store.find_duplicate_conversation(conversation) {
+                Ok(Some(conversation_id)) => ImportStatus::Duplicate(conversation_id),
+                Ok(None) if dry_run => ImportStatus::WouldImport,
+                Ok(None) => match store.import_conversation(conversation, &item.images) {
+                    Ok(conversation_id) => ImportStatus::Imported(conversation_id),
+                    Err(e) => ImportStatus::Failed(e.to_string()),
+                },
+                Err(e) => ImportStatus::Failed(e.to_string()),
+            }
+        };
+        entries.push(ImportEntry {
+            title,
+            record_count,
+            status,
+        });
+    }
+    Ok(ImportReport { format, entries })
+}
+
+/// 单个会话的解析结果，解析失败时携带会话标题与错误信息
+type ParsedItem = std::result::Result<ImportItem, (String, anyhow::Error)>;
+
+/// 识别导入文件的格式并解析其中的所有会话
+fn parse_import_value(value: Value) -> Result<(ImportFormat, Vec<ParsedItem>)> {
+    let values = match value {
+        Value::Array(values) => values,
+        value => vec![value],
+    };
+    let format = match values.first() {
+        Some(value) if value.get("mapping").is_some() => ImportFormat::ChatGpt,
+        Some(value) if value.get("conversation").is_some() => ImportFormat::Transcript,
+        Some(_) => bail!("Unrecognized import file format"),
+        None => bail!("No conversation found in import file"),
+    };
+    let items = values
+        .into_iter()
+        .map(|value| {
+            let title = value
+                .get("title")
+                .or_else(|| value.get("conversation").and_then(|c| c.get("conversation_title")))
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_owned();
+            let item = match format {
+                ImportFormat::Transcript => parse_transcript(value),
+                ImportFormat::ChatGpt => parse_chatgpt(value),
+            };
+            item.map_err(|e| (title, e))
+        })
+        .collect();
+    Ok((format, items))
+}
+
+/// 解析本程序导出的 JSON 文档
+fn parse_transcript(value: Value) -> Result<ImportItem> {
+    let document: TranscriptDocument = serde_json::from_value(value)?;
+    if document.version > EXPORT_VERSION {
+        bail!("Unsupported document version {}", document.version);
+    }
+    let mut images = HashMap::new();
+    for (image_record_id, image) in document.images {
+        images.insert(image_record_id, STANDARD.decode(image.data)?);
+    }
+    Ok(ImportItem {
+        conversation: document.conversation,
+        images,
+    })
+}
+
+/// 解析 ChatGPT 导出的单个会话，只保留当前分支上用户与助手的文本消息
+fn parse_chatgpt(value: Value) -> Result<ImportItem> {
+    let Some(mapping) = value.get("mapping").and_then(Value::as_object) else {
+        bail!("Missing mapping");
+    };
+    let create_time = value.get("create_time").and_then(timestamp_to_local);
+    let update_time = value.get("update_time").and_then(timestamp_to_local);
+    // 从当前节点开始沿父节点向上查找，得到当前分支上的所有节点
+    let mut node_id = value
+        .get("current_node")
+        .and_then(Value::as_str)
+        .or_else(|| latest_leaf_node(mapping));
+    let mut nodes = Vec::new();
+    while let Some(id) = node_id {
+        // 避免错误数据导致的循环引用
+        if nodes.len() >= mapping.len() {
+            break;
+        }
+        let Some(node) = mapping.get(id) else {
+            break;
+        };
+        nodes.push(node);
+        node_id = node.get("parent").and_then(Value::as_str);
+    }
+    nodes.reverse();
+
+    let mut records: Vec<MessageRecord> = Vec::new();
+    for node in nodes {
+        let Some(message) = node.get("message").filter(|message| !message.is_null()) else {
+            continue;
+        };
+        let record_sender = match message.pointer("/author/role").and_then(Value::as_str) {
+            Some("user") => Sender::User(Vec::new()),
+            Some("assistant") => Sender::Bot,
+            _ => continue,
+        };
+        // 跳过界面上隐藏的消息
+        if message.pointer("/metadata/is_visually_hidden_from_conversation") == Some(&Value::Bool(true)) {
+            continue;
+        }
+        let text = chatgpt_message_text(message);
+        if text.trim().is_empty() {
+            continue;
+        }
+        let record_time = message
+            .get("create_time")
+            .and_then(timestamp_to_local)
+            .or(records.last().map(|record| record.record_time))
+            .or(create_time)
+            .unwrap_or_else(Local::now);
+        // 同一发送者的连续消息合并为一条记录，保证用户与回复交替出现
+        if let Some(last) = records.last_mut() {
+            if matches!(
+                (&last.record_sender, &record_sender),
+                (Sender::User(_), Sender::User(_)) | (Sender::Bot, Sender::Bot)
+            ) {
+                last.record_content.push_str("\n\n");
+                last.record_content.push_str(&text);
+                continue;
+            }
+        }
+        let record_id = message
+            .get("id")
+            .and_then(Value::as_str)
+            .map(str::to_owned)
+            .unwrap_or_else(|| records.len().to_string());
+        records.push(MessageRecord {
+            record_id,
+            conversation_id: String::new(),
+            record_content: text,
+            record_time,
+            record_sender,
+            sort_index: records.len() as u32,
+            record_state: MessageState::Complete,
+            image_records: Vec::new(),
+            record_versions: Vec::new(),
+            parent_record_id: records.last().map(|record| record.record_id.clone()),
+            sibling_record_ids: Vec::new(),
+            token_usage: None,
+            finish_info: None,
+            response_schema: None,
+            model: None,
+            pinned: false,
+        });
+    }
+
+    let conversation_start_time = create_time
+        .or(records.first().map(|record| record.record_time))
+        .unwrap_or_else(Local::now);
+    let conversation_modify_time = update_time
+        .or(records.last().map(|record| record.record_time))
+        .unwrap_or(conversation_start_time);
+    Ok(ImportItem {
+        conversation: Conversation {
+            conversation_title: value
+                .get("title")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_owned(),
+            conversation_start_time,
+            conversation_modify_time,
+            active_record_id: records.last().map(|record| record.record_id.clone()),
+            conversation_records: records,
+            ..Default::default()
+        },
+        images: HashMap::new(),
+    })
+}
+
+/// 拼接 ChatGPT 消息中的文本内容，忽略图片等非文本内容
+fn chatgpt_message_text(message: &Value) -> String {
+    let content_type = message.pointer("/content/content_type").and_then(Value::as_str);
+    if !matches!(content_type, Some("text" | "multimodal_text")) {
+        return String::new();
+    }
+    message
+        .pointer("/content/parts")
+        .and_then(Value::as_array)
+        .map(|parts| parts.iter().filter_map(Value::as_str).collect::<Vec<_>>().join("\n"))
+        .unwrap_or_default()
+}
+
+/// 没有当前节点时，使用最新的叶子节点
+fn latest_leaf_node(mapping: &Map<String, Value>) -> Option<&str> {
+    mapping
+        .iter()
+        .filter(|(_, node)| {
+            !matches!(node.get("children").and_then(Value::as_array), Some(children) if !children.is_empty())
+        })
+        .max_by(|(_, a), (_, b)| {
+            let a = a
+                .pointer("/message/create_time")
+                .and_then(Value::as_f64)
+                .unwrap_or_default();
+            let b = b
+                .pointer("/message/create_time")
+                .and_then(Value::as_f64)
+                .unwrap_or_default();
+            a.total_cmp(&b)
+        })
+        .map(|(id, _)| id.as_str())
+}
+
+/// 将以秒为单位的时间戳转换为本地时间
+fn timestamp_to_local(value: &Value) -> Option<DateTime<Local>> {
+    let timestamp = value.as_f64()?;
+    let secs = timestamp.floor();
+    let nanos = ((timestamp - secs) * 1_000_000_000.0) as u32;
+    DateTime::from_timestamp(secs as i64, nanos).map(|date_time| date_time.with_timezone(&Local))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::store::memory_store::MemoryStore;
+
+    fn node(id: &str, parent: Option<&str>, role: &str, content_type: &str, text: &str, time: f64) -> Value {
+        json!({
+            "id": id,
+            "parent": parent,
+            "message": {
+                "id": id,
+                "author": { "role": role },
+                "create_time": time,
+                "content": { "content_type": content_type, "parts": [text] }
+            }
+        })
+    }
+
+    fn chatgpt(title: &str, create_time: f64, first_message: &str) -> Value {
+        json!({
+            "title": title,
+            "create_time": create_time,
+            "current_node": "a",
+            "mapping": {
+                "u": node("u", None, "user", "text", first_message, create_time),
+                "a": node("a", Some("u"), "assistant", "text", "reply", create_time + 1.0)
+            }
+        })
+    }
+
+    fn contents(item: &ImportItem) -> Vec<(&str, Option<&str>, &str)> {
+        item.conversation
+            .conversation_records
+            .iter()
+            .map(|record| {
+                (
+                    record.record_id.as_str(),
+                    record.parent_record_id.as_deref(),
+                    record.record_content.as_str(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn chatgpt_keeps_current_branch() {
+        let mut hidden = node("n6", Some("n5"), "assistant", "text", "hidden", 6.0);
+        hidden["message"]["metadata"] = json!({ "is_visually_hidden_from_conversation": true });
+        let value = json!({
+            "title": "Trip",
+            "create_time": 1.0,
+            "update_time": 9.0,
+            "current_node": "n7",
+            "mapping": {
+                "root": { "id": "root", "parent": null, "message": null, "children": ["n1"] },
+                "n1": node("n1", Some("root"), "user", "text", "hi", 1.0),
+                "n2": node("n2", Some("n1"), "system", "text", "be brief", 2.0),
+                "n3": node("n3", Some("n2"), "assistant", "text", "part 1", 3.0),
+                "n3b": node("n3b", Some("n2"), "assistant", "text", "other branch", 3.5),
+                "n4": node("n4", Some("n3"), "assistant", "multimodal_text", "part 2", 4.0),
+                "n5": node("n5", Some("n4"), "assistant", "image_asset_pointer", "", 5.0),
+                "n6": hidden,
+                "n7": node("n7", Some("n6"), "user", "text", "next", 7.0)
+            }
+        });
+        let item = parse_chatgpt(value).unwrap();
+        // 跳过系统消息、隐藏消息与非文本消息，连续的回复合并为一条记录
+        assert_eq!(
+            contents(&item),
+            [
+                ("n1", None, "hi"),
+                ("n3", Some("n1"), "part 1\n\npart 2"),
+                ("n7", Some("n3"), "next")
+            ]
+        );
+        let conversation = &item.conversation;
+        assert_eq!(conversation.conversation_title, "Trip");
+        assert_eq!(conversation.active_record_id.as_deref(), Some("n7"));
+        assert_eq!(conversation.conversation_start_time.timestamp(), 1);
+        assert_eq!(conversation.conversation_modify_time.timestamp(), 9);
+        assert!(matches!(
+            conversation.conversation_records[1].record_sender,
+            Sender::Bot
+        ));
+    }
+
+    #[test]
+    fn chatgpt_uses_latest_leaf_without_current_node() {
+        let mut user = node("u", None, "user", "text", "question", 1.0);
+        user["children"] = json!(["old", "new"]);
+        let value = json!({
+            "mapping": {
+                "u": user,
+                "old": node("old", Some("u"), "assistant", "text", "old answer", 2.0),
+                "new": node("new", Some("u"), "assistant", "text", "new answer", 3.0)
+            }
+        });
+        let item = parse_chatgpt(value).unwrap();
+        assert_eq!(
+            contents(&item),
+            [("u", None, "question"), ("new", Some("u"), "new answer")]
+        );
+    }
+
+    #[test]
+    fn chatgpt_breaks_cycles() {
+        let value = json!({
+            "current_node": "a",
+            "mapping": {
+                "q": node("q", Some("a"), "user", "text", "question", 1.0),
+                "a": node("a", Some("q"), "assistant", "text", "answer", 2.0)
+            }
+        });
+        assert_eq!(parse_chatgpt(value).unwrap().conversation.conversation_records.len(), 2);
+    }
+
+    #[test]
+    fn transcript_version() {
+        let document = |version: u32| {
+            json!({
+                "version": version,
+                "conversation": { "conversation_title": "Notes" },
+                "images": { "img": { "mime_type": "image/png", "data": STANDARD.encode([1, 2, 3]) } }
+            })
+        };
+        let item = parse_transcript(document(EXPORT_VERSION)).unwrap();
+        assert_eq!(item.conversation.conversation_title, "Notes");
+        assert_eq!(item.images["img"], [1, 2, 3]);
+        let error = parse_transcript(document(EXPORT_VERSION + 1)).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            format!("Unsupported document version {}", EXPORT_VERSION + 1)
+        );
+        assert!(parse_import_value(json!({ "messages": [] })).is_err());
+    }
+
+    #[test]
+    fn dry_run_reports_duplicates() {
+        let store = MemoryStore::default();
+        let existing = parse_chatgpt(chatgpt("Existing", 200.0, "bye")).unwrap();
+        let existing_id = store
+            .import_conversation(&existing.conversation, &existing.images)
+            .unwrap();
+        let path = env::temp_dir().join(format!("gemini-import-test-{}.json", process::id()));
+        let content = json!([
+            chatgpt("New", 100.0, "hello"),
+            chatgpt("New Again", 100.0, "hello"),
+            chatgpt("Existing", 200.0, "bye"),
+            { "title": "Empty", "mapping": {} }
+        ]);
+        fs::write(&path, content.to_string()).unwrap();
+        let report = import_file(&store, &path, true);
+        fs::remove_file(&path).unwrap();
+        let report = report.unwrap();
+        assert_eq!(report.format, ImportFormat::ChatGpt);
+        let statuses: Vec<String> = report
+            .entries
+            .iter()
+            .map(|entry| format!("{}: {:?}", entry.title, entry.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            [
+                "New: WouldImport".to_owned(),
+                "New Again: Duplicate(\"\")".to_owned(),
+                format!("Existing: Duplicate({:?})", existing_id),
+                "Empty: Failed(\"Empty conversation\")".to_owned(),
+            ]
+        );
+        assert_eq!(report.imported_count(), 1);
+        assert_eq!(report.duplicate_count(), 2);
+        assert_eq!(report.failed_count(), 1);
+        // 试运行不写入存储
+        assert_eq!(store.list().unwrap().len(), 1);
+    }
+}
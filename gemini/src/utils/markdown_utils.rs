@@ -0,0 +1,633 @@
+use pulldown_cmark::{Alignment, CodeBlockKind, Event, HeadingLevel, LinkType, Options, Parser, Tag, TagEnd};
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span, Text},
+};
+use unicode_width::UnicodeWidthChar;
+
+use super::highlight_utils::highlight_code;
+
+/// 内容区域的最小宽度，避免宽度过小时无法排版
+const MIN_CONTENT_WIDTH: usize = 4;
+
+/// 制表符替换为的空格
+const TAB_SPACES: &str = "    ";
+
+/// 将 Markdown 文本渲染为终端中展示的文本，渲染后每一行的宽度都不超过 `width`
+pub fn render_markdown(markdown: &str, width: usize) -> Text<'static> {
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+    let mut renderer = MarkdownRenderer::new(width);
+    for event in Parser::new_ext(markdown, options) {
+        renderer.handle_event(event);
+    }
+    renderer.finish()
+}
+
+/// 提取 Markdown 文本中所有代码块的内容
+pub fn code_blocks(markdown: &str) -> Vec<String> {
+    let mut code_blocks = Vec::new();
+    let mut current: Option<String> = None;
+    for event in Parser::new_ext(markdown, Options::empty()) {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => current = Some(String::new()),
+            Event::Text(text) => {
+                if let Some(code) = current.as_mut() {
+                    code.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                if let Some(code) = current.take() {
+                    code_blocks.push(code);
+                }
+            }
+            _ => {}
+        }
+    }
+    code_blocks
+}
+
+/// 按显示宽度对文本片段进行换行，保留每一段文本的样式
+///
+/// `word_wrap` 为真时优先在空格处换行，并去除换行后行首的空格；否则按字符换行，保留所有空白
+pub(crate) fn wrap_spans(spans: Vec<Span<'static>>, width: usize, word_wrap: bool) -> Vec<Vec<Span<'static>>> {
+    let width = width.max(1);
+    let chars_width = |chars: &[(char, Style)]| chars.iter().map(|(c, _)| c.width().unwrap_or_default()).sum();
+    let mut lines: Vec<Vec<(char, Style)>> = Vec::new();
+    let mut line: Vec<(char, Style)> = Vec::new();
+    let mut line_width = 0;
+    // 当前行中最后一个可以换行的位置
+    let mut break_at = None;
+    for span in spans.iter() {
+        for c in span.content.chars() {
+            let char_width = c.width().unwrap_or_default();
+            // 当前行已放不下该字符，则换行
+            if line_width + char_width > width && !line.is_empty() {
+                let rest = match break_at.take() {
+                    Some(index) if word_wrap => line.split_off(index),
+                    _ => Vec::new(),
+                };
+                lines.push(std::mem::replace(&mut line, rest));
+                line_width = chars_width(&line);
+                // 单词本身超出宽度，则按字符换行
+                if line_width + char_width > width && !line.is_empty() {
+                    lines.push(std::mem::take(&mut line));
+                    line_width = 0;
+                }
+            }
+            if word_wrap && c == ' ' && line.is_empty() && !lines.is_empty() {
+                continue;
+            }
+            line.push((c, span.style));
+            line_width += char_width;
+            if c == ' ' {
+                break_at = Some(line.len());
+            }
+        }
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    // 将相同样式的连续字符合并为一个文本片段
+    lines
+        .into_iter()
+        .map(|line| {
+            let mut spans: Vec<Span<'static>> = Vec::new();
+            for (c, style) in line {
+                match spans.last_mut() {
+                    Some(span) if span.style == style => span.content.to_mut().push(c),
+                    _ => spans.push(Span::styled(c.to_string(), style)),
+                }
+            }
+            spans
+        })
+        .collect()
+}
+
+/// 计算文本片段的显示宽度
+pub(crate) fn spans_width(spans: &[Span]) -> usize {
+    spans.iter().map(|span| span.width()).sum()
+}
+
+/// 正在解析的代码块
+struct CodeBlock {
+    /// 代码语言，取自代码块的信息字符串
+    language: String,
+    /// 代码内容
+    code: String,
+}
+
+/// 正在解析的表格
+struct Table {
+    /// 每一列的对齐方式
+    alignments: Vec<Alignment>,
+    /// 所有行，每一行包含多个单元格，每个单元格包含多个文本片段
+    rows: Vec<Vec<Vec<Span<'static>>>>,
+    /// 表头行数
+    header_rows: usize,
+}
+
+/// Markdown 渲染器，逐个处理解析事件，生成带样式的文本行
+struct MarkdownRenderer {
+    /// 可用宽度
+    width: usize,
+    /// 已生成的文本行
+    lines: Vec<Line<'static>>,
+    /// 当前行中尚未输出的文本片段
+    spans: Vec<Span<'static>>,
+    /// 行内样式栈
+    styles: Vec<Style>,
+    /// 列表栈，有序列表记录下一个列表项的序号，无序列表为空
+    lists: Vec<Option<u64>>,
+    /// 每一层列表项内容的缩进宽度
+    item_indents: Vec<usize>,
+    /// 下一行输出时使用的列表项标记
+    item_marker: Option<String>,
+    /// 引用块层级
+    quote_depth: usize,
+    /// 下一个块开始前是否需要插入空行
+    pending_blank: bool,
+    /// 正在解析的代码块
+    code_block: Option<CodeBlock>,
+    /// 正在解析的表格
+    table: Option<Table>,
+    /// 正在解析的链接地址，自动链接为空
+    link_url: Option<String>,
+    /// 已渲染的代码块数量，用于给代码块编号
+    code_block_count: usize,
+}
+
+impl MarkdownRenderer {
+    fn new(width: usize) -> Self {
+        Self {
+            width: width.max(MIN_CONTENT_WIDTH),
+            lines: Vec::new(),
+            spans: Vec::new(),
+            styles: Vec::new(),
+            lists: Vec::new(),
+            item_indents: Vec::new(),
+            item_marker: None,
+            quote_depth: 0,
+            pending_blank: false,
+            code_block: None,
+            table: None,
+            link_url: None,
+            code_block_count: 0,
+        }
+    }
+
+    /// 结束渲染，输出所有文本行
+    fn finish(mut self) -> Text<'static> {
+        self.flush_line();
+        Text::from(self.lines)
+    }
+
+    /// 处理一个解析事件
+    fn handle_event(&mut self, event: Event) {
+        match event {
+            Event::Start(tag) => self.start_tag(tag),
+            Event::End(tag) => self.end_tag(tag),
+            Event::Text(text) => self.push_text(&text),
+            Event::Code(code) => self.push_span(Span::styled(code.to_string(), inline_code_style())),
+            Event::Html(html) | Event::InlineHtml(html) => self.push_text(&html),
+            Event::FootnoteReference(name) => self.push_text(&format!("[^{}]", name)),
+            // 聊天中的单个换行通常是有意为之，因此软换行也按换行处理，表格单元格中除外
+            Event::SoftBreak | Event::HardBreak => {
+                if self.table.is_some() {
+                    self.push_text(" ");
+                } else {
+                    self.flush_line();
+                }
+            }
+            Event::Rule => {
+                self.start_block();
+                let width = self.content_width();
+                self.push_line(vec![Span::styled("─".repeat(width), border_style())]);
+                self.end_block();
+            }
+            Event::TaskListMarker(checked) => self.push_text(if checked { "[x] " } else { "[ ] " }),
+            _ => {}
+        }
+    }
+
+    /// 处理开始标签
+    fn start_tag(&mut self, tag: Tag) {
+        match tag {
+            Tag::Paragraph => self.start_block(),
+            Tag::Heading { level, .. } => {
+                self.start_block();
+                self.styles.push(heading_style(level));
+            }
+            Tag::BlockQuote(_) => {
+                self.start_block();
+                self.quote_depth += 1;
+                self.styles
+                    .push(Style::default().fg(Color::Gray).add_modifier(Modifier::ITALIC));
+            }
+            Tag::CodeBlock(kind) => {
+                self.start_block();
+                let language = match kind {
+                    CodeBlockKind::Fenced(info) => info.split_whitespace().next().unwrap_or_default().to_owned(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                self.code_block = Some(CodeBlock {
+                    language,
+                    code: String::new(),
+                });
+            }
+            Tag::List(start) => {
+                // 嵌套列表需要先输出父列表项中的文本
+                if self.lists.is_empty() {
+                    self.start_block();
+                } else {
+                    self.flush_line();
+                }
+                self.lists.push(start);
+            }
+            Tag::Item => {
+                self.flush_line();
+                let marker = match self.lists.last_mut() {
+                    Some(Some(index)) => {
+                        let marker = format!("{}. ", index);
+                        *index += 1;
+                        marker
+                    }
+                    _ => "• ".into(),
+                };
+                self.item_indents.push(Span::raw(marker.as_str()).width());
+                self.item_marker = Some(marker);
+            }
+            Tag::Table(alignments) => {
+                self.start_block();
+                self.table = Some(Table {
+                    alignments,
+                    rows: Vec::new(),
+                    header_rows: 0,
+                });
+            }
+            Tag::TableHead | Tag::TableRow => {
+                if let Some(table) = self.table.as_mut() {
+                    table.rows.push(Vec::new());
+                }
+            }
+            Tag::TableCell => {
+                if let Some(row) = self.table.as_mut().and_then(|table| table.rows.last_mut()) {
+                    row.push(Vec::new());
+                }
+            }
+            Tag::Emphasis => self.styles.push(Style::default().add_modifier(Modifier::ITALIC)),
+            Tag::Strong => self.styles.push(Style::default().add_modifier(Modifier::BOLD)),
+            Tag::Strikethrough => self.styles.push(Style::default().add_modifier(Modifier::CROSSED_OUT)),
+            Tag::Link {
+                link_type, dest_url, ..
+            } => {
+                self.styles.push(link_style());
+                // 自动链接的文本即为链接地址，无需重复展示
+                self.link_url = match link_type {
+                    LinkType::Autolink | LinkType::Email => None,
+                    _ => Some(dest_url.to_string()),
+                };
+            }
+            Tag::Image { .. } => self.styles.push(link_style()),
+            _ => {}
+        }
+    }
+
+    /// 处理结束标签
+    fn end_tag(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Paragraph => {
+                self.flush_line();
+                self.end_block();
+            }
+            TagEnd::Heading(_) => {
+                self.styles.pop();
+                self.flush_line();
+                self.end_block();
+            }
+            TagEnd::BlockQuote(_) => {
+                self.flush_line();
+                self.styles.pop();
+                self.quote_depth = self.quote_depth.saturating_sub(1);
+                self.end_block();
+            }
+            TagEnd::CodeBlock => {
+                if let Some(code_block) = self.code_block.take() {
+                    self.render_code_block(code_block);
+                }
+                self.end_block();
+            }
+            TagEnd::List(_) => {
+                self.flush_line();
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.end_block();
+                }
+            }
+            TagEnd::Item => {
+                self.flush_line();
+                // 空的列表项也需要输出列表项标记
+                if self.item_marker.is_some() {
+                    self.push_line(Vec::new());
+                }
+                self.item_indents.pop();
+            }
+            TagEnd::TableHead => {
+                if let Some(table) = self.table.as_mut() {
+                    table.header_rows = table.rows.len();
+                }
+            }
+            TagEnd::Table => {
+                if let Some(table) = self.table.take() {
+                    self.render_table(table);
+                }
+                self.end_block();
+            }
+            TagEnd::Emphasis | TagEnd::Strong | TagEnd::Strikethrough | TagEnd::Image => {
+                self.styles.pop();
+            }
+            TagEnd::Link => {
+                self.styles.pop();
+                if let Some(url) = self.link_url.take().filter(|url| !url.is_empty()) {
+                    self.push_span(Span::styled(format!(" ({})", url), border_style()));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// 当前行内样式
+    fn current_style(&self) -> Style {
+        self.styles
+            .iter()
+            .fold(Style::default(), |style, current| style.patch(*current))
+    }
+
+    /// 追加文本，文本中的换行符将结束当前行
+    fn push_text(&mut self, text: &str) {
+        if let Some(code_block) = self.code_block.as_mut() {
+            code_block.code.push_str(text);
+            return;
+        }
+        let style = self.current_style();
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                self.flush_line();
+            }
+            if !line.is_empty() {
+                self.push_span(Span::styled(line.replace('\t', TAB_SPACES), style));
+            }
+        }
+    }
+
+    /// 追加文本片段，处于表格中时追加到当前单元格
+    fn push_span(&mut self, span: Span<'static>) {
+        let cell = self
+            .table
+            .as_mut()
+            .and_then(|table| table.rows.last_mut())
+            .and_then(|row| row.last_mut());
+        match cell {
+            Some(cell) => cell.push(span),
+            None => self.spans.push(span),
+        }
+    }
+
+    /// 开始一个新的块，如有需要则与上一个块之间插入空行
+    fn start_block(&mut self) {
+        self.flush_line();
+        if self.pending_blank && !self.lines.is_empty() {
+            self.lines.push(Line::from(self.quote_prefix()));
+        }
+        self.pending_blank = false;
+    }
+
+    /// 结束当前块，列表中的块之间不插入空行
+    fn end_block(&mut self) {
+        if self.lists.is_empty() {
+            self.pending_blank = true;
+        }
+    }
+
+    /// 引用块前缀
+    fn quote_prefix(&self) -> Vec<Span<'static>> {
+        (0..self.quote_depth)
+            .map(|_| Span::styled("│ ", border_style()))
+            .collect()
+    }
+
+    /// 计算行前缀，列表项的第一行使用列表项标记，其余行使用等宽的空白
+    fn line_prefix(&mut self) -> (Vec<Span<'static>>, Vec<Span<'static>>) {
+        let mut first = self.quote_prefix();
+        let mut rest = first.clone();
+        let indent: usize = self.item_indents.iter().sum();
+        rest.push(Span::raw(" ".repeat(indent)));
+        match self.item_marker.take() {
+            Some(marker) => {
+                let marker_width = Span::raw(marker.as_str()).width();
+                first.push(Span::raw(" ".repeat(indent.saturating_sub(marker_width))));
+                first.push(Span::styled(marker, Style::default().fg(Color::LightBlue)));
+            }
+            None => first = rest.clone(),
+        }
+        (first, rest)
+    }
+
+    /// 去除前缀后的内容宽度
+    fn content_width(&self) -> usize {
+        let prefix_width = self.quote_depth * 2 + self.item_indents.iter().sum::<usize>();
+        self.width.saturating_sub(prefix_width).max(MIN_CONTENT_WIDTH)
+    }
+
+    /// 输出当前行，超出宽度的内容自动换行
+    fn flush_line(&mut self) {
+        if self.spans.is_empty() {
+            return;
+        }
+        let spans = std::mem::take(&mut self.spans);
+        let width = self.content_width();
+        let (first, rest) = self.line_prefix();
+        for (i, line) in wrap_spans(spans, width, true).into_iter().enumerate() {
+            let mut prefix = if i == 0 { first.clone() } else { rest.clone() };
+            prefix.extend(line);
+            self.lines.push(Line::from(prefix));
+        }
+    }
+
+    /// 直接输出一行，调用方需保证该行宽度不超过内容宽度
+    fn push_line(&mut self, spans: Vec<Span<'static>>) {
+        let (mut prefix, _) = self.line_prefix();
+        prefix.extend(spans);
+        self.lines.push(Line::from(prefix));
+    }
+
+    /// 渲染代码块，使用边框与正文区分
+    fn render_code_block(&mut self, code_block: CodeBlock) {
+        let width = self.content_width();
+        let border = border_style();
+        // 顶部边框，包含代码块编号与代码语言，编号用于复制指定的代码块
+        self.code_block_count += 1;
+        let mut top = vec![
+            Span::styled("┌─", border),
+            Span::styled(format!(" [{}]", self.code_block_count), border),
+        ];
+        if !code_block.language.is_empty() {
+            top.push(Span::styled(
+                format!(" {}", code_block.language),
+                Style::default().fg(Color::LightBlue),
+            ));
+        }
+        top.push(Span::styled(" ", border));
+        let top_width = spans_width(&top);
+        top.push(Span::styled("─".repeat(width.saturating_sub(top_width)), border));
+        self.push_line(top);
+        // 代码内容，带有行号，超出宽度的行按字符换行，续行与代码对齐，不影响缩进
+        let code = code_block.code.strip_suffix('\n').unwrap_or(&code_block.code);
+        let lines = highlight_code(code, &code_block.language);
+        let number_width = lines.len().to_string().len();
+        // 左边框、行号以及行号与代码之间的空格
+        let code_width = width.saturating_sub(number_width + 3);
+        for (i, line) in lines.into_iter().enumerate() {
+            let line = line
+                .into_iter()
+                .map(|span| Span::styled(span.content.replace('\t', TAB_SPACES), span.style))
+                .collect();
+            for (j, wrapped) in wrap_spans(line, code_width, false).into_iter().enumerate() {
+                let number = if j == 0 {
+                    format!("{:>width$} ", i + 1, width = number_width)
+                } else {
+                    " ".repeat(number_width + 1)
+                };
+                let mut spans = vec![Span::styled("│ ", border), Span::styled(number, border)];
+                spans.extend(wrapped);
+                self.push_line(spans);
+            }
+        }
+        // 底部边框
+        self.push_line(vec![Span::styled(
+            format!("└{}", "─".repeat(width.saturating_sub(1))),
+            border,
+        )]);
+    }
+
+    /// 渲染表格，按列对齐，总宽度超出时截断最宽的列
+    fn render_table(&mut self, table: Table) {
+        let columns = table.rows.iter().map(Vec::len).max().unwrap_or_default();
+        if columns == 0 {
+            return;
+        }
+        let mut widths = vec![1; columns];
+        for row in table.rows.iter() {
+            for (i, cell) in row.iter().enumerate() {
+                widths[i] = widths[i].max(spans_width(cell));
+            }
+        }
+        // 每一列左右各一个空格以及一条竖线，再加上最右侧的竖线
+        let available = self.content_width();
+        let total = |widths: &[usize]| widths.iter().sum::<usize>() + columns * 3 + 1;
+        while total(&widths) > available {
+            let Some((i, width)) = widths.iter().enumerate().max_by_key(|(_, width)| **width) else {
+                break;
+            };
+            if *width <= 1 {
+                break;
+            }
+            widths[i] -= 1;
+        }
+        let border = border_style();
+        let separator = |left: &str, middle: &str, right: &str| {
+            let inner = widths
+                .iter()
+                .map(|width| "─".repeat(width + 2))
+                .collect::<Vec<String>>()
+                .join(middle);
+            vec![Span::styled(format!("{}{}{}", left, inner, right), border)]
+        };
+        self.push_line(separator("┌", "┬", "┐"));
+        for (row_index, row) in table.rows.iter().enumerate() {
+            let mut spans = vec![Span::styled("│", border)];
+            for (i, width) in widths.iter().enumerate() {
+                let cell = row.get(i).cloned().unwrap_or_default();
+                let mut cell = truncate_spans(cell, *width);
+                // 表头加粗
+                if row_index < table.header_rows {
+                    cell = cell
+                        .into_iter()
+                        .map(|span| span.patch_style(Style::default().add_modifier(Modifier::BOLD)))
+                        .collect();
+                }
+                let padding = width - spans_width(&cell);
+                let (left, right) = match table.alignments.get(i) {
+                    Some(Alignment::Right) => (padding, 0),
+                    Some(Alignment::Center) => (padding / 2, padding - padding / 2),
+                    _ => (0, padding),
+                };
+                spans.push(Span::raw(" ".repeat(left + 1)));
+                spans.extend(cell);
+                spans.push(Span::raw(" ".repeat(right + 1)));
+                spans.push(Span::styled("│", border));
+            }
+            self.push_line(spans);
+            if row_index + 1 == table.header_rows {
+                self.push_line(separator("├", "┼", "┤"));
+            }
+        }
+        self.push_line(separator("└", "┴", "┘"));
+    }
+}
+
+/// 截断文本片段，使其宽度不超过 `width`，被截断时以省略号结尾
+fn truncate_spans(spans: Vec<Span<'static>>, width: usize) -> Vec<Span<'static>> {
+    if spans_width(&spans) <= width {
+        return spans;
+    }
+    let mut result = Vec::new();
+    let mut result_width = 0;
+    // 预留省略号的宽度
+    let limit = width.saturating_sub(1);
+    'outer: for span in spans {
+        let mut content = String::new();
+        for c in span.content.chars() {
+            let char_width = c.width().unwrap_or_default();
+            if result_width + char_width > limit {
+                if !content.is_empty() {
+                    result.push(Span::styled(content, span.style));
+                }
+                break 'outer;
+            }
+            content.push(c);
+            result_width += char_width;
+        }
+        result.push(Span::styled(content, span.style));
+    }
+    result.push(Span::styled("…", border_style()));
+    // 宽字符可能导致截断后宽度不足，补齐空格
+    let result_width = spans_width(&result);
+    if result_width < width {
+        result.push(Span::raw(" ".repeat(width - result_width)));
+    }
+    result
+}
+
+/// 标题样式
+fn heading_style(level: HeadingLevel) -> Style {
+    let style = Style::default().fg(Color::LightMagenta).add_modifier(Modifier::BOLD);
+    match level {
+        HeadingLevel::H1 => style.add_modifier(Modifier::UNDERLINED),
+        _ => style,
+    }
+}
+
+/// 行内代码样式
+fn inline_code_style() -> Style {
+    Style::default().fg(Color::LightRed)
+}
+
+/// 链接样式
+fn link_style() -> Style {
+    Style::default().fg(Color::LightBlue).add_modifier(Modifier::UNDERLINED)
+}
+
+/// 边框与装饰符号样式
+fn border_style() -> Style {
+    Style::default().fg(Color::DarkGray)
+}
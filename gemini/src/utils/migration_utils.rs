@@ -0,0 +1,338 @@
+use std::{
+    collections::HashSet,
+    fs::{create_dir_all, remove_file},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use rusqlite::{params, Connection};
+
+/// 记录已执行迁移脚本的表名
+const MIGRATION_TABLE: &str = "schema_migrations";
+
+/// 数据库迁移脚本
+pub(crate) struct Migration {
+    /// 版本，与脚本文件名相同
+    version: &'static str,
+    /// 迁移脚本
+    sql: &'static str,
+    /// 判断没有迁移记录的旧数据库是否已经执行过此脚本，查询结果大于 0 时视为已执行
+    probe: &'static str,
+}
+
+/// 数据库迁移脚本，按执行顺序排列，已发布的脚本不能再修改
+pub(crate) static MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: "20240929_create",
+        sql: include_str!("../../migrations/20240929_create.sql"),
+        probe: r#"SELECT count(*) FROM sqlite_master WHERE type = 'table' AND name = 'gemini_conversation'"#,
+    },
+    Migration {
+        version: "20241025_add_index",
+        sql: include_str!("../../migrations/20241025_add_index.sql"),
+        probe: r#"SELECT count(*) FROM sqlite_master WHERE type = 'index' AND name = 'idx_gemini_conversation_modify_time'"#,
+    },
+    Migration {
+        version: "20261017_add_record_state",
+        sql: include_str!("../../migrations/20261017_add_record_state.sql"),
+        probe: r#"SELECT count(*) FROM pragma_table_info('gemini_message_record') WHERE name = 'record_state'"#,
+    },
+    Migration {
+        version: "20261017_add_message_version",
+        sql: include_str!("../../migrations/20261017_add_message_version.sql"),
+        probe: r#"SELECT count(*) FROM sqlite_master WHERE type = 'table' AND name = 'gemini_message_version'"#,
+    },
+    Migration {
+        version: "20261017_add_message_tree",
+        sql: include_str!("../../migrations/20261017_add_message_tree.sql"),
+        probe: r#"SELECT count(*) FROM pragma_table_info('gemini_conversation') WHERE name = 'active_record_id'"#,
+    },
+    Migration {
+        version: "20261017_add_search_index",
+        sql: include_str!("../../migrations/20261017_add_search_index.sql"),
+        probe: r#"SELECT count(*) FROM sqlite_master WHERE type = 'table' AND name = 'gemini_search_index'"#,
+    },
+    Migration {
+        version: "20261017_add_conversation_settings",
+        sql: include_str!("../../migrations/20261017_add_conversation_settings.sql"),
+        probe: r#"SELECT count(*) FROM pragma_table_info('gemini_conversation') WHERE name = 'conversation_model'"#,
+    },
+    Migration {
+        version: "20261017_add_token_usage",
+        sql: include_str!("../../migrations/20261017_add_token_usage.sql"),
+        probe: r#"SELECT count(*) FROM pragma_table_info('gemini_message_record') WHERE name = 'prompt_token_count'"#,
+    },
+    Migration {
+        version: "20261017_add_pinned",
+        sql: include_str!("../../migrations/20261017_add_pinned.sql"),
+        probe: r#"SELECT count(*) FROM pragma_table_info('gemini_message_record') WHERE name = 'pinned'"#,
+    },
+    Migration {
+        version: "20261017_add_finish_reason",
+        sql: include_str!("../../migrations/20261017_add_finish_reason.sql"),
+        probe: r#"SELECT count(*) FROM pragma_table_info('gemini_message_record') WHERE name = 'finish_reason'"#,
+    },
+    Migration {
+        version: "20261017_add_tool_payload",
+        sql: include_str!("../../migrations/20261017_add_tool_payload.sql"),
+        probe: r#"SELECT count(*) FROM pragma_table_info('gemini_message_record') WHERE name = 'tool_payload'"#,
+    },
+    Migration {
+        version: "20261017_add_response_schema",
+        sql: include_str!("../../migrations/20261017_add_response_schema.sql"),
+        probe: r#"SELECT count(*) FROM pragma_table_info('gemini_message_record') WHERE name = 'response_schema'"#,
+    },
+    Migration {
+        version: "20261017_add_reply_model",
+        sql: include_str!("../../migrations/20261017_add_reply_model.sql"),
+        probe: r#"SELECT count(*) FROM pragma_table_info('gemini_message_record') WHERE name = 'model'"#,
+    },
+];
+
+/// 执行所有未执行的迁移脚本，返回本次执行的脚本版本
+///
+/// 每个脚本与它的迁移记录在同一个事务中执行，失败时回滚该脚本并返回错误，之前执行成功的脚本保留。
+/// 已有数据的数据库在执行脚本前先备份到 `backup`
+pub(crate) fn migrate(
+    conn: &mut Connection,
+    migrations: &[Migration],
+    backup: Option<&Path>,
+) -> Result<Vec<&'static str>> {
+    let has_tables = table_count(conn)? > 0;
+    // 旧版本的数据库没有迁移记录，根据表结构判断已执行的脚本
+    let legacy = !table_exists(conn, MIGRATION_TABLE)?;
+    let applied = if legacy {
+        probe_versions(conn, migrations)?
+    } else {
+        recorded_versions(conn)?
+    };
+    let pending: Vec<&Migration> = migrations
+        .iter()
+        .filter(|migration| !applied.contains(migration.version))
+        .collect();
+    if has_tables && !pending.is_empty() {
+        if let Some(backup) = backup {
+            backup_db(conn, backup)?;
+        }
+    }
+    if legacy {
+        // 创建迁移记录表，并补全已执行的脚本
+        let tx = conn.transaction()?;
+        tx.execute_batch(&format!(
+            r#"CREATE TABLE IF NOT EXISTS "{}" ("version" TEXT NOT NULL, "applied_time" DATE, PRIMARY KEY ("version"));"#,
+            MIGRATION_TABLE
+        ))?;
+        for migration in migrations
+            .iter()
+            .filter(|migration| applied.contains(migration.version))
+        {
+            record_version(&tx, migration.version)?;
+        }
+        tx.commit()?;
+    }
+    let mut versions = Vec::new();
+    for migration in pending {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration.sql)
+            .with_context(|| format!("Failed to apply migration {}", migration.version))?;
+        record_version(&tx, migration.version)?;
+        tx.commit()?;
+        versions.push(migration.version);
+    }
+    Ok(versions)
+}
+
+/// 数据库中表的数量，不包括迁移记录表
+fn table_count(conn: &Connection) -> Result<i64> {
+    Ok(conn.query_row(
+        "SELECT count(*) FROM sqlite_master WHERE type = 'table' AND name != ?1",
+        [MIGRATION_TABLE],
+        |row| row.get(0),
+    )?)
+}
+
+/// 表是否存在
+fn table_exists(conn: &Connection, name: &str) -> Result<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT count(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        [name],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// 迁移记录表中已执行的脚本版本
+fn recorded_versions(conn: &Connection) -> Result<HashSet<String>> {
+    let mut stmt = conn.prepare(&format!(r#"SELECT "version" FROM "{}""#, MIGRATION_TABLE))?;
+    let versions = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<HashSet<String>>>()?;
+    Ok(versions)
+}
+
+/// 根据表结构判断旧版本数据库已执行的脚本版本
+fn probe_versions(conn: &Connection, migrations: &[Migration]) -> Result<HashSet<String>> {
+    let mut versions = HashSet::new();
+    for migration in migrations {
+        let count: i64 = conn.query_row(migration.probe, [], |row| row.get(0))?;
+        if count > 0 {
+            versions.insert(migration.version.to_owned());
+        }
+    }
+    Ok(versions)
+}
+
+/// 记录已执行的脚本
+fn record_version(conn: &Connection, version: &str) -> Result<()> {
+    conn.execute(
+        &format!(
+            r#"INSERT INTO "{}" ("version", "applied_time") VALUES (?1, ?2)"#,
+            MIGRATION_TABLE
+        ),
+        params![version, Local::now()],
+    )?;
+    Ok(())
+}
+
+/// 将数据库备份到指定的文件，文件已存在时覆盖
+fn backup_db(conn: &Connection, path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent)?;
+    }
+    if path.exists() {
+        remove_file(path)?;
+    }
+    conn.execute("VACUUM INTO ?1", [path.to_string_lossy()])
+        .with_context(|| format!("Failed to back up the database to {}", path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    /// 2024-10-25 版本创建的数据库，包含两个会话
+    const LEGACY_FIXTURE: &str = include_str!("../../tests/fixtures/gemini_20241025.sql");
+
+    fn legacy_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(LEGACY_FIXTURE).unwrap();
+        conn
+    }
+
+    fn has_column(conn: &Connection, table: &str, column: &str) -> bool {
+        let count: i64 = conn
+            .query_row(
+                "SELECT count(*) FROM pragma_table_info(?1) WHERE name = ?2",
+                [table, column],
+                |row| row.get(0),
+            )
+            .unwrap();
+        count > 0
+    }
+
+    fn all_versions() -> Vec<&'static str> {
+        MIGRATIONS.iter().map(|migration| migration.version).collect()
+    }
+
+    #[test]
+    fn migrate_legacy_database_to_latest() {
+        let mut conn = legacy_db();
+        let applied = migrate(&mut conn, MIGRATIONS, None).unwrap();
+        // 旧数据库已有的两个脚本不会重新执行
+        assert_eq!(applied, all_versions()[2..]);
+        let recorded = recorded_versions(&conn).unwrap();
+        assert!(all_versions().iter().all(|version| recorded.contains(*version)));
+        assert!(has_column(&conn, "gemini_message_record", "record_state"));
+        assert!(has_column(&conn, "gemini_message_record", "parent_record_id"));
+        assert!(has_column(&conn, "gemini_conversation", "conversation_model"));
+        // 原有的数据保留，并补全分支信息与搜索索引
+        let (records, active_record_id): (i64, String) = conn
+            .query_row(
+                r#"SELECT (SELECT count(*) FROM gemini_message_record), active_record_id
+                FROM gemini_conversation WHERE conversation_id = 'conversation-a'"#,
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(records, 5);
+        assert_eq!(active_record_id, "record-a4");
+        let parent: String = conn
+            .query_row(
+                "SELECT parent_record_id FROM gemini_message_record WHERE record_id = 'record-a2'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(parent, "record-a1");
+        let matches: i64 = conn
+            .query_row(
+                "SELECT count(*) FROM gemini_search_index WHERE content MATCH 'borrow'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(matches, 1);
+    }
+
+    #[test]
+    fn migrate_is_idempotent() {
+        let mut conn = legacy_db();
+        migrate(&mut conn, MIGRATIONS, None).unwrap();
+        assert!(migrate(&mut conn, MIGRATIONS, None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn migrate_new_database() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        assert_eq!(migrate(&mut conn, MIGRATIONS, None).unwrap(), all_versions());
+        assert!(migrate(&mut conn, MIGRATIONS, None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn failed_migration_is_rolled_back() {
+        let migrations = [
+            Migration {
+                version: "1_create",
+                sql: "CREATE TABLE a (id TEXT);",
+                probe: "SELECT 0",
+            },
+            Migration {
+                version: "2_broken",
+                sql: "CREATE TABLE b (id TEXT); INSERT INTO missing VALUES (1);",
+                probe: "SELECT 0",
+            },
+        ];
+        let mut conn = Connection::open_in_memory().unwrap();
+        let error = migrate(&mut conn, &migrations, None).unwrap_err();
+        assert!(error.to_string().contains("2_broken"));
+        assert!(table_exists(&conn, "a").unwrap());
+        assert!(!table_exists(&conn, "b").unwrap());
+        let recorded = recorded_versions(&conn).unwrap();
+        assert!(recorded.contains("1_create"));
+        assert!(!recorded.contains("2_broken"));
+    }
+
+    #[test]
+    fn backup_before_migration() {
+        let backup = env::temp_dir().join(format!("gemini-migration-test-{}.db", std::process::id()));
+        let mut conn = legacy_db();
+        migrate(&mut conn, MIGRATIONS, Some(&backup)).unwrap();
+        // 备份为迁移前的数据库
+        let backup_conn = Connection::open(&backup).unwrap();
+        assert!(!has_column(&backup_conn, "gemini_message_record", "record_state"));
+        assert!(!table_exists(&backup_conn, MIGRATION_TABLE).unwrap());
+        let count: i64 = backup_conn
+            .query_row("SELECT count(*) FROM gemini_message_record", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 5);
+        drop(backup_conn);
+        remove_file(&backup).unwrap();
+        // 没有需要执行的脚本时不备份
+        migrate(&mut conn, MIGRATIONS, Some(&backup)).unwrap();
+        assert!(!backup.exists());
+    }
+}
@@ -1,4 +1,19 @@
+pub(crate) mod attachment_utils;
 pub(crate) mod char_utils;
+pub(crate) mod clipboard_utils;
+pub(crate) mod context_utils;
 pub(crate) mod db_utils;
+pub(crate) mod export_utils;
+pub(crate) mod gemini_utils;
+pub(crate) mod highlight_utils;
 pub(crate) mod image_utils;
+pub(crate) mod import_utils;
+pub(crate) mod markdown_utils;
+pub(crate) mod migration_utils;
+pub(crate) mod path_utils;
+pub(crate) mod preview_utils;
+pub(crate) mod schema_utils;
+pub(crate) mod secret_utils;
 pub(crate) mod store_utils;
+pub(crate) mod tool_utils;
+pub(crate) mod worker_utils;
@@ -0,0 +1,184 @@
+use std::{
+    env,
+    fs::{copy, create_dir_all, read_dir, remove_dir_all, remove_file, rename},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use anyhow::Result;
+use chrono::Local;
+
+/// 程序目录名
+const APP_DIR_NAME: &str = "gemini";
+/// 指定配置目录的环境变量名
+const CONFIG_DIR_ENV_NAME: &str = "GEMINI_CONFIG_DIR";
+/// 指定数据目录的环境变量名
+const DATA_DIR_ENV_NAME: &str = "GEMINI_DATA_DIR";
+/// 配置文件名
+const CONFIG_FILE_NAME: &str = "gemini.json";
+/// 数据库文件名
+const DB_FILE_NAME: &str = "gemini.db";
+/// 附件缓存目录名
+const CACHE_DIR_NAME: &str = "data";
+/// 导出文件的默认目录名
+const EXPORT_DIR_NAME: &str = "export";
+
+/// 程序使用的目录
+#[derive(Debug, Clone)]
+struct AppDirs {
+    /// 配置目录，存放配置文件
+    config_dir: PathBuf,
+    /// 数据目录，存放数据库、附件缓存以及导出文件
+    data_dir: PathBuf,
+}
+
+static APP_DIRS: OnceLock<AppDirs> = OnceLock::new();
+
+/// 初始化程序使用的目录，命令行参数优先于环境变量，其次为 XDG 目录
+///
+/// 需要在读取配置或打开数据库之前调用，未调用时使用环境变量或 XDG 目录
+pub fn init_dirs(config_dir: Option<PathBuf>, data_dir: Option<PathBuf>) {
+    let _ = APP_DIRS.set(AppDirs {
+        config_dir: config_dir.unwrap_or_else(default_config_dir),
+        data_dir: data_dir.unwrap_or_else(default_data_dir),
+    });
+}
+
+fn app_dirs() -> &'static AppDirs {
+    APP_DIRS.get_or_init(|| AppDirs {
+        config_dir: default_config_dir(),
+        data_dir: default_data_dir(),
+    })
+}
+
+/// 环境变量中的非空路径
+fn env_path(name: &str) -> Option<PathBuf> {
+    env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from)
+}
+
+/// 用户主目录
+fn home_dir() -> Option<PathBuf> {
+    env_path("HOME").or_else(|| env_path("USERPROFILE"))
+}
+
+/// 默认配置目录，依次为 `$GEMINI_CONFIG_DIR`、`$XDG_CONFIG_HOME/gemini`、`~/.config/gemini`，
+/// Windows 下为 `%APPDATA%\gemini`
+fn default_config_dir() -> PathBuf {
+    env_path(CONFIG_DIR_ENV_NAME)
+        .or_else(|| env_path("XDG_CONFIG_HOME").map(|dir| dir.join(APP_DIR_NAME)))
+        .or_else(|| {
+            if cfg!(windows) {
+                env_path("APPDATA").map(|dir| dir.join(APP_DIR_NAME))
+            } else {
+                home_dir().map(|dir| dir.join(".config").join(APP_DIR_NAME))
+            }
+        })
+        .unwrap_or_else(legacy_dir)
+}
+
+/// 默认数据目录，依次为 `$GEMINI_DATA_DIR`、`$XDG_DATA_HOME/gemini`、`~/.local/share/gemini`，
+/// Windows 下为 `%LOCALAPPDATA%\gemini`
+fn default_data_dir() -> PathBuf {
+    env_path(DATA_DIR_ENV_NAME)
+        .or_else(|| env_path("XDG_DATA_HOME").map(|dir| dir.join(APP_DIR_NAME)))
+        .or_else(|| {
+            if cfg!(windows) {
+                env_path("LOCALAPPDATA").map(|dir| dir.join(APP_DIR_NAME))
+            } else {
+                home_dir().map(|dir| dir.join(".local").join("share").join(APP_DIR_NAME))
+            }
+        })
+        .unwrap_or_else(legacy_dir)
+}
+
+/// 旧版本存放配置与数据的目录，即程序所在目录
+fn legacy_dir() -> PathBuf {
+    env::current_exe()
+        .ok()
+        .and_then(|exe_path| exe_path.parent().map(Path::to_path_buf))
+        .unwrap_or_default()
+}
+
+/// 配置文件路径
+pub fn config_file() -> PathBuf {
+    app_dirs().config_dir.join(CONFIG_FILE_NAME)
+}
+
+/// 数据库文件路径
+pub fn db_file() -> PathBuf {
+    app_dirs().data_dir.join(DB_FILE_NAME)
+}
+
+/// 迁移前的数据库备份路径，以备份时间区分
+pub fn db_backup_file() -> PathBuf {
+    let file_name = format!("{}.{}.bak", DB_FILE_NAME, Local::now().format("%Y%m%d%H%M%S"));
+    app_dirs().data_dir.join(file_name)
+}
+
+/// 附件缓存文件路径
+pub fn cache_file(file_name: &str) -> PathBuf {
+    app_dirs().data_dir.join(CACHE_DIR_NAME).join(file_name)
+}
+
+/// 导出文件的默认目录
+pub fn export_dir() -> PathBuf {
+    app_dirs().data_dir.join(EXPORT_DIR_NAME)
+}
+
+/// 将旧版本保存在程序所在目录的配置文件、数据库与附件缓存迁移到新的目录
+///
+/// 只有新目录中不存在对应文件时才会迁移，迁移后删除旧文件，旧文件无法删除时保留
+pub fn migrate_legacy_files() -> Result<()> {
+    let legacy_dir = legacy_dir();
+    let dirs = app_dirs();
+    let items = [
+        (legacy_dir.join(CONFIG_FILE_NAME), config_file()),
+        (legacy_dir.join(DB_FILE_NAME), db_file()),
+        (legacy_dir.join(CACHE_DIR_NAME), dirs.data_dir.join(CACHE_DIR_NAME)),
+    ];
+    for (from, to) in items {
+        // 新旧目录相同时目标文件已存在，不会迁移
+        if !from.exists() || to.exists() {
+            continue;
+        }
+        if let Some(parent) = to.parent() {
+            create_dir_all(parent)?;
+        }
+        move_path(&from, &to)?;
+    }
+    Ok(())
+}
+
+/// 移动文件或目录，无法直接重命名时（例如跨文件系统）复制后删除
+fn move_path(from: &Path, to: &Path) -> Result<()> {
+    if rename(from, to).is_ok() {
+        return Ok(());
+    }
+    // 先复制到临时位置，避免复制中断后留下不完整的文件被当作已迁移
+    let mut temp = to.as_os_str().to_owned();
+    temp.push(".migrating");
+    let temp = PathBuf::from(temp);
+    copy_path(from, &temp)?;
+    rename(&temp, to)?;
+    // 旧目录可能是只读的，删除失败时保留旧文件
+    let _ = if from.is_dir() {
+        remove_dir_all(from)
+    } else {
+        remove_file(from)
+    };
+    Ok(())
+}
+
+/// 递归复制文件或目录
+fn copy_path(from: &Path, to: &Path) -> Result<()> {
+    if from.is_dir() {
+        create_dir_all(to)?;
+        for entry in read_dir(from)? {
+            let entry = entry?;
+            copy_path(&entry.path(), &to.join(entry.file_name()))?;
+        }
+    } else {
+        copy(from, to)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,341 @@
+use std::{
+    env,
+    fmt::Write as _,
+    io::{Cursor, Write},
+    sync::{Arc, LazyLock},
+};
+
+use anyhow::Result;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use image::{codecs::jpeg::JpegEncoder, imageops::FilterType, GenericImageView, ImageReader, RgbImage};
+use ratatui::{
+    buffer::Buffer,
+    crossterm::{
+        cursor::{MoveTo, RestorePosition, SavePosition},
+        queue,
+        style::Print,
+        terminal::window_size,
+    },
+    layout::Rect,
+    style::Color,
+    DefaultTerminal,
+};
+use strum::EnumString;
+
+use super::path_utils::cache_file;
+
+/// 预览图片的最大宽度（单元格数量）
+pub const PREVIEW_MAX_WIDTH: u16 = 40;
+/// 预览图片的最大高度（单元格数量）
+pub const PREVIEW_MAX_HEIGHT: u16 = 12;
+/// 指定图形协议的环境变量名，可选值为 kitty、sixel、iterm2、halfblock
+const PROTOCOL_ENV_NAME: &str = "GEMINI_IMAGE_PROTOCOL";
+/// 无法获取终端像素尺寸时使用的单元格像素尺寸
+const DEFAULT_CELL_SIZE: (u32, u32) = (10, 20);
+/// kitty 协议每一段数据的最大长度
+const KITTY_CHUNK_SIZE: usize = 4096;
+
+/// 终端图形协议
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString)]
+#[strum(ascii_case_insensitive)]
+pub enum GraphicsProtocol {
+    /// kitty 图形协议，kitty、ghostty 等终端支持
+    #[strum(serialize = "kitty")]
+    Kitty,
+    /// sixel 图形协议，foot、mlterm 等终端支持
+    #[strum(serialize = "sixel")]
+    Sixel,
+    /// iTerm2 内联图片协议，iTerm2、WezTerm 等终端支持
+    #[strum(serialize = "iterm2")]
+    Iterm2,
+    /// 使用 Unicode 上半块字符与前景、背景色模拟图片，所有支持真彩色的终端均可使用
+    #[strum(serialize = "halfblock")]
+    HalfBlock,
+}
+
+impl GraphicsProtocol {
+    /// 根据环境变量识别当前终端支持的图形协议
+    fn detect() -> Self {
+        if let Some(protocol) = env::var(PROTOCOL_ENV_NAME).ok().and_then(|v| v.parse().ok()) {
+            return protocol;
+        }
+        // tmux 中需要额外转发图形协议，直接使用字符模拟
+        if env::var_os("TMUX").is_some() {
+            return Self::HalfBlock;
+        }
+        let term = env::var("TERM").unwrap_or_default();
+        let term_program = env::var("TERM_PROGRAM").unwrap_or_default();
+        if env::var_os("KITTY_WINDOW_ID").is_some() || term.contains("kitty") || term.contains("ghostty") {
+            Self::Kitty
+        } else if matches!(term_program.as_str(), "iTerm.app" | "WezTerm")
+            || env::var("LC_TERMINAL").is_ok_and(|v| v == "iTerm2")
+        {
+            Self::Iterm2
+        } else if term.contains("foot") || term.contains("mlterm") || term.contains("sixel") {
+            Self::Sixel
+        } else {
+            Self::HalfBlock
+        }
+    }
+}
+
+/// 当前终端使用的图形协议
+pub static PROTOCOL: LazyLock<GraphicsProtocol> = LazyLock::new(GraphicsProtocol::detect);
+
+/// 单张图片的预览
+#[derive(Debug)]
+pub struct ImagePreview {
+    /// 图片记录 id
+    pub image_record_id: String,
+    /// 预览宽度（单元格数量）
+    pub width: u16,
+    /// 预览高度（单元格数量）
+    pub height: u16,
+    /// 字符模拟使用的图片，每个单元格对应上下两个像素
+    half_block: RgbImage,
+    /// 图形协议的输出内容，使用字符模拟时为空
+    sequence: String,
+}
+
+/// 单元格的像素尺寸
+fn cell_size() -> (u32, u32) {
+    match window_size() {
+        Ok(size) if size.width > 0 && size.height > 0 && size.columns > 0 && size.rows > 0 => (
+            (size.width / size.columns).max(1) as u32,
+            (size.height / size.rows).max(1) as u32,
+        ),
+        _ => DEFAULT_CELL_SIZE,
+    }
+}
+
+/// 读取缓存的图片并生成不超过指定宽度的预览
+pub fn load_preview(image_record_id: &str, max_width: u16) -> Option<Arc<ImagePreview>> {
+    let file_path = cache_file(image_record_id);
+    // 缓存文件没有扩展名，根据文件内容识别图片格式
+    let image = ImageReader::open(file_path)
+        .ok()?
+        .with_guessed_format()
+        .ok()?
+        .decode()
+        .ok()?;
+    let (image_width, image_height) = image.dimensions();
+    if image_width == 0 || image_height == 0 {
+        return None;
+    }
+    // 按照单元格的宽高比计算预览尺寸，宽度与高度均不超过上限
+    let (cell_width, cell_height) = cell_size();
+    let max_width = max_width.clamp(1, PREVIEW_MAX_WIDTH) as u32;
+    let mut width = image_width.div_ceil(cell_width).clamp(1, max_width);
+    let mut height = (width * cell_width * image_height)
+        .div_ceil(image_width * cell_height)
+        .max(1);
+    if height > PREVIEW_MAX_HEIGHT as u32 {
+        height = PREVIEW_MAX_HEIGHT as u32;
+        width = (height * cell_height * image_width / (image_height * cell_width)).clamp(1, max_width);
+    }
+    let half_block = image.resize_exact(width, height * 2, FilterType::Triangle).to_rgb8();
+    let sequence = match *PROTOCOL {
+        GraphicsProtocol::HalfBlock => String::new(),
+        protocol => {
+            let pixels = image
+                .resize_exact(width * cell_width, height * cell_height, FilterType::Triangle)
+                .to_rgb8();
+            match protocol {
+                GraphicsProtocol::Kitty => encode_kitty(&pixels, width, height),
+                GraphicsProtocol::Sixel => encode_sixel(&pixels),
+                _ => encode_iterm2(&pixels, width, height)?,
+            }
+        }
+    };
+    Some(Arc::new(ImagePreview {
+        image_record_id: image_record_id.into(),
+        width: width as u16,
+        height: height as u16,
+        half_block,
+        sequence,
+    }))
+}
+
+/// 使用 Unicode 上半块字符将预览渲染到缓冲区中，超出缓冲区的部分会被忽略
+pub fn render_half_block(preview: &ImagePreview, area: Rect, buf: &mut Buffer) {
+    for row in 0..preview.height.min(area.height) {
+        for col in 0..preview.width.min(area.width) {
+            let top = preview.half_block.get_pixel(col as u32, row as u32 * 2);
+            let bottom = preview.half_block.get_pixel(col as u32, row as u32 * 2 + 1);
+            if let Some(cell) = buf.cell_mut((area.x + col, area.y + row)) {
+                cell.set_char('▀')
+                    .set_fg(Color::Rgb(top[0], top[1], top[2]))
+                    .set_bg(Color::Rgb(bottom[0], bottom[1], bottom[2]));
+            }
+        }
+    }
+}
+
+/// kitty 协议，直接传输 RGB 像素数据，数据较长时分段传输
+fn encode_kitty(pixels: &RgbImage, width: u32, height: u32) -> String {
+    let data = STANDARD.encode(pixels.as_raw());
+    let chunks: Vec<&[u8]> = data.as_bytes().chunks(KITTY_CHUNK_SIZE).collect();
+    let mut sequence = String::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = if i + 1 < chunks.len() { 1 } else { 0 };
+        if i == 0 {
+            // C=1 输出图片后不移动光标，q=2 不返回任何响应
+            let _ = write!(
+                sequence,
+                "\x1b_Ga=T,f=24,s={},v={},c={},r={},C=1,q=2,m={};",
+                pixels.width(),
+                pixels.height(),
+                width,
+                height,
+                more
+            );
+        } else {
+            let _ = write!(sequence, "\x1b_Gm={};", more);
+        }
+        sequence.push_str(std::str::from_utf8(chunk).unwrap_or_default());
+        sequence.push_str("\x1b\\");
+    }
+    sequence
+}
+
+/// iTerm2 协议，以 JPEG 格式传输图片，并指定占用的单元格数量
+fn encode_iterm2(pixels: &RgbImage, width: u32, height: u32) -> Option<String> {
+    let mut data = Cursor::new(Vec::new());
+    JpegEncoder::new_with_quality(&mut data, 90).encode_image(pixels).ok()?;
+    let data = data.into_inner();
+    Some(format!(
+        "\x1b]1337;File=inline=1;size={};width={};height={};preserveAspectRatio=0:{}\x07",
+        data.len(),
+        width,
+        height,
+        STANDARD.encode(data)
+    ))
+}
+
+/// sixel 协议，颜色量化为 6x6x6 的调色板，每 6 行像素为一个条带
+fn encode_sixel(pixels: &RgbImage) -> String {
+    let (width, height) = pixels.dimensions();
+    let mut sequence = format!("\x1bP0;1;0q\"1;1;{};{}", width, height);
+    // 定义调色板，颜色分量的取值范围为 0 到 100
+    for index in 0..216 {
+        let _ = write!(
+            sequence,
+            "#{};2;{};{};{}",
+            index,
+            index / 36 * 20,
+            index / 6 % 6 * 20,
+            index % 6 * 20
+        );
+    }
+    let quantize = |c: u8| (c as usize * 5 + 127) / 255;
+    for band in (0..height).step_by(6) {
+        // 每种颜色在当前条带中每一列的像素位
+        let mut bits = vec![Vec::new(); 216];
+        for x in 0..width {
+            for dy in 0..6.min(height - band) {
+                let pixel = pixels.get_pixel(x, band + dy);
+                let index = quantize(pixel[0]) * 36 + quantize(pixel[1]) * 6 + quantize(pixel[2]);
+                let row: &mut Vec<u8> = &mut bits[index];
+                if row.is_empty() {
+                    row.resize(width as usize, 0);
+                }
+                row[x as usize] |= 1 << dy;
+            }
+        }
+        let mut first = true;
+        for (index, row) in bits.iter().enumerate().filter(|(_, row)| !row.is_empty()) {
+            // 同一条带中的多种颜色需要回到行首再绘制
+            if !first {
+                sequence.push('$');
+            }
+            first = false;
+            let _ = write!(sequence, "#{}", index);
+            // 连续相同的像素使用重复标记压缩
+            let mut x = 0;
+            while x < row.len() {
+                let value = row[x];
+                let run = row[x..].iter().take_while(|v| **v == value).count();
+                let c = (63 + value) as char;
+                if run > 3 {
+                    let _ = write!(sequence, "!{}{}", run, c);
+                } else {
+                    (0..run).for_each(|_| sequence.push(c));
+                }
+                x += run;
+            }
+        }
+        sequence.push('-');
+    }
+    sequence.push_str("\x1b\\");
+    sequence
+}
+
+/// 图片预览在屏幕上的位置
+#[derive(Debug, Clone)]
+pub struct ImagePlacement {
+    /// 屏幕上的区域
+    pub area: Rect,
+    /// 图片预览
+    pub preview: Arc<ImagePreview>,
+}
+
+/// 通过图形协议输出的图片层
+///
+/// 图形协议输出的图片不经过 ratatui 的缓冲区，需要在界面绘制完成后单独输出，
+/// 只有图片位置发生变化时才重新输出，避免每次绘制都传输图片数据
+#[derive(Default)]
+pub struct GraphicsLayer {
+    /// 已输出的图片位置
+    shown: Vec<(Rect, String)>,
+}
+
+impl GraphicsLayer {
+    /// 图片位置是否发生了变化
+    pub fn changed(&self, placements: &[ImagePlacement]) -> bool {
+        self.shown.len() != placements.len()
+            || self
+                .shown
+                .iter()
+                .zip(placements)
+                .any(|((area, id), placement)| *area != placement.area || *id != placement.preview.image_record_id)
+    }
+
+    /// 清除已输出的图片，kitty 协议直接删除图片，其它协议需要重绘整个界面
+    pub fn clear(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        if self.shown.is_empty() {
+            return Ok(());
+        }
+        if *PROTOCOL == GraphicsProtocol::Kitty {
+            let backend = terminal.backend_mut();
+            backend.write_all(b"\x1b_Ga=d,d=A,q=2\x1b\\")?;
+            backend.flush()?;
+        } else {
+            terminal.clear()?;
+        }
+        self.shown.clear();
+        Ok(())
+    }
+
+    /// 在指定位置输出图片，输出完成后恢复光标位置
+    pub fn show(&mut self, terminal: &mut DefaultTerminal, placements: &[ImagePlacement]) -> Result<()> {
+        if placements.is_empty() {
+            return Ok(());
+        }
+        let backend = terminal.backend_mut();
+        queue!(backend, SavePosition)?;
+        for placement in placements {
+            queue!(
+                backend,
+                MoveTo(placement.area.x, placement.area.y),
+                Print(&placement.preview.sequence)
+            )?;
+        }
+        queue!(backend, RestorePosition)?;
+        backend.flush()?;
+        self.shown = placements
+            .iter()
+            .map(|placement| (placement.area, placement.preview.image_record_id.clone()))
+            .collect();
+        Ok(())
+    }
+}
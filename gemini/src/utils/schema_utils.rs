@@ -0,0 +1,202 @@
+use anyhow::{bail, Result};
+use serde_json::Value;
+
+/// 解析设置页面中输入的回复格式约束，为空时返回空
+pub fn parse_schema(text: &str) -> Result<Option<Value>> {
+    if text.trim().is_empty() {
+        return Ok(None);
+    }
+    let schema = serde_json::from_str::<Value>(text)?;
+    if !schema.is_object() {
+        bail!("Response schema must be a JSON object")
+    }
+    Ok(Some(schema))
+}
+
+/// 格式化 JSON 回复，只处理对象与数组，其它内容返回空
+pub fn pretty_json(text: &str) -> Option<String> {
+    let value = serde_json::from_str::<Value>(text.trim()).ok()?;
+    if !value.is_object() && !value.is_array() {
+        return None;
+    }
+    serde_json::to_string_pretty(&value).ok()
+}
+
+/// 回复不符合格式约束时的说明，只展示第一处不符合之处及其余的数量
+pub fn schema_mismatch(text: &str, schema: &Value) -> Option<String> {
+    let value = match serde_json::from_str::<Value>(text.trim()) {
+        Ok(value) => value,
+        Err(_) => return Some("invalid JSON".into()),
+    };
+    let mut errors = Vec::new();
+    check(&value, schema, "$", &mut errors);
+    match errors.len() {
+        0 => None,
+        1 => Some(errors.remove(0)),
+        n => Some(format!("{} (+{} more)", errors[0], n - 1)),
+    }
+}
+
+/// 按 API 支持的 OpenAPI 子集校验数据，类型名称不区分大小写，不符合之处以 JSON 路径标注
+fn check(value: &Value, schema: &Value, path: &str, errors: &mut Vec<String>) {
+    if value.is_null() && schema.get("nullable").and_then(Value::as_bool) == Some(true) {
+        return;
+    }
+    if let Some(expected) = schema.get("type").and_then(Value::as_str) {
+        let expected = expected.to_lowercase();
+        let matched = match expected.as_str() {
+            "string" => value.is_string(),
+            "number" => value.is_number(),
+            "integer" => value.is_i64() || value.is_u64(),
+            "boolean" => value.is_boolean(),
+            "array" => value.is_array(),
+            "object" => value.is_object(),
+            _ => true,
+        };
+        if !matched {
+            errors.push(format!("{}: expected {}", path, expected));
+            return;
+        }
+    }
+    if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+        if !values.contains(value) {
+            errors.push(format!("{}: not one of the enum values", path));
+        }
+    }
+    match value {
+        Value::Object(map) => {
+            let required = schema.get("required").and_then(Value::as_array);
+            for name in required.into_iter().flatten().filter_map(Value::as_str) {
+                if !map.contains_key(name) {
+                    errors.push(format!("{}: missing property {}", path, name));
+                }
+            }
+            // 没有声明属性时不限制属性
+            let Some(properties) = schema.get("properties").and_then(Value::as_object) else {
+                return;
+            };
+            for (name, value) in map {
+                match properties.get(name) {
+                    Some(schema) => check(value, schema, &format!("{}.{}", path, name), errors),
+                    None => errors.push(format!("{}: unexpected property {}", path, name)),
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(min) = schema.get("minItems").and_then(count) {
+                if (items.len() as u64) < min {
+                    errors.push(format!("{}: fewer than {} items", path, min));
+                }
+            }
+            if let Some(max) = schema.get("maxItems").and_then(count) {
+                if items.len() as u64 > max {
+                    errors.push(format!("{}: more than {} items", path, max));
+                }
+            }
+            if let Some(schema) = schema.get("items") {
+                for (index, item) in items.iter().enumerate() {
+                    check(item, schema, &format!("{}[{}]", path, index), errors);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// 数量限制，API 中的 64 位整数可能以字符串表示
+fn count(value: &Value) -> Option<u64> {
+    value.as_u64().or_else(|| value.as_str()?.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn errors(value: Value, schema: Value) -> Vec<String> {
+        let mut errors = Vec::new();
+        check(&value, &schema, "$", &mut errors);
+        errors
+    }
+
+    #[test]
+    fn type_names_ignore_case() {
+        assert!(errors(json!("a"), json!({ "type": "STRING" })).is_empty());
+        assert!(errors(json!(1), json!({ "type": "integer" })).is_empty());
+        assert!(errors(json!(1.5), json!({ "type": "Number" })).is_empty());
+        assert_eq!(
+            errors(json!(1.5), json!({ "type": "INTEGER" })),
+            ["$: expected integer"]
+        );
+        assert_eq!(
+            errors(json!("true"), json!({ "type": "boolean" })),
+            ["$: expected boolean"]
+        );
+        // 可为空的值允许为 null
+        assert!(errors(json!(null), json!({ "type": "string", "nullable": true })).is_empty());
+        assert_eq!(errors(json!(null), json!({ "type": "string" })), ["$: expected string"]);
+    }
+
+    #[test]
+    fn enum_values() {
+        let schema = json!({ "type": "string", "enum": ["red", "green"] });
+        assert!(errors(json!("red"), schema.clone()).is_empty());
+        assert_eq!(errors(json!("blue"), schema), ["$: not one of the enum values"]);
+    }
+
+    #[test]
+    fn required_and_unexpected_properties() {
+        let schema = json!({
+            "type": "object",
+            "properties": { "name": { "type": "string" }, "age": { "type": "integer" } },
+            "required": ["name"]
+        });
+        assert!(errors(json!({ "name": "a" }), schema.clone()).is_empty());
+        assert_eq!(
+            errors(json!({ "age": 1 }), schema.clone()),
+            ["$: missing property name"]
+        );
+        assert_eq!(
+            errors(json!({ "name": "a", "email": "" }), schema.clone()),
+            ["$: unexpected property email"]
+        );
+        assert_eq!(errors(json!({ "name": 1 }), schema), ["$.name: expected string"]);
+        // 没有声明属性时不限制属性
+        assert!(errors(json!({ "any": 1 }), json!({ "type": "object" })).is_empty());
+    }
+
+    #[test]
+    fn nested_items() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "tags": { "type": "array", "items": { "type": "object", "properties": { "id": { "type": "integer" } } } }
+            }
+        });
+        assert!(errors(json!({ "tags": [{ "id": 1 }] }), schema.clone()).is_empty());
+        assert_eq!(
+            errors(json!({ "tags": [{ "id": 1 }, { "id": "2" }] }), schema),
+            ["$.tags[1].id: expected integer"]
+        );
+    }
+
+    #[test]
+    fn min_and_max_items() {
+        let schema = json!({ "type": "array", "minItems": 2, "maxItems": "3" });
+        assert!(errors(json!([1, 2]), schema.clone()).is_empty());
+        assert_eq!(errors(json!([1]), schema.clone()), ["$: fewer than 2 items"]);
+        assert_eq!(errors(json!([1, 2, 3, 4]), schema), ["$: more than 3 items"]);
+    }
+
+    #[test]
+    fn mismatch_summary() {
+        let schema = json!({ "type": "object", "required": ["a", "b", "c"] });
+        assert_eq!(schema_mismatch(r#"{"a": 1, "b": 2, "c": 3}"#, &schema), None);
+        assert_eq!(
+            schema_mismatch("{}", &schema),
+            Some("$: missing property a (+2 more)".into())
+        );
+        assert_eq!(schema_mismatch("not json", &schema), Some("invalid JSON".into()));
+    }
+}
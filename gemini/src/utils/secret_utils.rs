@@ -0,0 +1,206 @@
+use std::{
+    env,
+    io::{stderr, IsTerminal, Write},
+    num::NonZeroU32,
+    process::Command,
+    sync::Mutex,
+};
+
+use anyhow::{anyhow, bail, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ratatui::crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    terminal::{disable_raw_mode, enable_raw_mode},
+};
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    pbkdf2::{self, PBKDF2_HMAC_SHA256},
+    rand::{SecureRandom, SystemRandom},
+};
+use serde::{Deserialize, Serialize};
+
+/// 存储口令的环境变量名
+pub(crate) const PASSPHRASE_ENV_NAME: &str = "GEMINI_PASSPHRASE";
+/// 口令派生密钥的迭代次数
+const PBKDF2_ITERATIONS: u32 = 100_000;
+/// 盐的长度
+const SALT_LEN: usize = 16;
+
+/// 本次运行中输入的口令，只保存在内存中
+static PASSPHRASE: Mutex<Option<String>> = Mutex::new(None);
+
+/// 使用口令加密后的密钥，各字段均为 Base64 编码
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub(crate) struct EncryptedKey {
+    pub salt: String,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+/// 设置本次运行使用的口令，传入空值时清除
+pub(crate) fn set_passphrase(passphrase: Option<String>) {
+    *PASSPHRASE.lock().unwrap() = passphrase.filter(|passphrase| !passphrase.is_empty());
+}
+
+/// 获取口令，优先使用本次运行中输入的口令，其次为环境变量
+pub(crate) fn passphrase() -> Option<String> {
+    PASSPHRASE
+        .lock()
+        .unwrap()
+        .clone()
+        .or_else(|| env::var(PASSPHRASE_ENV_NAME).ok())
+        .filter(|passphrase| !passphrase.is_empty())
+}
+
+/// 由口令与盐派生加密密钥
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<LessSafeKey> {
+    let mut key = [0u8; 32];
+    pbkdf2::derive(
+        PBKDF2_HMAC_SHA256,
+        NonZeroU32::new(PBKDF2_ITERATIONS).unwrap(),
+        salt,
+        passphrase.as_bytes(),
+        &mut key,
+    );
+    let key = UnboundKey::new(&AES_256_GCM, &key).map_err(|_| anyhow!("Invalid encryption key"))?;
+    Ok(LessSafeKey::new(key))
+}
+
+/// 使用口令加密密钥，每次加密使用随机的盐与随机数
+pub(crate) fn encrypt_key(key: &str, passphrase: &str) -> Result<EncryptedKey> {
+    let rng = SystemRandom::new();
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    if rng.fill(&mut salt).is_err() || rng.fill(&mut nonce).is_err() {
+        bail!("Failed to generate random bytes")
+    }
+    let mut data = key.as_bytes().to_vec();
+    derive_key(passphrase, &salt)?
+        .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut data)
+        .map_err(|_| anyhow!("Failed to encrypt the key"))?;
+    Ok(EncryptedKey {
+        salt: STANDARD.encode(salt),
+        nonce: STANDARD.encode(nonce),
+        ciphertext: STANDARD.encode(data),
+    })
+}
+
+/// 使用口令解密密钥，口令错误时返回错误
+pub(crate) fn decrypt_key(encrypted_key: &EncryptedKey, passphrase: &str) -> Result<String> {
+    let salt = STANDARD.decode(&encrypted_key.salt)?;
+    let nonce = Nonce::try_assume_unique_for_key(&STANDARD.decode(&encrypted_key.nonce)?)
+        .map_err(|_| anyhow!("Invalid encrypted key"))?;
+    let mut data = STANDARD.decode(&encrypted_key.ciphertext)?;
+    let key = derive_key(passphrase, &salt)?
+        .open_in_place(nonce, Aad::empty(), &mut data)
+        .map_err(|_| anyhow!("Wrong passphrase"))?;
+    Ok(String::from_utf8(key.to_vec())?)
+}
+
+/// 执行外部命令（例如 `pass show gemini`），使用其标准输出的第一行作为密钥
+pub(crate) fn run_key_command(command: &str) -> Result<String> {
+    let output = if cfg!(windows) {
+        Command::new("cmd").args(["/C", command]).output()?
+    } else {
+        Command::new("sh").args(["-c", command]).output()?
+    };
+    if !output.status.success() {
+        bail!("Key command exited with {}", output.status)
+    }
+    first_line(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// 读取文件的第一行作为密钥
+pub(crate) fn read_key_file(path: &str) -> Result<String> {
+    first_line(&std::fs::read_to_string(path)?)
+}
+
+/// 第一行非空内容
+fn first_line(text: &str) -> Result<String> {
+    match text.lines().map(str::trim).find(|line| !line.is_empty()) {
+        Some(line) => Ok(line.into()),
+        None => bail!("The key is empty"),
+    }
+}
+
+/// 在终端中输入口令，输入内容不会回显
+pub(crate) fn read_passphrase(prompt: &str) -> Result<String> {
+    if !stderr().is_terminal() {
+        bail!(
+            "Passphrase required, set the environment variable {}",
+            PASSPHRASE_ENV_NAME
+        )
+    }
+    eprint!("{}", prompt);
+    stderr().flush()?;
+    enable_raw_mode()?;
+    let result = read_hidden_line();
+    let _ = disable_raw_mode();
+    eprintln!();
+    result
+}
+
+/// 读取一行输入，不回显
+fn read_hidden_line() -> Result<String> {
+    let mut line = String::new();
+    loop {
+        if let Event::Key(key) = event::read()? {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Enter => return Ok(line),
+                KeyCode::Backspace => {
+                    line.pop();
+                }
+                KeyCode::Esc => bail!("Cancelled"),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => bail!("Cancelled"),
+                KeyCode::Char(c) => line.push(c),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encrypt_and_decrypt() {
+        let encrypted_key = encrypt_key("AIza-密钥", "correct horse").unwrap();
+        assert_eq!(decrypt_key(&encrypted_key, "correct horse").unwrap(), "AIza-密钥");
+        // 每次加密使用不同的盐与随机数
+        let again = encrypt_key("AIza-密钥", "correct horse").unwrap();
+        assert_ne!(again.salt, encrypted_key.salt);
+        assert_ne!(again.nonce, encrypted_key.nonce);
+        assert_ne!(again.ciphertext, encrypted_key.ciphertext);
+    }
+
+    #[test]
+    fn wrong_passphrase() {
+        let encrypted_key = encrypt_key("AIza-test-key", "correct horse").unwrap();
+        let error = decrypt_key(&encrypted_key, "battery staple").err().unwrap();
+        assert_eq!(error.to_string(), "Wrong passphrase");
+        assert!(decrypt_key(&encrypted_key, "").is_err());
+        // 密文被修改时同样无法解密
+        let mut ciphertext = STANDARD.decode(&encrypted_key.ciphertext).unwrap();
+        ciphertext[0] ^= 1;
+        let tampered = EncryptedKey {
+            ciphertext: STANDARD.encode(ciphertext),
+            ..encrypted_key
+        };
+        assert!(decrypt_key(&tampered, "correct horse").is_err());
+    }
+
+    #[test]
+    fn decrypt_saved_key() {
+        // 已保存在配置文件中的密钥，修改派生参数后将无法解密
+        let encrypted_key = EncryptedKey {
+            salt: "YMBZgae0srZZxjIhnCk+5A==".into(),
+            nonce: "ZzJ0ENX0MdnWTCC7".into(),
+            ciphertext: "QODWTNIDPd6LMwZdYKd7RNnnJwFNOYyl1KKMbOI=".into(),
+        };
+        assert_eq!(decrypt_key(&encrypted_key, "correct horse").unwrap(), "AIza-test-key");
+    }
+}
@@ -1,6 +1,6 @@
 use std::{
-    env,
-    fs::File,
+    collections::BTreeMap,
+    fs::{create_dir_all, File},
     io::{Read, Write},
     path::PathBuf,
 };
@@ -8,46 +8,396 @@ use std::{
 use anyhow::{bail, Result};
 use gemini_api::{body::request::GenerationConfig, param::LanguageModel};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use strum::{Display, EnumString};
 
-use super::db_utils::current_db_version;
+use crate::model::view::TokenUsage;
+
+use super::{
+    path_utils::config_file,
+    secret_utils::{decrypt_key, encrypt_key, passphrase, read_key_file, run_key_command, EncryptedKey},
+};
 
 /// 存储配置数据
 #[derive(Serialize, Deserialize, Default, Clone)]
 pub(crate) struct StoreData {
+    /// 明文密钥，只在内存中使用，不会写入配置文件
+    ///
+    /// 旧版本的配置文件中保存的明文密钥会被读取，并在下一次保存配置时加密
+    #[serde(default, skip_serializing)]
     pub key: String,
+    /// 密钥来源
+    #[serde(default)]
+    pub key_source: KeySource,
+    /// 当前使用的配置方案名称，以下三项为当前配置方案的内容
+    #[serde(default = "default_profile_name")]
+    pub profile: String,
     pub model: LanguageModel,
     pub system_instruction: Option<String>,
     pub options: GenerationConfig,
-    pub db_version: Option<String>,
+    /// 回复格式约束，设置后回复为符合该约束的 JSON
+    #[serde(default)]
+    pub response_schema: Option<Value>,
+    /// 其它未使用的配置方案
+    #[serde(default)]
+    pub profiles: Vec<Profile>,
+    /// 各模型的价格，键为模型名称，用于估算会话费用，只能在配置文件中修改
+    #[serde(default)]
+    pub prices: BTreeMap<String, ModelPrice>,
+    /// 上下文窗口设置
+    #[serde(default)]
+    pub context: ContextConfig,
+    /// 各类别的安全阈值
+    #[serde(default)]
+    pub safety: SafetyConfig,
+    /// 本地工具设置，只能在配置文件中修改
+    #[serde(default)]
+    pub tools: ToolConfig,
+}
+
+/// 上下文窗口设置，请求超出预算时将最早的对话移出上下文
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub(crate) struct ContextConfig {
+    /// 每次请求的 token 预算，包含系统指令、历史记录与新消息，0 表示不限制
+    pub budget: u32,
+    /// 超出预算时的处理方式
+    pub strategy: ContextStrategy,
+}
+
+/// 超出上下文预算时的处理方式
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq, Display, EnumString)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case", ascii_case_insensitive)]
+pub(crate) enum ContextStrategy {
+    /// 直接丢弃最早的对话
+    #[default]
+    Drop,
+    /// 将最早的对话总结后附加到系统指令中
+    Summarize,
+}
+
+/// 各类别的安全阈值，未设置的类别使用 API 的默认值
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub(crate) struct SafetyConfig {
+    /// 骚扰内容
+    pub harassment: SafetyThreshold,
+    /// 仇恨言论
+    pub hate_speech: SafetyThreshold,
+    /// 色情内容
+    pub sexually_explicit: SafetyThreshold,
+    /// 危险内容
+    pub dangerous_content: SafetyThreshold,
+}
+
+/// 安全阈值，达到阈值的内容会被拦截
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq, Display, EnumString)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case", ascii_case_insensitive)]
+pub(crate) enum SafetyThreshold {
+    /// 使用 API 的默认值
+    #[default]
+    Default,
+    /// 关闭安全过滤
+    Off,
+    /// 不拦截任何内容，但仍返回安全评级
+    BlockNone,
+    /// 只拦截概率高的内容
+    OnlyHigh,
+    /// 拦截概率中等及以上的内容
+    MediumAndAbove,
+    /// 拦截概率低及以上的内容
+    LowAndAbove,
+}
+
+impl SafetyConfig {
+    /// 转换为请求中的安全设置，不传递使用默认值的类别
+    pub fn settings(&self) -> Vec<Value> {
+        [
+            ("HARM_CATEGORY_HARASSMENT", self.harassment),
+            ("HARM_CATEGORY_HATE_SPEECH", self.hate_speech),
+            ("HARM_CATEGORY_SEXUALLY_EXPLICIT", self.sexually_explicit),
+            ("HARM_CATEGORY_DANGEROUS_CONTENT", self.dangerous_content),
+        ]
+        .into_iter()
+        .filter_map(|(category, threshold)| {
+            let threshold = threshold.api_value()?;
+            Some(serde_json::json!({ "category": category, "threshold": threshold }))
+        })
+        .collect()
+    }
+}
+
+impl SafetyThreshold {
+    /// API 中对应的阈值，使用默认值时返回空
+    fn api_value(self) -> Option<&'static str> {
+        match self {
+            Self::Default => None,
+            Self::Off => Some("OFF"),
+            Self::BlockNone => Some("BLOCK_NONE"),
+            Self::OnlyHigh => Some("BLOCK_ONLY_HIGH"),
+            Self::MediumAndAbove => Some("BLOCK_MEDIUM_AND_ABOVE"),
+            Self::LowAndAbove => Some("BLOCK_LOW_AND_ABOVE"),
+        }
+    }
+}
+
+/// 本地工具设置，启用后模型可以请求调用本地工具，每次调用前都需要确认
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub(crate) struct ToolConfig {
+    /// 是否向模型声明本地工具
+    pub enabled: bool,
+    /// 允许执行的命令名称，为空时不声明执行命令的工具
+    pub allowed_commands: Vec<String>,
 }
 
-/// 配置文件名
-const CONFIG_FILE_NAME: &str = "gemini.json";
+/// 模型价格，单位为美元每百万 token
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub(crate) struct ModelPrice {
+    /// 请求内容的价格
+    pub input: f64,
+    /// 回复内容的价格
+    pub output: f64,
+}
+
+impl ModelPrice {
+    /// 估算消耗指定 token 用量的费用
+    pub fn cost(&self, usage: TokenUsage) -> f64 {
+        (usage.prompt_tokens as f64 * self.input + usage.response_tokens as f64 * self.output) / 1_000_000.0
+    }
+}
+
+/// 配置方案，保存模型、系统指令与生成配置
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub(crate) struct Profile {
+    pub name: String,
+    pub model: LanguageModel,
+    pub system_instruction: Option<String>,
+    pub options: GenerationConfig,
+    #[serde(default)]
+    pub response_schema: Option<Value>,
+}
+
+/// 会话中保存的生成配置里回复格式约束的键名，与请求中的字段名一致
+const RESPONSE_SCHEMA_KEY: &str = "responseSchema";
+
+impl Profile {
+    /// 保存到会话中的生成配置，JSON 格式，回复格式约束一并保存
+    pub fn options_json(&self) -> String {
+        let mut options = serde_json::to_value(&self.options).unwrap_or_default();
+        if let (Value::Object(map), Some(schema)) = (&mut options, &self.response_schema) {
+            map.insert(RESPONSE_SCHEMA_KEY.into(), schema.clone());
+        }
+        options.to_string()
+    }
+
+    /// 解析会话中保存的生成配置，返回生成配置与回复格式约束
+    pub fn parse_options(options: &str) -> (GenerationConfig, Option<Value>) {
+        let mut options = serde_json::from_str::<Value>(options).unwrap_or_default();
+        let response_schema = options.as_object_mut().and_then(|map| map.remove(RESPONSE_SCHEMA_KEY));
+        (serde_json::from_value(options).unwrap_or_default(), response_schema)
+    }
+}
+
+/// 默认配置方案名称
+pub(crate) const DEFAULT_PROFILE_NAME: &str = "default";
+
+fn default_profile_name() -> String {
+    DEFAULT_PROFILE_NAME.into()
+}
 
-/// 保存配置
-pub(crate) fn save_config(store_data: StoreData) -> Result<()> {
+/// 密钥来源
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum KeySource {
+    /// 不保存密钥，从环境变量中读取
+    #[default]
+    None,
+    /// 使用口令加密后保存在配置文件中
+    Encrypted(EncryptedKey),
+    /// 执行外部命令，使用其输出作为密钥，例如 `pass show gemini`
+    Command { command: String },
+    /// 读取文件内容作为密钥
+    File { path: String },
+}
+
+impl StoreData {
+    /// 是否需要输入口令才能解密密钥
+    pub fn is_locked(&self) -> bool {
+        matches!(self.key_source, KeySource::Encrypted(_)) && self.key.is_empty() && passphrase().is_none()
+    }
+
+    /// 是否为旧版本保存的明文密钥，需要设置口令后加密
+    pub fn is_plaintext(&self) -> bool {
+        self.key_source == KeySource::None && !self.key.is_empty()
+    }
+
+    /// 保存配置时是否需要加密密钥
+    fn should_encrypt(&self) -> bool {
+        !self.key.is_empty() && matches!(self.key_source, KeySource::None | KeySource::Encrypted(_))
+    }
+
+    /// 是否需要先设置口令才能保存配置
+    pub fn needs_passphrase(&self) -> bool {
+        self.should_encrypt() && passphrase().is_none()
+    }
+
+    /// 根据密钥来源获取密钥，并填充到 `key` 中
+    pub fn resolve_key(&mut self) -> Result<()> {
+        if !self.key.is_empty() {
+            return Ok(());
+        }
+        self.key = match &self.key_source {
+            KeySource::None => match std::env::var(ENV_NAME) {
+                Ok(key) if !key.is_empty() => key,
+                _ => bail!("No key configured, set the environment variable {}", ENV_NAME),
+            },
+            KeySource::Encrypted(encrypted_key) => match passphrase() {
+                Some(passphrase) => decrypt_key(encrypted_key, &passphrase)?,
+                None => bail!("Passphrase required"),
+            },
+            KeySource::Command { command } => run_key_command(command)?,
+            KeySource::File { path } => read_key_file(path)?,
+        };
+        Ok(())
+    }
+}
+
+/// 配置方案相关操作
+impl StoreData {
+    /// 所有配置方案的名称，按名称排序
+    pub fn profile_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.profiles.iter().map(|profile| profile.name.clone()).collect();
+        names.push(self.profile.clone());
+        names.sort();
+        names
+    }
+
+    /// 将当前配置方案的内容转换为配置方案
+    pub fn current_profile(&self) -> Profile {
+        Profile {
+            name: self.profile.clone(),
+            model: self.model.clone(),
+            system_instruction: self.system_instruction.clone(),
+            options: self.options.clone(),
+            response_schema: self.response_schema.clone(),
+        }
+    }
+
+    /// 是否存在同名的配置方案
+    fn has_profile(&self, name: &str) -> bool {
+        self.profile == name || self.profiles.iter().any(|profile| profile.name == name)
+    }
+
+    /// 切换到指定的配置方案，当前配置方案保存到配置方案列表中
+    pub fn switch_profile(&mut self, name: &str) -> Result<()> {
+        if self.profile == name {
+            return Ok(());
+        }
+        let Some(index) = self.profiles.iter().position(|profile| profile.name == name) else {
+            bail!("Profile {} does not exist", name)
+        };
+        let profile = self.profiles.remove(index);
+        self.profiles.push(self.current_profile());
+        self.profile = profile.name;
+        self.model = profile.model;
+        self.system_instruction = profile.system_instruction;
+        self.options = profile.options;
+        self.response_schema = profile.response_schema;
+        Ok(())
+    }
+
+    /// 复制当前配置方案创建新的配置方案，并切换到新的配置方案
+    pub fn create_profile(&mut self, name: &str) -> Result<()> {
+        let name = name.trim();
+        if name.is_empty() {
+            bail!("Profile name is empty")
+        }
+        if self.has_profile(name) {
+            bail!("Profile {} already exists", name)
+        }
+        self.profiles.push(self.current_profile());
+        self.profile = name.into();
+        Ok(())
+    }
+
+    /// 重命名当前配置方案
+    pub fn rename_profile(&mut self, name: &str) -> Result<()> {
+        let name = name.trim();
+        if name.is_empty() {
+            bail!("Profile name is empty")
+        }
+        if name != self.profile && self.has_profile(name) {
+            bail!("Profile {} already exists", name)
+        }
+        self.profile = name.into();
+        Ok(())
+    }
+
+    /// 删除配置方案，当前使用的配置方案不能删除
+    pub fn delete_profile(&mut self, name: &str) -> Result<()> {
+        if self.profile == name {
+            bail!("Cannot delete the profile in use")
+        }
+        self.profiles.retain(|profile| profile.name != name);
+        Ok(())
+    }
+}
+
+/// 存储密钥的环境变量名
+pub(crate) const ENV_NAME: &str = "GEMINI_KEY";
+
+/// 保存配置，密钥使用口令加密后保存，没有可用的口令时返回错误
+///
+/// 密钥来源为外部命令或文件时不保存密钥，密钥为空时保留原有的密钥来源
+pub(crate) fn save_config(mut store_data: StoreData) -> Result<()> {
+    if store_data.should_encrypt() {
+        match passphrase() {
+            Some(passphrase) => {
+                store_data.key_source = KeySource::Encrypted(encrypt_key(&store_data.key, &passphrase)?);
+            }
+            None => bail!("Passphrase required to save the key"),
+        }
+    }
     let json_data = serde_json::to_string(&store_data).unwrap();
+    write_config(json_data)
+}
+
+/// 修改配置中除密钥以外的内容，不需要口令，保存的密钥保持不变
+pub(crate) fn update_config(f: impl FnOnce(&mut StoreData) -> Result<()>) -> Result<()> {
+    let mut config = read_config()?;
+    f(&mut config)?;
+    let mut value = serde_json::to_value(&config)?;
+    // 旧版本保存的明文密钥在设置口令之前保持不变
+    if config.is_plaintext() {
+        value["key"] = Value::String(config.key.clone());
+    }
+    write_config(serde_json::to_string(&value)?)
+}
+
+/// 写入配置文件
+fn write_config(json_data: String) -> Result<()> {
     let config_file = get_config_file()?;
     let mut file = File::create(config_file)?;
     file.write_all(json_data.as_bytes())?;
     Ok(())
 }
 
-/// 保存数据库版本变更
-pub(crate) fn update_db_version_into_profile() -> Result<()> {
-    let mut config = read_config()?;
-    config.db_version = Some(current_db_version());
-    save_config(config)
+/// 读取配置，不会获取密钥，需要时调用 [`StoreData::resolve_key`]
+pub(crate) fn read_config() -> Result<StoreData> {
+    Ok(serde_json::from_str::<StoreData>(&read_config_file()?)?)
 }
 
-/// 读取配置
-pub(crate) fn read_config() -> Result<StoreData> {
+/// 读取配置文件内容
+fn read_config_file() -> Result<String> {
     let config_file = get_config_file()?;
     if config_file.exists() {
         let mut file = File::open(config_file)?;
         let mut contents = String::new();
         file.read_to_string(&mut contents)?;
-        Ok(serde_json::from_str::<StoreData>(&contents)?)
+        Ok(contents)
     } else {
         bail!("配置文件不存在")
     }
@@ -55,6 +405,9 @@ pub(crate) fn read_config() -> Result<StoreData> {
 
 /// 获取配置文件路径
 fn get_config_file() -> Result<PathBuf> {
-    let exe_path = env::current_exe()?;
-    Ok(exe_path.parent().unwrap().join(CONFIG_FILE_NAME))
+    let config_file = config_file();
+    if let Some(parent) = config_file.parent() {
+        create_dir_all(parent)?;
+    }
+    Ok(config_file)
 }
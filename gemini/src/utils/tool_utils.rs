@@ -0,0 +1,341 @@
+use std::{
+    fs::{read_dir, File},
+    io::Read,
+    process::{Command, Stdio},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail, Result};
+use chrono::Local;
+use gemini_api::body::{Content, Part, Role};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::model::view::{FunctionCall, FunctionResponse};
+
+use super::{attachment_utils::TEXT_ATTACHMENT_LIMIT, store_utils::ToolConfig};
+
+/// 工具调用在历史记录中的片段类型，数据为 `functionCall` 的 JSON
+const FUNCTION_CALL_TYPE: &str = "application/x-function-call";
+/// 工具执行结果在历史记录中的片段类型，数据为 `functionResponse` 的 JSON
+const FUNCTION_RESPONSE_TYPE: &str = "application/x-function-response";
+/// 命令输出保留的最大字节数
+const OUTPUT_LIMIT: usize = 64 * 1024;
+/// 命令执行的超时时间
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+/// 界面中每个执行结果最多展示的行数
+const PREVIEW_LINES: usize = 8;
+
+/// 向模型声明的本地工具，未启用时返回空
+pub fn tool_declarations(config: &ToolConfig) -> Option<Value> {
+    if !config.enabled {
+        return None;
+    }
+    let mut declarations = vec![
+        json!({
+            "name": "read_file",
+            "description": "Read a text file on the user's computer",
+            "parameters": {
+                "type": "object",
+                "properties": { "path": { "type": "string", "description": "Path of the file" } },
+                "required": ["path"]
+            }
+        }),
+        json!({
+            "name": "list_directory",
+            "description": "List the entries of a directory on the user's computer, directories end with a slash",
+            "parameters": {
+                "type": "object",
+                "properties": { "path": { "type": "string", "description": "Path of the directory" } },
+                "required": ["path"]
+            }
+        }),
+        json!({
+            "name": "get_current_time",
+            "description": "Get the current local date and time of the user's computer"
+        }),
+    ];
+    // 没有允许执行的命令时不声明该工具
+    if !config.allowed_commands.is_empty() {
+        declarations.push(json!({
+            "name": "run_command",
+            "description": format!(
+                "Run a command on the user's computer without a shell, the program must be one of: {}",
+                config.allowed_commands.join(", ")
+            ),
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "command": {
+                        "type": "string",
+                        "description": "The program followed by its arguments, separated by spaces"
+                    }
+                },
+                "required": ["command"]
+            }
+        }));
+    }
+    Some(json!([{ "functionDeclarations": declarations }]))
+}
+
+/// 执行工具调用，成功时结果为 `output`，失败时为 `error`
+pub fn execute(call: &FunctionCall, config: &ToolConfig) -> FunctionResponse {
+    let result = match call.name.as_str() {
+        "read_file" => string_arg(call, "path").and_then(|path| read_file(&path)),
+        "list_directory" => string_arg(call, "path").and_then(|path| list_directory(&path)),
+        "run_command" => string_arg(call, "command").and_then(|command| run_command(&command, config)),
+        "get_current_time" => Ok(Value::String(Local::now().to_rfc3339())),
+        name => Err(anyhow!("Unknown tool: {}", name)),
+    };
+    let response = match result {
+        Ok(output) => json!({ "output": output }),
+        Err(e) => json!({ "error": e.to_string() }),
+    };
+    FunctionResponse {
+        name: call.name.clone(),
+        response,
+    }
+}
+
+/// 用户拒绝执行时返回给模型的结果
+pub fn decline(call: &FunctionCall) -> FunctionResponse {
+    FunctionResponse {
+        name: call.name.clone(),
+        response: json!({ "error": "The user declined to run this tool" }),
+    }
+}
+
+/// 工具执行过程中请求被取消时的结果，工具可能已经执行完成
+pub fn interrupted(call: &FunctionCall) -> FunctionResponse {
+    FunctionResponse {
+        name: call.name.clone(),
+        response: json!({ "error": "The request was cancelled while this tool was running, its result is unknown" }),
+    }
+}
+
+/// 读取字符串类型的参数
+fn string_arg(call: &FunctionCall, name: &str) -> Result<String> {
+    match call.args.get(name).and_then(Value::as_str) {
+        Some(value) => Ok(value.into()),
+        None => bail!("Missing argument: {}", name),
+    }
+}
+
+/// 读取文本文件，超出大小限制的部分被截断
+fn read_file(path: &str) -> Result<Value> {
+    let mut data = Vec::new();
+    File::open(path)?
+        .take(TEXT_ATTACHMENT_LIMIT as u64 + 1)
+        .read_to_end(&mut data)?;
+    Ok(Value::String(truncate(data, TEXT_ATTACHMENT_LIMIT)))
+}
+
+/// 列出目录中的所有条目，按名称排序
+fn list_directory(path: &str) -> Result<Value> {
+    let mut names = read_dir(path)?
+        .map(|entry| -> Result<String> {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            Ok(if entry.file_type()?.is_dir() {
+                format!("{}/", name)
+            } else {
+                name
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    names.sort();
+    Ok(Value::String(names.join("\n")))
+}
+
+/// 执行允许的命令，不经过 shell，参数以空白字符分隔，超时后结束进程
+fn run_command(command: &str, config: &ToolConfig) -> Result<Value> {
+    let mut args = command.split_whitespace();
+    let Some(program) = args.next() else {
+        bail!("Empty command")
+    };
+    if !config.allowed_commands.iter().any(|allowed| allowed == program) {
+        bail!("Command not allowed: {}", program)
+    }
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    // 在独立线程中读取输出，避免输出过多时阻塞子进程
+    let stdout = child.stdout.take().map(read_output);
+    let stderr = child.stderr.take().map(read_output);
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if started.elapsed() > COMMAND_TIMEOUT {
+            let _ = child.kill();
+            let _ = child.wait();
+            bail!("Command timed out after {} seconds", COMMAND_TIMEOUT.as_secs())
+        }
+        thread::sleep(Duration::from_millis(50));
+    };
+    let output = |handle: Option<JoinHandle<Vec<u8>>>| {
+        truncate(
+            handle.and_then(|handle| handle.join().ok()).unwrap_or_default(),
+            OUTPUT_LIMIT,
+        )
+    };
+    Ok(json!({
+        "exit_code": status.code(),
+        "stdout": output(stdout),
+        "stderr": output(stderr),
+    }))
+}
+
+/// 在新线程中读取全部输出
+fn read_output<R: Read + Send + 'static>(mut reader: R) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut data = Vec::new();
+        let _ = reader.read_to_end(&mut data);
+        data
+    })
+}
+
+/// 转换为文本，超出限制的部分被截断
+fn truncate(mut data: Vec<u8>, limit: usize) -> String {
+    let truncated = data.len() > limit;
+    data.truncate(limit);
+    let mut text = String::from_utf8_lossy(&data).into_owned();
+    if truncated {
+        text.push_str("\n[truncated]");
+    }
+    text
+}
+
+/// 模型请求调用工具的消息，添加到历史记录中
+pub fn function_call_content(calls: &[FunctionCall]) -> Content {
+    Content {
+        parts: calls.iter().map(|call| tool_part(FUNCTION_CALL_TYPE, call)).collect(),
+        role: Some(Role::Model),
+    }
+}
+
+/// 工具执行结果的消息，作为用户消息发送给模型
+pub fn function_response_content(responses: &[FunctionResponse]) -> Content {
+    Content {
+        parts: responses
+            .iter()
+            .map(|response| tool_part(FUNCTION_RESPONSE_TYPE, response))
+            .collect(),
+        role: Some(Role::User),
+    }
+}
+
+/// 历史记录中的工具片段，以自定义类型的数据片段保存
+fn tool_part<T: Serialize>(mime_type: &str, value: &T) -> Part {
+    Part::InlineData {
+        mime_type: mime_type.into(),
+        data: serde_json::to_string(value).unwrap_or_default(),
+    }
+}
+
+/// 工具调用或执行结果片段中的 JSON 数据，其它片段返回空
+pub fn tool_data(part: &Part) -> Option<&str> {
+    match part {
+        Part::InlineData { mime_type, data }
+            if [FUNCTION_CALL_TYPE, FUNCTION_RESPONSE_TYPE].contains(&mime_type.as_str()) =>
+        {
+            Some(data)
+        }
+        _ => None,
+    }
+}
+
+/// 消息中的所有工具执行结果
+pub fn function_responses(content: &Content) -> Vec<FunctionResponse> {
+    content
+        .parts
+        .iter()
+        .filter_map(|part| match part {
+            Part::InlineData { mime_type, data } if mime_type == FUNCTION_RESPONSE_TYPE => {
+                serde_json::from_str(data).ok()
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// 消息是否为工具执行结果，执行结果属于上一轮对话
+pub fn is_function_response(content: &Content) -> bool {
+    content
+        .parts
+        .iter()
+        .any(|part| matches!(part, Part::InlineData { mime_type, .. } if mime_type == FUNCTION_RESPONSE_TYPE))
+}
+
+/// 转换为请求中的消息，工具片段转换为 `functionCall` 与 `functionResponse`
+pub fn request_content(content: &Content) -> Value {
+    let parts = content
+        .parts
+        .iter()
+        .map(|part| match part {
+            Part::InlineData { mime_type, data } if mime_type == FUNCTION_CALL_TYPE => {
+                json!({ "functionCall": serde_json::from_str::<Value>(data).unwrap_or_default() })
+            }
+            Part::InlineData { mime_type, data } if mime_type == FUNCTION_RESPONSE_TYPE => {
+                json!({ "functionResponse": serde_json::from_str::<Value>(data).unwrap_or_default() })
+            }
+            part => serde_json::to_value(part).unwrap_or_default(),
+        })
+        .collect();
+    let mut value = serde_json::to_value(content).unwrap_or_default();
+    value["parts"] = Value::Array(parts);
+    value
+}
+
+/// 单个工具调用的展示内容，例如 `read_file {"path":"Cargo.toml"}`
+pub fn describe_call(call: &FunctionCall) -> String {
+    match &call.args {
+        Value::Null => call.name.clone(),
+        Value::Object(args) if args.is_empty() => call.name.clone(),
+        args => format!("{} {}", call.name, args),
+    }
+}
+
+/// 工具调用消息的展示内容，模型在调用前回复的文本放在最前面
+pub fn describe_calls(text: &str, calls: &[FunctionCall]) -> String {
+    let text = text.trim();
+    let mut lines = Vec::new();
+    if !text.is_empty() {
+        lines.push(text.to_owned());
+    }
+    lines.extend(calls.iter().map(describe_call));
+    lines.join("\n")
+}
+
+/// 工具执行结果消息的展示内容，每个结果只展示前几行
+pub fn describe_responses(responses: &[FunctionResponse]) -> String {
+    responses
+        .iter()
+        .map(|response| {
+            let (label, value) = match response.response.get("error") {
+                Some(error) => ("error", error),
+                None => ("output", response.response.get("output").unwrap_or(&response.response)),
+            };
+            let text = match value {
+                Value::String(text) => text.clone(),
+                value => serde_json::to_string_pretty(value).unwrap_or_default(),
+            };
+            let lines = text.lines().collect::<Vec<_>>();
+            let mut description = format!("{} {}:", response.name, label);
+            for line in lines.iter().take(PREVIEW_LINES) {
+                description.push('\n');
+                description.push_str(line);
+            }
+            if lines.len() > PREVIEW_LINES {
+                description.push_str(&format!("\n... {} more lines", lines.len() - PREVIEW_LINES));
+            }
+            description
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
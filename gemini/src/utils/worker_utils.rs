@@ -0,0 +1,181 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    thread,
+};
+
+use anyhow::Result;
+use gemini_api::{body::Content, model::blocking::Gemini};
+
+use crate::model::view::{FinishInfo, FunctionCall, FunctionResponse, TokenUsage};
+
+use super::context_utils::{fit_context, ContextOptions, ContextWindow};
+use super::gemini_utils::{send_message_stream, user_content, RequestOptions};
+use super::tool_utils::{decline, execute, function_response_content, function_responses};
+
+/// 后台任务返回给界面的事件
+pub enum WorkerEvent {
+    /// 本地工具执行完成，携带执行结果，执行结果随后发送给模型
+    ToolResults(Vec<FunctionResponse>),
+    /// 已确定本次请求使用的上下文，携带被移出上下文的历史记录
+    Context(ContextWindow),
+    /// 请求已发出，携带本次发送的用户消息
+    Started(Content),
+    /// 接收到一段回复
+    Chunk(String),
+    /// 接收到本次请求的 token 用量，流式响应中可能多次返回，以最后一次为准
+    Usage(TokenUsage),
+    /// 接收到回复结束的原因与安全评级
+    Finish(FinishInfo),
+    /// 模型请求调用本地工具
+    FunctionCalls(Vec<FunctionCall>),
+    /// 回复接收完成
+    Done,
+    /// 请求失败或接收过程中被中断，提供错误信息
+    Failed(String),
+}
+
+/// 后台请求任务，在独立线程中发送请求并通过通道逐段返回回复，界面线程不会被阻塞
+#[derive(Default)]
+pub struct ChatWorker {
+    /// 当前请求的事件接收端
+    receiver: Option<Receiver<WorkerEvent>>,
+    /// 当前请求的取消标志
+    cancel_flag: Arc<AtomicBool>,
+}
+
+impl ChatWorker {
+    /// 发送消息，如果已有正在进行的请求，则先取消该请求
+    pub fn send(
+        &mut self,
+        gemini: Gemini,
+        message: String,
+        image_paths: Vec<String>,
+        context: ContextOptions,
+        options: RequestOptions,
+    ) {
+        self.spawn(gemini, context, options, move || user_content(message, image_paths));
+    }
+
+    /// 重新发送已构建好的用户消息，用于重新生成或继续生成回复
+    pub fn resend(&mut self, gemini: Gemini, content: Content, context: ContextOptions, options: RequestOptions) {
+        self.spawn(gemini, context, options, move || Ok(content));
+    }
+
+    /// 执行或拒绝模型请求调用的工具，并将执行结果发送给模型
+    pub fn run_tools(
+        &mut self,
+        gemini: Gemini,
+        calls: Vec<FunctionCall>,
+        approved: bool,
+        context: ContextOptions,
+        options: RequestOptions,
+    ) {
+        let tools = options.tools.clone();
+        self.spawn(gemini, context, options, move || {
+            let responses = calls
+                .iter()
+                .map(|call| if approved { execute(call, &tools) } else { decline(call) })
+                .collect::<Vec<_>>();
+            Ok(function_response_content(&responses))
+        });
+    }
+
+    /// 在新线程中执行请求
+    fn spawn<F>(&mut self, gemini: Gemini, context: ContextOptions, options: RequestOptions, content: F)
+    where
+        F: FnOnce() -> Result<Content> + Send + 'static,
+    {
+        self.cancel();
+        let (tx, rx) = mpsc::channel();
+        let cancel_flag = Arc::new(AtomicBool::new(false));
+        self.receiver = Some(rx);
+        self.cancel_flag = cancel_flag.clone();
+        thread::spawn(move || request(gemini, context, options, content, tx, cancel_flag));
+    }
+
+    /// 取消当前请求，之后该请求产生的事件都将被丢弃
+    pub fn cancel(&mut self) {
+        self.cancel_flag.store(true, Ordering::Relaxed);
+        self.receiver = None;
+    }
+
+    /// 非阻塞地获取下一个事件
+    pub fn try_recv(&self) -> Option<WorkerEvent> {
+        self.receiver.as_ref()?.try_recv().ok()
+    }
+}
+
+/// 在后台线程中执行请求
+fn request<F>(
+    gemini: Gemini,
+    context: ContextOptions,
+    options: RequestOptions,
+    content: F,
+    tx: Sender<WorkerEvent>,
+    cancel_flag: Arc<AtomicBool>,
+) where
+    F: FnOnce() -> Result<Content>,
+{
+    let content = match content() {
+        Ok(content) => content,
+        Err(e) => {
+            let _ = tx.send(WorkerEvent::Failed(e.to_string()));
+            return;
+        }
+    };
+    // 先展示工具执行结果，之后的请求失败时也不会丢失
+    let responses = function_responses(&content);
+    if !responses.is_empty() && tx.send(WorkerEvent::ToolResults(responses)).is_err() {
+        return;
+    }
+    // 超出上下文预算时裁剪历史记录
+    let (gemini, window) = fit_context(&gemini, &content, &context, &options);
+    if cancel_flag.load(Ordering::Relaxed) || tx.send(WorkerEvent::Context(window)).is_err() {
+        return;
+    }
+    let stream = match send_message_stream(&gemini, content.clone(), &options) {
+        Ok(stream) => stream,
+        Err(e) => {
+            let _ = tx.send(WorkerEvent::Failed(e.to_string()));
+            return;
+        }
+    };
+    let _ = tx.send(WorkerEvent::Started(content));
+    for chunk in stream {
+        // 请求已被取消，丢弃响应流以断开连接
+        if cancel_flag.load(Ordering::Relaxed) {
+            return;
+        }
+        let event = match chunk {
+            Ok(chunk) => {
+                if let Some(usage) = chunk.usage {
+                    if tx.send(WorkerEvent::Usage(usage)).is_err() {
+                        return;
+                    }
+                }
+                if let Some(finish) = chunk.finish {
+                    if tx.send(WorkerEvent::Finish(finish)).is_err() {
+                        return;
+                    }
+                }
+                if !chunk.function_calls.is_empty()
+                    && tx.send(WorkerEvent::FunctionCalls(chunk.function_calls)).is_err()
+                {
+                    return;
+                }
+                WorkerEvent::Chunk(chunk.text)
+            }
+            Err(e) => WorkerEvent::Failed(e.to_string()),
+        };
+        let failed = matches!(event, WorkerEvent::Failed(_));
+        // 接收端已被丢弃，说明请求已被取消
+        if tx.send(event).is_err() || failed {
+            return;
+        }
+    }
+    let _ = tx.send(WorkerEvent::Done);
+}
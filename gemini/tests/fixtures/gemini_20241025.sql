@@ -0,0 +1,41 @@
+-- ----------------------------
+-- database created by the version released on 2024-10-25, before migrations were recorded in the database
+-- ----------------------------
+CREATE TABLE "gemini_conversation" (
+  "conversation_id" TEXT NOT NULL,
+  "conversation_title" TEXT,
+  "conversation_start_time" DATE,
+  "conversation_modify_time" DATE,
+  PRIMARY KEY ("conversation_id")
+);
+CREATE TABLE "gemini_message_record" (
+  "record_id" TEXT NOT NULL,
+  "conversation_id" TEXT,
+  "record_content" TEXT,
+  "record_time" DATE,
+  "record_sender" TEXT,
+  "sort_index" INTEGER DEFAULT 0,
+  PRIMARY KEY ("record_id"),
+  FOREIGN KEY ("conversation_id") REFERENCES "gemini_conversation" ("conversation_id") ON DELETE CASCADE ON UPDATE CASCADE
+);
+CREATE TABLE "gemini_image_record" (
+  "image_record_id" TEXT NOT NULL,
+  "record_id" TEXT,
+  "image_path" TEXT,
+  "image_type" TEXT,
+  PRIMARY KEY ("image_record_id"),
+  FOREIGN KEY ("record_id") REFERENCES "gemini_message_record" ("record_id") ON DELETE CASCADE ON UPDATE CASCADE
+);
+CREATE INDEX "idx_gemini_conversation_modify_time" ON "gemini_conversation" ("conversation_modify_time");
+
+INSERT INTO "gemini_conversation" VALUES
+  ('conversation-a', 'Rust ownership', '2024-10-26 09:00:00.000000 +08:00', '2024-10-26 09:05:00.000000 +08:00'),
+  ('conversation-b', 'Describe a picture', '2024-10-27 20:00:00.000000 +08:00', '2024-10-27 20:01:00.000000 +08:00');
+INSERT INTO "gemini_message_record" VALUES
+  ('record-a1', 'conversation-a', 'What is ownership?', '2024-10-26 09:00:00.000000 +08:00', 'User', 0),
+  ('record-a2', 'conversation-a', 'Every value has a single owner.', '2024-10-26 09:00:05.000000 +08:00', 'Bot', 1),
+  ('record-a3', 'conversation-a', 'And borrowing?', '2024-10-26 09:04:00.000000 +08:00', 'User', 2),
+  ('record-a4', 'conversation-a', 'References let you use a value without taking ownership.', '2024-10-26 09:05:00.000000 +08:00', 'Bot', 3),
+  ('record-b1', 'conversation-b', 'What is in this picture?', '2024-10-27 20:00:00.000000 +08:00', 'User', 0);
+INSERT INTO "gemini_image_record" VALUES
+  ('image-b1', 'record-b1', '/home/user/picture.png', 'image/jpeg');